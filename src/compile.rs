@@ -10,8 +10,8 @@ use lru::LruCache;
 use crate::error::{AclError, Result};
 use crate::geo::GeoLoader;
 use crate::matcher::{AllMatcher, CidrMatcher, DomainMatcher, HostMatcher, IpMatcher, Matcher};
-use crate::parser::parse_proto_port;
-use crate::types::{CacheKey, HostInfo, MatchResult, Protocol, TextRule};
+use crate::parser::{parse_hijack_address, parse_proto_port};
+use crate::types::{CacheKey, HijackTarget, HostInfo, MatchResult, Protocol, TextRule};
 
 /// Cache entry storing verification data and the cached result.
 /// CacheKey is a u64 hash, so we store the original query fields here
@@ -22,7 +22,7 @@ struct CacheEntry<O> {
     ipv6: Option<Ipv6Addr>,
     protocol: Protocol,
     port: u16,
-    result: Option<(O, Option<IpAddr>)>,
+    result: Option<(O, Option<HijackTarget>, usize)>,
 }
 
 impl<O> CacheEntry<O> {
@@ -51,11 +51,21 @@ pub struct CompiledRule<O> {
     pub(crate) start_port: u16,
     /// End port (inclusive)
     pub(crate) end_port: u16,
-    /// Hijack IP address
-    pub hijack_ip: Option<IpAddr>,
+    /// Hijack target (host and/or port rewrite)
+    pub(crate) hijack: Option<HijackTarget>,
 }
 
 impl<O> CompiledRule<O> {
+    /// Hijack target (host and/or port rewrite), if any.
+    pub fn hijack(&self) -> Option<&HijackTarget> {
+        self.hijack.as_ref()
+    }
+
+    /// Hijack IP address, set when the hijack target host is an IP literal.
+    pub fn hijack_ip(&self) -> Option<IpAddr> {
+        self.hijack.as_ref().and_then(HijackTarget::ip)
+    }
+
     /// Check if this rule matches the given host, protocol, and port
     pub fn matches(&self, host: &HostInfo, proto: Protocol, port: u16) -> bool {
         // Check protocol
@@ -122,13 +132,13 @@ impl<O: Clone> CompiledRuleSet<O> {
             let mut cache = self.cache.lock();
             if let Some(entry) = cache.get(&key) {
                 if entry.matches_query(host, proto, port) {
-                    return entry
-                        .result
-                        .clone()
-                        .map(|(outbound, hijack_ip)| MatchResult {
+                    return entry.result.clone().map(|(outbound, hijack, rule_index)| {
+                        MatchResult {
                             outbound,
-                            hijack_ip,
-                        });
+                            hijack,
+                            rule_index,
+                        }
+                    });
                 }
                 // Hash collision (extremely rare) — treat as cache miss
             }
@@ -149,7 +159,9 @@ impl<O: Clone> CompiledRuleSet<O> {
                     ipv6: host.ipv6,
                     protocol: proto,
                     port,
-                    result: result.as_ref().map(|r| (r.outbound.clone(), r.hijack_ip)),
+                    result: result
+                        .as_ref()
+                        .map(|r| (r.outbound.clone(), r.hijack.clone(), r.rule_index)),
                 },
             );
         }
@@ -159,11 +171,12 @@ impl<O: Clone> CompiledRuleSet<O> {
 
     /// Find a matching rule without caching
     fn find_match(&self, host: &HostInfo, proto: Protocol, port: u16) -> Option<MatchResult<O>> {
-        for rule in &self.rules {
+        for (rule_index, rule) in self.rules.iter().enumerate() {
             if rule.matches(host, proto, port) {
                 return Some(MatchResult {
                     outbound: rule.outbound.clone(),
-                    hijack_ip: rule.hijack_ip,
+                    hijack: rule.hijack.clone(),
+                    rule_index,
                 });
             }
         }
@@ -237,14 +250,11 @@ fn compile_rule<O: Clone>(
     };
 
     // Parse hijack address
-    let hijack_ip = if let Some(ref addr) = rule.hijack_address {
-        Some(
-            addr.parse::<IpAddr>()
-                .map_err(|_| AclError::InvalidIp(addr.clone()))?,
-        )
-    } else {
-        None
-    };
+    let hijack = rule
+        .hijack_address
+        .as_deref()
+        .map(parse_hijack_address)
+        .transpose()?;
 
     Ok(CompiledRule {
         outbound,
@@ -252,7 +262,7 @@ fn compile_rule<O: Clone>(
         protocol,
         start_port,
         end_port,
-        hijack_ip,
+        hijack,
     })
 }
 
@@ -456,7 +466,49 @@ proxy(all)
         assert!(result.is_some());
         let result = result.unwrap();
         assert_eq!(result.outbound, "DIRECT");
-        assert_eq!(result.hijack_ip, Some("127.0.0.1".parse().unwrap()));
+        assert_eq!(result.hijack_ip(), Some("127.0.0.1".parse().unwrap()));
+    }
+
+    #[test]
+    fn test_match_rule_index() {
+        let rules = parse_rules("direct(*.cn)\nproxy(all)").unwrap();
+
+        let mut outbounds = HashMap::new();
+        outbounds.insert("direct".to_string(), "DIRECT");
+        outbounds.insert("proxy".to_string(), "PROXY");
+
+        let compiled = compile(
+            &rules,
+            &outbounds,
+            NonZeroUsize::new(1024).unwrap(),
+            &NilGeoLoader,
+        )
+        .unwrap();
+
+        let host = HostInfo::from_name("example.com");
+        assert_eq!(
+            compiled
+                .match_host(&host, Protocol::TCP, 443)
+                .unwrap()
+                .rule_index(),
+            1
+        );
+        // Cached results keep the rule index
+        assert_eq!(
+            compiled
+                .match_host(&host, Protocol::TCP, 443)
+                .unwrap()
+                .rule_index(),
+            1
+        );
+        let host = HostInfo::from_name("baidu.cn");
+        assert_eq!(
+            compiled
+                .match_host(&host, Protocol::TCP, 443)
+                .unwrap()
+                .rule_index(),
+            0
+        );
     }
 
     #[test]
@@ -692,7 +744,7 @@ proxy(all)
         assert!(result.is_some());
         let result = result.unwrap();
         assert_eq!(result.outbound, "PROXY");
-        assert_eq!(result.hijack_ip, Some("1.2.3.4".parse().unwrap()));
+        assert_eq!(result.hijack_ip(), Some("1.2.3.4".parse().unwrap()));
 
         // Wrong protocol should not match
         let result = compiled.match_host(&host, Protocol::UDP, 443);
@@ -848,4 +900,69 @@ proxy(all)
             Ok(_) => panic!("Expected UnknownOutbound error"),
         }
     }
+
+    #[test]
+    fn test_hijack_port_and_hostname_targets() {
+        use crate::types::HijackHost;
+
+        let text = "direct(all, udp/53, 127.0.0.1:5353)\ndirect(all, tcp/53, :5353)\nproxy(suffix:pkg.example, tcp/443, mirror.internal)";
+        let rules = parse_rules(text).unwrap();
+
+        let mut outbounds = HashMap::new();
+        outbounds.insert("direct".to_string(), "DIRECT");
+        outbounds.insert("proxy".to_string(), "PROXY");
+
+        let compiled = compile(
+            &rules,
+            &outbounds,
+            NonZeroUsize::new(1024).unwrap(),
+            &NilGeoLoader,
+        )
+        .unwrap();
+
+        let host = HostInfo::from_name("dns.google");
+        // Query twice so the cached path is exercised as well
+        for _ in 0..2 {
+            let result = compiled.match_host(&host, Protocol::UDP, 53).unwrap();
+            assert_eq!(result.hijack_ip(), Some("127.0.0.1".parse().unwrap()));
+            assert_eq!(result.hijack().unwrap().port, Some(5353));
+        }
+
+        let result = compiled.match_host(&host, Protocol::TCP, 53).unwrap();
+        assert_eq!(result.hijack_ip(), None);
+        let hijack = result.hijack().unwrap();
+        assert_eq!(hijack.host, None);
+        assert_eq!(hijack.port, Some(5353));
+
+        let host = HostInfo::from_name("cdn.pkg.example");
+        for _ in 0..2 {
+            let result = compiled.match_host(&host, Protocol::TCP, 443).unwrap();
+            assert_eq!(result.outbound, "PROXY");
+            assert_eq!(result.hijack_ip(), None);
+            let hijack = result.hijack().unwrap();
+            assert_eq!(
+                hijack.host,
+                Some(HijackHost::Name("mirror.internal".to_string()))
+            );
+            assert_eq!(hijack.port, None);
+        }
+    }
+
+    #[test]
+    fn test_compile_invalid_hijack_address() {
+        let rules = parse_rules("direct(all, udp/53, 1.2.3.4:notaport)").unwrap();
+        let mut outbounds = HashMap::new();
+        outbounds.insert("direct".to_string(), "DIRECT");
+
+        let result = compile(
+            &rules,
+            &outbounds,
+            NonZeroUsize::new(1024).unwrap(),
+            &NilGeoLoader,
+        );
+        assert!(matches!(
+            result,
+            Err(AclError::ParseError { line: Some(1), .. })
+        ));
+    }
 }
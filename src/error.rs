@@ -1,7 +1,10 @@
 use thiserror::Error;
 
 /// Classifies outbound connection errors for programmatic matching.
+///
+/// New kinds may be added in minor releases; matches need a wildcard arm.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
 pub enum OutboundErrorKind {
     /// Connection to the remote host failed
     ConnectionFailed,
@@ -19,6 +22,8 @@ pub enum OutboundErrorKind {
     InvalidInput,
     /// Feature not supported (e.g., UDP over HTTP proxy)
     Unsupported,
+    /// Outbound temporarily disabled by an open circuit breaker
+    CircuitOpen,
 }
 
 /// Classifies GeoIP/GeoSite errors for programmatic matching.
@@ -70,6 +70,13 @@
 //! - `udp/53` - UDP port 53
 //! - `*/80` - Any protocol, port 80
 //! - `tcp/8000-9000` - TCP port range
+//!
+//! ## Hijack Address
+//!
+//! - `127.0.0.1` / `::1` - Rewrite destination IP, keep port
+//! - `127.0.0.1:5353` / `[::1]:5353` - Rewrite IP and port
+//! - `:5353` - Rewrite port only
+//! - `mirror.local[:8080]` - Rewrite to a hostname (resolved again), optionally with port
 
 pub mod compile;
 pub mod error;
@@ -91,21 +98,31 @@ pub use matcher::{
     AllMatcher, CidrMatcher, DomainEntry, DomainMatcher, DomainType, GeoIpMatcher, GeoSiteMatcher,
     HostMatcher, IpMatcher, Matcher,
 };
-pub use parser::{parse_proto_port, parse_rules, parse_rules_from_file};
-pub use types::{HostInfo, MatchResult, Protocol, TextRule};
+pub use parser::{parse_hijack_address, parse_proto_port, parse_rules, parse_rules_from_file};
+pub use types::{HijackHost, HijackTarget, HostInfo, MatchResult, Protocol, TextRule};
 
 // Re-export outbound types
 pub use outbound::{
-    Addr, Direct, DirectMode, DirectOptions, Http, Outbound, Reject, ResolveInfo, Socks5, TcpConn,
-    UdpConn, DEFAULT_DIALER_TIMEOUT,
+    Addr, BalanceStrategy, CircuitBreaker, CircuitState, Direct, DirectMode, DirectOptions,
+    FailoverGroup, HealthCheck, Hosts, Http, HttpAuthScheme, MemberStatus, Outbound, OutboundGroup,
+    RateLimitScope, RateLimited, RateLimiter, Reject, RejectMode, ResolveInfo, Retry, Selector,
+    Shadowsocks, ShadowsocksCipher, Socks4, Socks5, Socks5Bind, SourceStrategy, TcpConn,
+    TlsOptions, UdpConn, UrlTestGroup, DEFAULT_DIALER_TIMEOUT,
 };
 
 // Re-export async outbound types
 #[cfg(feature = "async")]
-pub use outbound::{AsyncOutbound, AsyncTcpConn, AsyncUdpConn, TokioTcpConn, TokioUdpConn};
+pub use outbound::{
+    AsyncCircuitBreaker, AsyncFailoverGroup, AsyncOutbound, AsyncOutboundGroup, AsyncRateLimited,
+    AsyncRetry, AsyncSelector, AsyncSocks5Bind, AsyncTcpConn, AsyncUdpConn, AsyncUrlTestGroup,
+    TokioTcpConn, TokioUdpConn,
+};
 
 // Re-export router types
-pub use router::{OutboundEntry, Router, RouterOptions, DEFAULT_CACHE_SIZE};
+pub use router::{
+    Accounting, AccountingSnapshot, OutboundEntry, Router, RouterOptions, TrafficStats,
+    DEFAULT_CACHE_SIZE,
+};
 
 // Re-export async router types
 #[cfg(feature = "async")]
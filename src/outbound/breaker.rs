@@ -0,0 +1,622 @@
+//! Circuit breaker wrapper for outbounds.
+//!
+//! [`CircuitBreaker`] tracks the dial failure rate of another outbound over
+//! a sliding window. Once the rate crosses a threshold the circuit opens and
+//! dials fail immediately with `OutboundErrorKind::CircuitOpen` instead of
+//! waiting for the upstream's connect timeout. After a cool-down the circuit
+//! half-opens and lets a limited number of trial dials through: a success
+//! closes it again, a failure re-opens it.
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use parking_lot::Mutex;
+
+use crate::error::{AclError, OutboundErrorKind, Result};
+
+use super::{Addr, Outbound, TcpConn, UdpConn};
+
+#[cfg(feature = "async")]
+use super::{AsyncOutbound, AsyncTcpConn, AsyncUdpConn};
+#[cfg(feature = "async")]
+use async_trait::async_trait;
+
+/// Default failure rate that opens the circuit
+pub const DEFAULT_FAILURE_RATE: f64 = 0.5;
+
+/// Default length of the sliding window
+pub const DEFAULT_BREAKER_WINDOW: Duration = Duration::from_secs(60);
+
+/// Default minimum number of dials in the window before the rate is evaluated
+pub const DEFAULT_MIN_REQUESTS: u32 = 10;
+
+/// Default time the circuit stays open before half-opening
+pub const DEFAULT_OPEN_DURATION: Duration = Duration::from_secs(30);
+
+/// Number of buckets the sliding window is divided into.
+const WINDOW_BUCKETS: u32 = 10;
+
+/// State of a circuit breaker.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CircuitState {
+    /// Dials pass through and are counted
+    Closed,
+    /// Dials fail fast
+    Open,
+    /// A limited number of trial dials pass through
+    HalfOpen,
+}
+
+/// Dial counts for one slice of the window.
+#[derive(Debug)]
+struct Bucket {
+    start: Instant,
+    total: u32,
+    failures: u32,
+}
+
+#[derive(Debug)]
+struct Breaker {
+    state: CircuitState,
+    /// When an open circuit half-opens
+    open_until: Instant,
+    /// Trial dials in flight while half-open
+    trials: u32,
+    buckets: VecDeque<Bucket>,
+}
+
+/// An admitted dial, recorded through [`finish`](Admission::finish).
+///
+/// A trial dropped before it finishes (its future was cancelled, e.g. by a
+/// timeout) releases its slot and counts as a failed trial, so a half-open
+/// circuit never waits on a trial that will not report back.
+struct Admission<'a, T: ?Sized> {
+    breaker: &'a CircuitBreaker<T>,
+    trial: bool,
+    finished: bool,
+}
+
+impl<T: ?Sized> Admission<'_, T> {
+    /// Record the result of the dial.
+    fn finish<C>(mut self, result: &Result<C>) {
+        self.finished = true;
+        let failed = matches!(
+            result,
+            Err(AclError::OutboundError {
+                kind: OutboundErrorKind::ConnectionFailed | OutboundErrorKind::Timeout,
+                ..
+            })
+        );
+        self.breaker.record(self.trial, failed);
+    }
+}
+
+impl<T: ?Sized> Drop for Admission<'_, T> {
+    fn drop(&mut self) {
+        if !self.finished && self.trial {
+            self.breaker.record(true, true);
+        }
+    }
+}
+
+/// Outbound wrapper that stops dialing a failing upstream for a while.
+///
+/// Generic over the outbound trait; wrap an `Arc<dyn Outbound>` or an
+/// `Arc<dyn AsyncOutbound>`. Only `ConnectionFailed` and `Timeout` errors
+/// count as failures; other errors say nothing about the upstream's health
+/// and count as successful dials.
+pub struct CircuitBreaker<T: ?Sized = dyn Outbound> {
+    inner: Arc<T>,
+    failure_rate: f64,
+    window: Duration,
+    min_requests: u32,
+    open_duration: Duration,
+    half_open_trials: u32,
+    breaker: Mutex<Breaker>,
+}
+
+/// Async circuit breaker (type alias for `CircuitBreaker<dyn AsyncOutbound>`).
+#[cfg(feature = "async")]
+pub type AsyncCircuitBreaker = CircuitBreaker<dyn AsyncOutbound>;
+
+impl<T: ?Sized> fmt::Debug for CircuitBreaker<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CircuitBreaker")
+            .field("state", &self.state())
+            .field("failure_rate", &self.failure_rate)
+            .field("window", &self.window)
+            .field("min_requests", &self.min_requests)
+            .field("open_duration", &self.open_duration)
+            .field("half_open_trials", &self.half_open_trials)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<T: ?Sized> CircuitBreaker<T> {
+    /// Wrap `inner` with the default thresholds.
+    pub fn new(inner: Arc<T>) -> Self {
+        Self {
+            inner,
+            failure_rate: DEFAULT_FAILURE_RATE,
+            window: DEFAULT_BREAKER_WINDOW,
+            min_requests: DEFAULT_MIN_REQUESTS,
+            open_duration: DEFAULT_OPEN_DURATION,
+            half_open_trials: 1,
+            breaker: Mutex::new(Breaker {
+                state: CircuitState::Closed,
+                open_until: Instant::now(),
+                trials: 0,
+                buckets: VecDeque::new(),
+            }),
+        }
+    }
+
+    /// Set the failure rate (`0.0..=1.0`) that opens the circuit.
+    pub fn with_failure_rate(mut self, rate: f64) -> Self {
+        self.failure_rate = rate.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Set the length of the sliding window.
+    pub fn with_window(mut self, window: Duration) -> Self {
+        self.window = window;
+        self
+    }
+
+    /// Set how many dials the window must hold before the rate is evaluated
+    /// (at least 1).
+    pub fn with_min_requests(mut self, min_requests: u32) -> Self {
+        self.min_requests = min_requests.max(1);
+        self
+    }
+
+    /// Set how long the circuit stays open before half-opening.
+    pub fn with_open_duration(mut self, duration: Duration) -> Self {
+        self.open_duration = duration;
+        self
+    }
+
+    /// Set how many trial dials may run at once while half-open (at least 1).
+    pub fn with_half_open_trials(mut self, trials: u32) -> Self {
+        self.half_open_trials = trials.max(1);
+        self
+    }
+
+    /// Get the wrapped outbound.
+    pub fn inner(&self) -> &Arc<T> {
+        &self.inner
+    }
+
+    /// Get the current state.
+    ///
+    /// An open circuit whose cool-down has passed is reported as half-open.
+    pub fn state(&self) -> CircuitState {
+        let breaker = self.breaker.lock();
+        match breaker.state {
+            CircuitState::Open if Instant::now() >= breaker.open_until => CircuitState::HalfOpen,
+            state => state,
+        }
+    }
+
+    /// Close the circuit and forget the recorded dials.
+    pub fn reset(&self) {
+        let mut breaker = self.breaker.lock();
+        breaker.state = CircuitState::Closed;
+        breaker.trials = 0;
+        breaker.buckets.clear();
+    }
+
+    /// Admit a dial or fail fast.
+    fn admit(&self) -> Result<Admission<'_, T>> {
+        let mut breaker = self.breaker.lock();
+        let now = Instant::now();
+        if breaker.state == CircuitState::Open {
+            if now < breaker.open_until {
+                return Err(AclError::OutboundError {
+                    kind: OutboundErrorKind::CircuitOpen,
+                    message: format!(
+                        "Circuit breaker open, retry in {:?}",
+                        breaker.open_until - now
+                    ),
+                });
+            }
+            breaker.state = CircuitState::HalfOpen;
+            breaker.trials = 0;
+        }
+        if breaker.state == CircuitState::HalfOpen {
+            if breaker.trials >= self.half_open_trials {
+                return Err(AclError::OutboundError {
+                    kind: OutboundErrorKind::CircuitOpen,
+                    message: "Circuit breaker half-open, trial dial in progress".to_string(),
+                });
+            }
+            breaker.trials += 1;
+        }
+        Ok(Admission {
+            breaker: self,
+            trial: breaker.state == CircuitState::HalfOpen,
+            finished: false,
+        })
+    }
+
+    /// Record the outcome of an admitted dial.
+    fn record(&self, trial: bool, failed: bool) {
+        let mut breaker = self.breaker.lock();
+        let now = Instant::now();
+
+        if trial {
+            breaker.trials = breaker.trials.saturating_sub(1);
+            // A reset or another trial may already have moved the state on
+            if breaker.state != CircuitState::HalfOpen {
+                return;
+            }
+            if failed {
+                self.open(&mut breaker, now);
+            } else {
+                breaker.state = CircuitState::Closed;
+                breaker.buckets.clear();
+            }
+            return;
+        }
+        if breaker.state != CircuitState::Closed {
+            return;
+        }
+
+        // Drop buckets that slid out of the window, then count this dial
+        while breaker
+            .buckets
+            .front()
+            .is_some_and(|b| now.duration_since(b.start) >= self.window)
+        {
+            breaker.buckets.pop_front();
+        }
+        let span = self.window / WINDOW_BUCKETS;
+        match breaker.buckets.back_mut() {
+            Some(bucket) if now.duration_since(bucket.start) < span => {
+                bucket.total += 1;
+                bucket.failures += failed as u32;
+            }
+            _ => breaker.buckets.push_back(Bucket {
+                start: now,
+                total: 1,
+                failures: failed as u32,
+            }),
+        }
+
+        let (total, failures) = breaker
+            .buckets
+            .iter()
+            .fold((0, 0), |(t, f), b| (t + b.total, f + b.failures));
+        if failed
+            && total >= self.min_requests
+            && failures as f64 >= self.failure_rate * total as f64
+        {
+            self.open(&mut breaker, now);
+        }
+    }
+
+    fn open(&self, breaker: &mut Breaker, now: Instant) {
+        breaker.state = CircuitState::Open;
+        breaker.open_until = now + self.open_duration;
+        breaker.buckets.clear();
+    }
+}
+
+impl Outbound for CircuitBreaker<dyn Outbound> {
+    fn dial_tcp(&self, addr: &mut Addr) -> Result<Box<dyn TcpConn>> {
+        let admission = self.admit()?;
+        let result = self.inner.dial_tcp(addr);
+        admission.finish(&result);
+        result
+    }
+
+    fn dial_udp(&self, addr: &mut Addr) -> Result<Box<dyn UdpConn>> {
+        let admission = self.admit()?;
+        let result = self.inner.dial_udp(addr);
+        admission.finish(&result);
+        result
+    }
+}
+
+#[cfg(feature = "async")]
+#[async_trait]
+impl AsyncOutbound for CircuitBreaker<dyn AsyncOutbound> {
+    async fn dial_tcp(&self, addr: &mut Addr) -> Result<Box<dyn AsyncTcpConn>> {
+        let admission = self.admit()?;
+        let result = self.inner.dial_tcp(addr).await;
+        admission.finish(&result);
+        result
+    }
+
+    async fn dial_udp(&self, addr: &mut Addr) -> Result<Box<dyn AsyncUdpConn>> {
+        let admission = self.admit()?;
+        let result = self.inner.dial_udp(addr).await;
+        admission.finish(&result);
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::outbound::{Reject, StdTcpConn};
+    use std::net::{TcpListener, TcpStream};
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+    /// Upstream that connects while `up` and is refused otherwise.
+    struct Upstream {
+        listener: TcpListener,
+        up: AtomicBool,
+        dials: AtomicUsize,
+    }
+
+    impl Outbound for Upstream {
+        fn dial_tcp(&self, _addr: &mut Addr) -> Result<Box<dyn TcpConn>> {
+            self.dials.fetch_add(1, Ordering::Relaxed);
+            if !self.up.load(Ordering::Relaxed) {
+                return Err(AclError::OutboundError {
+                    kind: OutboundErrorKind::ConnectionFailed,
+                    message: "refused".to_string(),
+                });
+            }
+            let stream = TcpStream::connect(self.listener.local_addr().unwrap())?;
+            Ok(Box::new(StdTcpConn::new(stream)))
+        }
+
+        fn dial_udp(&self, addr: &mut Addr) -> Result<Box<dyn UdpConn>> {
+            Outbound::dial_udp(&Reject::new(), addr)
+        }
+    }
+
+    fn upstream(up: bool) -> Arc<Upstream> {
+        Arc::new(Upstream {
+            listener: TcpListener::bind("127.0.0.1:0").unwrap(),
+            up: AtomicBool::new(up),
+            dials: AtomicUsize::new(0),
+        })
+    }
+
+    fn dial(breaker: &CircuitBreaker) -> Result<Box<dyn TcpConn>> {
+        Outbound::dial_tcp(breaker, &mut Addr::new("example.com", 80))
+    }
+
+    fn is_circuit_open<C>(result: &Result<C>) -> bool {
+        matches!(
+            result,
+            Err(AclError::OutboundError {
+                kind: OutboundErrorKind::CircuitOpen,
+                ..
+            })
+        )
+    }
+
+    #[test]
+    fn test_breaker_opens_at_failure_rate() {
+        let up = upstream(true);
+        let breaker = CircuitBreaker::new(up.clone() as Arc<dyn Outbound>)
+            .with_min_requests(4)
+            .with_failure_rate(0.5);
+
+        dial(&breaker).unwrap();
+        dial(&breaker).unwrap();
+        up.up.store(false, Ordering::Relaxed);
+        assert!(dial(&breaker).is_err());
+        assert_eq!(breaker.state(), CircuitState::Closed);
+        // 2 of 4 dials failed: 50% reaches the threshold
+        assert!(!is_circuit_open(&dial(&breaker)));
+        assert_eq!(breaker.state(), CircuitState::Open);
+
+        // Open circuit fails fast without touching the upstream
+        assert!(is_circuit_open(&dial(&breaker)));
+        assert_eq!(up.dials.load(Ordering::Relaxed), 4);
+    }
+
+    #[test]
+    fn test_breaker_needs_min_requests() {
+        let breaker =
+            CircuitBreaker::new(upstream(false) as Arc<dyn Outbound>).with_min_requests(3);
+        assert!(dial(&breaker).is_err());
+        assert!(dial(&breaker).is_err());
+        assert_eq!(breaker.state(), CircuitState::Closed);
+        assert!(dial(&breaker).is_err());
+        assert_eq!(breaker.state(), CircuitState::Open);
+    }
+
+    #[test]
+    fn test_breaker_window_forgets_old_failures() {
+        let breaker = CircuitBreaker::new(upstream(false) as Arc<dyn Outbound>)
+            .with_min_requests(2)
+            .with_window(Duration::from_millis(50));
+        assert!(dial(&breaker).is_err());
+        std::thread::sleep(Duration::from_millis(60));
+        assert!(dial(&breaker).is_err());
+        assert_eq!(breaker.state(), CircuitState::Closed);
+    }
+
+    #[test]
+    fn test_breaker_ignores_non_connectivity_errors() {
+        let breaker =
+            CircuitBreaker::new(Arc::new(Reject::new()) as Arc<dyn Outbound>).with_min_requests(1);
+        for _ in 0..5 {
+            assert!(dial(&breaker).is_err());
+        }
+        assert_eq!(breaker.state(), CircuitState::Closed);
+    }
+
+    #[test]
+    fn test_breaker_half_open_trial() {
+        let up = upstream(false);
+        let breaker = CircuitBreaker::new(up.clone() as Arc<dyn Outbound>)
+            .with_min_requests(1)
+            .with_open_duration(Duration::from_millis(30));
+
+        assert!(dial(&breaker).is_err());
+        assert_eq!(breaker.state(), CircuitState::Open);
+        std::thread::sleep(Duration::from_millis(40));
+        assert_eq!(breaker.state(), CircuitState::HalfOpen);
+
+        // Failed trial re-opens the circuit
+        assert!(!is_circuit_open(&dial(&breaker)));
+        assert_eq!(breaker.state(), CircuitState::Open);
+        assert!(is_circuit_open(&dial(&breaker)));
+
+        // Successful trial closes it
+        std::thread::sleep(Duration::from_millis(40));
+        up.up.store(true, Ordering::Relaxed);
+        dial(&breaker).unwrap();
+        assert_eq!(breaker.state(), CircuitState::Closed);
+        assert_eq!(up.dials.load(Ordering::Relaxed), 3);
+    }
+
+    #[test]
+    fn test_breaker_limits_concurrent_trials() {
+        let breaker = CircuitBreaker::new(upstream(false) as Arc<dyn Outbound>)
+            .with_min_requests(1)
+            .with_open_duration(Duration::ZERO);
+        assert!(dial(&breaker).is_err());
+
+        let first = breaker.admit().unwrap();
+        assert!(first.trial);
+        assert!(is_circuit_open(&breaker.admit()));
+        first.finish::<()>(&Ok(()));
+        assert_eq!(breaker.state(), CircuitState::Closed);
+        assert!(!breaker.admit().unwrap().trial);
+    }
+
+    #[test]
+    fn test_breaker_reset() {
+        let breaker =
+            CircuitBreaker::new(upstream(false) as Arc<dyn Outbound>).with_min_requests(1);
+        assert!(dial(&breaker).is_err());
+        assert_eq!(breaker.state(), CircuitState::Open);
+        breaker.reset();
+        assert_eq!(breaker.state(), CircuitState::Closed);
+    }
+}
+
+#[cfg(all(test, feature = "async"))]
+mod async_tests {
+    use super::*;
+    use crate::outbound::Direct;
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+    /// Upstream that hangs while `hang` is set and is refused otherwise.
+    #[derive(Default)]
+    struct Hanging {
+        hang: AtomicBool,
+        dials: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl AsyncOutbound for Hanging {
+        async fn dial_tcp(&self, _addr: &mut Addr) -> Result<Box<dyn AsyncTcpConn>> {
+            self.dials.fetch_add(1, Ordering::Relaxed);
+            if self.hang.load(Ordering::Relaxed) {
+                tokio::time::sleep(Duration::from_secs(10)).await;
+            }
+            Err(AclError::OutboundError {
+                kind: OutboundErrorKind::ConnectionFailed,
+                message: "Failed to connect: connection refused".to_string(),
+            })
+        }
+
+        async fn dial_udp(&self, addr: &mut Addr) -> Result<Box<dyn AsyncUdpConn>> {
+            AsyncOutbound::dial_udp(&crate::outbound::Reject::new(), addr).await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_async_breaker_counts_direct_timeouts() {
+        use crate::outbound::DirectOptions;
+
+        // TEST-NET-1 is blackholed (Timeout) or refused (ConnectionFailed),
+        // depending on the network; both must count as failures
+        let direct = Direct::with_options(DirectOptions {
+            timeout: Some(Duration::from_millis(100)),
+            ..Default::default()
+        })
+        .unwrap();
+        let breaker = AsyncCircuitBreaker::new(Arc::new(direct) as Arc<dyn AsyncOutbound>)
+            .with_min_requests(1);
+        let mut addr = Addr::new("192.0.2.1", 80);
+        let err = AsyncOutbound::dial_tcp(&breaker, &mut addr)
+            .await
+            .err()
+            .unwrap();
+        assert!(matches!(
+            err,
+            AclError::OutboundError {
+                kind: OutboundErrorKind::Timeout | OutboundErrorKind::ConnectionFailed,
+                ..
+            }
+        ));
+        assert_eq!(breaker.state(), CircuitState::Open);
+    }
+
+    #[tokio::test]
+    async fn test_async_breaker_dropped_trial_releases_slot() {
+        let upstream = Arc::new(Hanging::default());
+        let breaker = AsyncCircuitBreaker::new(upstream.clone() as Arc<dyn AsyncOutbound>)
+            .with_min_requests(1)
+            .with_open_duration(Duration::from_millis(50));
+        let mut addr = Addr::new("example.com", 80);
+        assert!(AsyncOutbound::dial_tcp(&breaker, &mut addr).await.is_err());
+        assert_eq!(breaker.state(), CircuitState::Open);
+
+        // The half-open trial is cancelled mid-dial and counts as a failure
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        upstream.hang.store(true, Ordering::Relaxed);
+        let trial = AsyncOutbound::dial_tcp(&breaker, &mut addr);
+        assert!(tokio::time::timeout(Duration::from_millis(20), trial)
+            .await
+            .is_err());
+        assert_eq!(breaker.state(), CircuitState::Open);
+
+        // After the cool-down the next dial is admitted as a new trial
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        upstream.hang.store(false, Ordering::Relaxed);
+        let err = AsyncOutbound::dial_tcp(&breaker, &mut addr)
+            .await
+            .err()
+            .unwrap();
+        assert!(matches!(
+            err,
+            AclError::OutboundError {
+                kind: OutboundErrorKind::ConnectionFailed,
+                ..
+            }
+        ));
+        assert_eq!(upstream.dials.load(Ordering::Relaxed), 3);
+    }
+
+    #[tokio::test]
+    async fn test_async_breaker_fails_fast() {
+        // Nothing listens on port 1, so every dial is refused
+        let breaker = AsyncCircuitBreaker::new(Arc::new(Direct::new()) as Arc<dyn AsyncOutbound>)
+            .with_min_requests(2);
+        for _ in 0..2 {
+            let mut addr = Addr::new("127.0.0.1", 1);
+            let err = AsyncOutbound::dial_tcp(&breaker, &mut addr)
+                .await
+                .err()
+                .unwrap();
+            assert!(!err.to_string().contains("Circuit breaker"));
+        }
+        assert_eq!(breaker.state(), CircuitState::Open);
+
+        let mut addr = Addr::new("127.0.0.1", 1);
+        let err = AsyncOutbound::dial_tcp(&breaker, &mut addr)
+            .await
+            .err()
+            .unwrap();
+        assert!(matches!(
+            err,
+            AclError::OutboundError {
+                kind: OutboundErrorKind::CircuitOpen,
+                ..
+            }
+        ));
+    }
+}
@@ -0,0 +1,450 @@
+//! Connection to a proxy server, directly or through another outbound.
+//!
+//! Proxy outbounds (`Socks4`, `Socks5`, `Http`, `Shadowsocks`) open their
+//! server connection through a [`ProxyDialer`]. By default it opens a plain
+//! TCP socket; with a dialer outbound set, the server is reached through
+//! that outbound's `dial_tcp`, which allows chaining proxies to any depth.
+//! An optional DSCP value marks the plain server connection and the UDP
+//! relay sockets of the proxy.
+
+use std::net::{SocketAddr, TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::error::{AclError, OutboundErrorKind, Result};
+
+use super::{apply_dscp, check_dscp, Addr, Outbound, StdTcpConn, TcpConn};
+
+#[cfg(feature = "async")]
+use super::{AsyncOutbound, AsyncTcpConn, TokioTcpConn};
+#[cfg(feature = "async")]
+use tokio::net::TcpStream as TokioTcpStream;
+
+/// Most helper threads one dialer keeps running chained dials at once.
+const MAX_DIAL_THREADS: usize = 32;
+
+/// How a proxy outbound reaches its server.
+#[derive(Clone, Default)]
+pub(crate) struct ProxyDialer {
+    /// Outbound used by the sync `Outbound` impl
+    dialer: Option<Arc<dyn Outbound>>,
+    /// Outbound used by the `AsyncOutbound` impl
+    #[cfg(feature = "async")]
+    async_dialer: Option<Arc<dyn AsyncOutbound>>,
+    /// DSCP value for sockets opened to the server
+    dscp: Option<u8>,
+    /// Helper threads still running a chained dial, including ones whose
+    /// caller already timed out
+    dial_threads: Arc<AtomicUsize>,
+}
+
+impl ProxyDialer {
+    /// Set the outbound used by sync dials.
+    pub(crate) fn set(&mut self, dialer: Arc<dyn Outbound>) {
+        self.dialer = Some(dialer);
+    }
+
+    /// Set the outbound used by async dials.
+    #[cfg(feature = "async")]
+    pub(crate) fn set_async(&mut self, dialer: Arc<dyn AsyncOutbound>) {
+        self.async_dialer = Some(dialer);
+    }
+
+    /// Mark sockets opened to the server with a DSCP value (0-63).
+    pub(crate) fn set_dscp(&mut self, dscp: u8) -> Result<()> {
+        check_dscp(dscp)?;
+        self.dscp = Some(dscp);
+        Ok(())
+    }
+
+    /// Mark a UDP relay socket with the configured DSCP value, if any.
+    pub(crate) fn mark_udp<'a>(
+        &self,
+        socket: impl Into<socket2::SockRef<'a>>,
+        ipv6: bool,
+    ) -> Result<()> {
+        match self.dscp {
+            Some(dscp) => apply_dscp(&socket.into(), ipv6, dscp),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns true if sync dials go through another outbound.
+    pub(crate) fn is_chained(&self) -> bool {
+        self.dialer.is_some()
+    }
+
+    /// Returns true if async dials go through another outbound.
+    #[cfg(feature = "async")]
+    pub(crate) fn is_async_chained(&self) -> bool {
+        self.async_dialer.is_some()
+    }
+
+    /// Connect to the proxy server at `server` (`host:port`).
+    ///
+    /// A chained dial runs on a helper thread so `timeout` bounds the whole
+    /// chain; a connection that completes after the deadline is dropped.
+    /// Helper threads are capped at [`MAX_DIAL_THREADS`] per dialer; past
+    /// the cap, dials run on the caller's thread and are bounded only by the
+    /// dialer outbound's own timeouts.
+    pub(crate) fn dial(&self, server: &str, timeout: Duration) -> Result<Box<dyn TcpConn>> {
+        if let Some(ref dialer) = self.dialer {
+            let mut addr = server_addr(server)?;
+            if self.dial_threads.fetch_add(1, Ordering::AcqRel) >= MAX_DIAL_THREADS {
+                self.dial_threads.fetch_sub(1, Ordering::AcqRel);
+                return dialer.dial_tcp(&mut addr);
+            }
+            let dialer = Arc::clone(dialer);
+            let dial_threads = Arc::clone(&self.dial_threads);
+            let (tx, rx) = mpsc::channel();
+            std::thread::spawn(move || {
+                let _ = tx.send(dialer.dial_tcp(&mut addr));
+                dial_threads.fetch_sub(1, Ordering::AcqRel);
+            });
+            return match rx.recv_timeout(timeout) {
+                Ok(result) => result,
+                Err(RecvTimeoutError::Timeout) => Err(AclError::OutboundError {
+                    kind: OutboundErrorKind::Timeout,
+                    message: "Connection timeout".to_string(),
+                }),
+                Err(RecvTimeoutError::Disconnected) => Err(AclError::OutboundError {
+                    kind: OutboundErrorKind::ConnectionFailed,
+                    message: "Dialer outbound panicked".to_string(),
+                }),
+            };
+        }
+
+        let addr: SocketAddr = server
+            .to_socket_addrs()
+            .map_err(|e| AclError::OutboundError {
+                kind: OutboundErrorKind::DnsFailed,
+                message: format!("Failed to resolve proxy address: {}", e),
+            })?
+            .next()
+            .ok_or_else(|| AclError::OutboundError {
+                kind: OutboundErrorKind::DnsFailed,
+                message: "No address resolved for proxy".to_string(),
+            })?;
+
+        Ok(Box::new(StdTcpConn::new(self.connect(addr, timeout)?)))
+    }
+
+    /// Open a plain TCP connection to the resolved server address.
+    fn connect(&self, addr: SocketAddr, timeout: Duration) -> Result<TcpStream> {
+        match self.dscp {
+            Some(dscp) => {
+                let socket = socket2::Socket::new(
+                    socket2::Domain::for_address(addr),
+                    socket2::Type::STREAM,
+                    Some(socket2::Protocol::TCP),
+                )
+                .map_err(|e| AclError::OutboundError {
+                    kind: OutboundErrorKind::Io,
+                    message: format!("Failed to create socket: {}", e),
+                })?;
+                apply_dscp(&socket, addr.is_ipv6(), dscp)?;
+                socket
+                    .connect_timeout(&addr.into(), timeout)
+                    .map(|()| socket.into())
+            }
+            None => TcpStream::connect_timeout(&addr, timeout),
+        }
+        .map_err(|e| AclError::OutboundError {
+            kind: OutboundErrorKind::ConnectionFailed,
+            message: format!("Failed to connect to proxy: {}", e),
+        })
+    }
+
+    /// Async: Connect to the proxy server at `server` (`host:port`).
+    /// Uses tokio async DNS to avoid blocking the runtime.
+    #[cfg(feature = "async")]
+    pub(crate) async fn async_dial(
+        &self,
+        server: &str,
+        timeout: Duration,
+    ) -> Result<Box<dyn AsyncTcpConn>> {
+        if let Some(ref dialer) = self.async_dialer {
+            let mut addr = server_addr(server)?;
+            return tokio::time::timeout(timeout, dialer.dial_tcp(&mut addr))
+                .await
+                .map_err(|_| AclError::OutboundError {
+                    kind: OutboundErrorKind::Timeout,
+                    message: "Connection timeout".to_string(),
+                })?;
+        }
+
+        let addr: SocketAddr = tokio::net::lookup_host(server)
+            .await
+            .map_err(|e| AclError::OutboundError {
+                kind: OutboundErrorKind::DnsFailed,
+                message: format!("Failed to resolve proxy address: {}", e),
+            })?
+            .next()
+            .ok_or_else(|| AclError::OutboundError {
+                kind: OutboundErrorKind::DnsFailed,
+                message: "No address resolved for proxy".to_string(),
+            })?;
+
+        let connected = match self.dscp {
+            Some(dscp) => {
+                let socket = if addr.is_ipv6() {
+                    tokio::net::TcpSocket::new_v6()
+                } else {
+                    tokio::net::TcpSocket::new_v4()
+                }
+                .map_err(|e| AclError::OutboundError {
+                    kind: OutboundErrorKind::Io,
+                    message: format!("Failed to create socket: {}", e),
+                })?;
+                apply_dscp(&socket2::SockRef::from(&socket), addr.is_ipv6(), dscp)?;
+                tokio::time::timeout(timeout, socket.connect(addr)).await
+            }
+            None => tokio::time::timeout(timeout, TokioTcpStream::connect(addr)).await,
+        };
+        let stream = connected
+            .map_err(|_| AclError::OutboundError {
+                kind: OutboundErrorKind::Timeout,
+                message: "Connection timeout".to_string(),
+            })?
+            .map_err(|e| AclError::OutboundError {
+                kind: OutboundErrorKind::ConnectionFailed,
+                message: format!("Failed to connect to proxy: {}", e),
+            })?;
+        Ok(Box::new(TokioTcpConn::new(stream)))
+    }
+}
+
+/// Split a `host:port` / `[v6]:port` server address into an [`Addr`].
+fn server_addr(server: &str) -> Result<Addr> {
+    let invalid = || AclError::OutboundError {
+        kind: OutboundErrorKind::InvalidInput,
+        message: format!("Invalid proxy address: {}", server),
+    };
+    let (host, port) = server.rsplit_once(':').ok_or_else(invalid)?;
+    let host = match host.strip_prefix('[') {
+        Some(v6) => v6.strip_suffix(']').ok_or_else(invalid)?,
+        None => host,
+    };
+    if host.is_empty() {
+        return Err(invalid());
+    }
+    let port = port.parse::<u16>().map_err(|_| invalid())?;
+    Ok(Addr::new(host, port))
+}
+
+/// Error for UDP relays that cannot be carried through a dialer outbound.
+pub(crate) fn udp_not_chainable(proto: &str) -> AclError {
+    AclError::OutboundError {
+        kind: OutboundErrorKind::Unsupported,
+        message: format!("{} UDP relay cannot be chained through a dialer", proto),
+    }
+}
+
+#[cfg(test)]
+pub(super) mod test_relay {
+    use std::io::{Read, Write};
+    use std::net::{Shutdown, TcpListener, TcpStream};
+    use std::thread::JoinHandle;
+
+    /// Spawn a SOCKS4 (IPv4 only) server that accepts one CONNECT, opens the
+    /// requested connection and relays both directions until either side
+    /// closes. Returns the address it connected to.
+    pub(in crate::outbound) fn spawn_socks4_relay() -> (u16, JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let handle = std::thread::spawn(move || {
+            let (mut client, _) = listener.accept().unwrap();
+            let mut req = [0u8; 8];
+            client.read_exact(&mut req).unwrap();
+            let mut byte = [0u8; 1];
+            // USERID
+            loop {
+                client.read_exact(&mut byte).unwrap();
+                if byte[0] == 0 {
+                    break;
+                }
+            }
+            let target = format!(
+                "{}.{}.{}.{}:{}",
+                req[4],
+                req[5],
+                req[6],
+                req[7],
+                u16::from_be_bytes([req[2], req[3]])
+            );
+            let upstream = TcpStream::connect(&target).unwrap();
+            client.write_all(&[0, 0x5A, 0, 0, 0, 0, 0, 0]).unwrap();
+            relay(client, upstream);
+            target
+        });
+        (port, handle)
+    }
+
+    /// Spawn an HTTP proxy that accepts one CONNECT and relays it like
+    /// [`spawn_socks4_relay`]. Returns the CONNECT authority.
+    pub(in crate::outbound) fn spawn_http_relay() -> (u16, JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let handle = std::thread::spawn(move || {
+            let (mut client, _) = listener.accept().unwrap();
+            let mut head = Vec::new();
+            let mut byte = [0u8; 1];
+            while !head.ends_with(b"\r\n\r\n") {
+                client.read_exact(&mut byte).unwrap();
+                head.push(byte[0]);
+            }
+            let head = String::from_utf8(head).unwrap();
+            let target = head
+                .strip_prefix("CONNECT ")
+                .and_then(|rest| rest.split(' ').next())
+                .unwrap()
+                .to_string();
+            let upstream = TcpStream::connect(&target).unwrap();
+            client
+                .write_all(b"HTTP/1.1 200 Connection established\r\n\r\n")
+                .unwrap();
+            relay(client, upstream);
+            target
+        });
+        (port, handle)
+    }
+
+    /// Copy both directions until either side closes.
+    fn relay(client: TcpStream, upstream: TcpStream) {
+        let pipe = |mut from: TcpStream, mut to: TcpStream| {
+            std::thread::spawn(move || {
+                let _ = std::io::copy(&mut from, &mut to);
+                let _ = to.shutdown(Shutdown::Write);
+            })
+        };
+        let up = pipe(client.try_clone().unwrap(), upstream.try_clone().unwrap());
+        let down = pipe(upstream, client);
+        up.join().unwrap();
+        down.join().unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_server_addr() {
+        let addr = server_addr("proxy.example.com:1080").unwrap();
+        assert_eq!((addr.host.as_str(), addr.port), ("proxy.example.com", 1080));
+        let addr = server_addr("[2001:db8::1]:8080").unwrap();
+        assert_eq!((addr.host.as_str(), addr.port), ("2001:db8::1", 8080));
+        assert!(server_addr("no-port").is_err());
+        assert!(server_addr(":80").is_err());
+        assert!(server_addr("[::1:80").is_err());
+        assert!(server_addr("host:http").is_err());
+    }
+
+    #[test]
+    fn test_dial_marks_dscp() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let server = listener.local_addr().unwrap();
+
+        let mut dialer = ProxyDialer::default();
+        dialer.set_dscp(46).unwrap();
+        let stream = dialer.connect(server, Duration::from_secs(1)).unwrap();
+        assert_eq!(socket2::SockRef::from(&stream).tos_v4().unwrap(), 46 << 2);
+
+        let udp = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        dialer.mark_udp(&udp, false).unwrap();
+        assert_eq!(socket2::SockRef::from(&udp).tos_v4().unwrap(), 46 << 2);
+
+        assert!(matches!(dialer.set_dscp(64), Err(AclError::ConfigError(_))));
+        assert_eq!(dialer.dscp, Some(46));
+    }
+
+    #[test]
+    fn test_dial_through_dialer() {
+        use crate::outbound::Reject;
+
+        let mut dialer = ProxyDialer::default();
+        assert!(!dialer.is_chained());
+        dialer.set(Arc::new(Reject::new()));
+        assert!(dialer.is_chained());
+        // The dialer outbound's error is surfaced unchanged
+        let err = dialer
+            .dial("127.0.0.1:1", Duration::from_secs(1))
+            .err()
+            .unwrap();
+        let reject_err = Outbound::dial_tcp(&Reject::new(), &mut Addr::new("127.0.0.1", 1))
+            .err()
+            .unwrap();
+        assert_eq!(err.to_string(), reject_err.to_string());
+    }
+
+    /// Dialer outbound that fails after sleeping for the given time.
+    struct Slow(Duration);
+
+    impl Outbound for Slow {
+        fn dial_tcp(&self, _addr: &mut Addr) -> Result<Box<dyn TcpConn>> {
+            std::thread::sleep(self.0);
+            Err(AclError::OutboundError {
+                kind: OutboundErrorKind::ConnectionFailed,
+                message: "too late".to_string(),
+            })
+        }
+
+        fn dial_udp(&self, _addr: &mut Addr) -> Result<Box<dyn crate::outbound::UdpConn>> {
+            unreachable!()
+        }
+    }
+
+    #[test]
+    fn test_dial_through_dialer_times_out() {
+        let mut dialer = ProxyDialer::default();
+        dialer.set(Arc::new(Slow(Duration::from_millis(500))));
+        let start = std::time::Instant::now();
+        let err = dialer
+            .dial("127.0.0.1:1", Duration::from_millis(100))
+            .err()
+            .unwrap();
+        assert!(matches!(
+            err,
+            AclError::OutboundError {
+                kind: OutboundErrorKind::Timeout,
+                ..
+            }
+        ));
+        assert!(start.elapsed() < Duration::from_millis(400));
+
+        // The helper thread is released once the abandoned dial finishes
+        let deadline = std::time::Instant::now() + Duration::from_secs(5);
+        while dialer.dial_threads.load(Ordering::Acquire) != 0 {
+            assert!(std::time::Instant::now() < deadline, "helper not released");
+            std::thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    #[test]
+    fn test_dial_through_dialer_caps_helper_threads() {
+        let mut dialer = ProxyDialer::default();
+        dialer.set(Arc::new(Slow(Duration::from_millis(200))));
+        dialer
+            .dial_threads
+            .store(MAX_DIAL_THREADS, Ordering::Release);
+
+        // Past the cap the dial runs inline and outlives `timeout`
+        let err = dialer
+            .dial("127.0.0.1:1", Duration::from_millis(10))
+            .err()
+            .unwrap();
+        assert!(matches!(
+            err,
+            AclError::OutboundError {
+                kind: OutboundErrorKind::ConnectionFailed,
+                ..
+            }
+        ));
+        assert_eq!(
+            dialer.dial_threads.load(Ordering::Acquire),
+            MAX_DIAL_THREADS
+        );
+    }
+}
@@ -0,0 +1,384 @@
+//! HTTP Digest access authentication (RFC 7616 / RFC 2617).
+//!
+//! Used by the HTTP proxy outbound to answer `407 Proxy Authentication
+//! Required` challenges. Supports the `MD5`, `MD5-sess`, `SHA-256` and
+//! `SHA-256-sess` algorithms with `qop=auth` or the legacy no-qop form.
+
+use md5::Md5;
+use sha2::{Digest, Sha256};
+
+use crate::error::{AclError, OutboundErrorKind, Result};
+
+/// Hash algorithm named in a Digest challenge.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Algorithm {
+    Md5,
+    Sha256,
+}
+
+impl Algorithm {
+    fn hash(self, data: &str) -> String {
+        match self {
+            Algorithm::Md5 => hex(&Md5::digest(data.as_bytes())),
+            Algorithm::Sha256 => hex(&Sha256::digest(data.as_bytes())),
+        }
+    }
+}
+
+/// A parsed `Digest` challenge from a `Proxy-Authenticate` header.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct DigestChallenge {
+    realm: String,
+    nonce: String,
+    opaque: Option<String>,
+    /// Algorithm token as sent by the server (echoed back verbatim)
+    algorithm_name: Option<String>,
+    algorithm: Algorithm,
+    session: bool,
+    /// True if the server offered `qop=auth`
+    qop_auth: bool,
+}
+
+impl DigestChallenge {
+    /// Parse a single `Proxy-Authenticate` header value.
+    ///
+    /// Returns `None` for non-Digest schemes, challenges missing a nonce,
+    /// unknown algorithms, or a `qop` list that does not include `auth`.
+    pub(crate) fn parse(header: &str) -> Option<Self> {
+        let header = header.trim();
+        let (scheme, params) = header.split_once(char::is_whitespace)?;
+        if !scheme.eq_ignore_ascii_case("digest") {
+            return None;
+        }
+
+        let mut realm = String::new();
+        let mut nonce = None;
+        let mut opaque = None;
+        let mut algorithm_name = None;
+        let mut qop = None;
+        for (key, value) in parse_params(params) {
+            match key.to_ascii_lowercase().as_str() {
+                "realm" => realm = value,
+                "nonce" => nonce = Some(value),
+                "opaque" => opaque = Some(value),
+                "algorithm" => algorithm_name = Some(value),
+                "qop" => qop = Some(value),
+                _ => {}
+            }
+        }
+
+        let (algorithm, session) = match algorithm_name.as_deref().map(str::to_ascii_uppercase) {
+            None => (Algorithm::Md5, false),
+            Some(ref a) if a == "MD5" => (Algorithm::Md5, false),
+            Some(ref a) if a == "MD5-SESS" => (Algorithm::Md5, true),
+            Some(ref a) if a == "SHA-256" => (Algorithm::Sha256, false),
+            Some(ref a) if a == "SHA-256-SESS" => (Algorithm::Sha256, true),
+            Some(_) => return None,
+        };
+        let qop_auth = match qop {
+            None => false,
+            Some(ref q) => {
+                if !q.split(',').any(|t| t.trim().eq_ignore_ascii_case("auth")) {
+                    return None;
+                }
+                true
+            }
+        };
+
+        Some(Self {
+            realm,
+            nonce: nonce?,
+            opaque,
+            algorithm_name,
+            algorithm,
+            session,
+            qop_auth,
+        })
+    }
+
+    /// Pick the first supported challenge from a list of header values.
+    ///
+    /// A single header value may combine several challenges.
+    pub(crate) fn select<'a>(headers: impl IntoIterator<Item = &'a String>) -> Result<Self> {
+        headers
+            .into_iter()
+            .flat_map(|h| split_challenges(h))
+            .find_map(Self::parse)
+            .ok_or_else(|| AclError::OutboundError {
+                kind: OutboundErrorKind::AuthFailed,
+                message: "Proxy did not offer a supported Digest challenge".to_string(),
+            })
+    }
+
+    /// Build the `Proxy-Authorization` header value with a fresh client nonce.
+    pub(crate) fn authorization(
+        &self,
+        username: &str,
+        password: &str,
+        method: &str,
+        uri: &str,
+    ) -> Result<String> {
+        let mut raw = [0u8; 16];
+        getrandom::getrandom(&mut raw).map_err(|e| AclError::OutboundError {
+            kind: OutboundErrorKind::Io,
+            message: format!("Failed to generate Digest cnonce: {}", e),
+        })?;
+        Ok(self.authorization_with_cnonce(username, password, method, uri, &hex(&raw)))
+    }
+
+    fn authorization_with_cnonce(
+        &self,
+        username: &str,
+        password: &str,
+        method: &str,
+        uri: &str,
+        cnonce: &str,
+    ) -> String {
+        const NC: &str = "00000001";
+        let h = |data: String| self.algorithm.hash(&data);
+
+        let mut ha1 = h(format!("{}:{}:{}", username, self.realm, password));
+        if self.session {
+            ha1 = h(format!("{}:{}:{}", ha1, self.nonce, cnonce));
+        }
+        let ha2 = h(format!("{}:{}", method, uri));
+        let response = if self.qop_auth {
+            h(format!(
+                "{}:{}:{}:{}:auth:{}",
+                ha1, self.nonce, NC, cnonce, ha2
+            ))
+        } else {
+            h(format!("{}:{}:{}", ha1, self.nonce, ha2))
+        };
+
+        let mut header = format!(
+            "Digest username=\"{}\", realm=\"{}\", nonce=\"{}\", uri=\"{}\"",
+            quote(username),
+            quote(&self.realm),
+            quote(&self.nonce),
+            quote(uri)
+        );
+        if let Some(ref algorithm) = self.algorithm_name {
+            header.push_str(&format!(", algorithm={}", algorithm));
+        }
+        header.push_str(&format!(", response=\"{}\"", response));
+        if self.qop_auth {
+            header.push_str(&format!(", qop=auth, nc={}, cnonce=\"{}\"", NC, cnonce));
+        }
+        if let Some(ref opaque) = self.opaque {
+            header.push_str(&format!(", opaque=\"{}\"", quote(opaque)));
+        }
+        header
+    }
+}
+
+/// Split a `Proxy-Authenticate` value into its challenges.
+///
+/// Challenges and their parameters are both comma separated
+/// (`Basic realm="x", Digest realm="y", nonce="z"`); a new challenge starts
+/// at a comma outside quotes followed by a token that is not a parameter
+/// name.
+fn split_challenges(header: &str) -> Vec<&str> {
+    let mut challenges = Vec::new();
+    let mut start = 0;
+    let mut in_quotes = false;
+    let mut escaped = false;
+    for (i, c) in header.char_indices() {
+        if in_quotes {
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == '"' {
+                in_quotes = false;
+            }
+            continue;
+        }
+        match c {
+            '"' => in_quotes = true,
+            ',' if starts_challenge(&header[i + 1..]) => {
+                challenges.push(header[start..i].trim());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    challenges.push(header[start..].trim());
+    challenges.retain(|c| !c.is_empty());
+    challenges
+}
+
+/// Returns true if the text after a comma begins with an auth scheme.
+fn starts_challenge(rest: &str) -> bool {
+    let rest = rest.trim_start();
+    let end = rest
+        .find(|c: char| c.is_whitespace() || c == '=' || c == ',')
+        .unwrap_or(rest.len());
+    // A parameter name is followed by `=`, possibly after whitespace
+    end > 0 && !rest[end..].trim_start().starts_with('=')
+}
+
+/// Split `key=value, key="quoted, value"` auth-params.
+fn parse_params(input: &str) -> Vec<(String, String)> {
+    let mut params = Vec::new();
+    let mut chars = input.chars().peekable();
+    loop {
+        while chars.peek().is_some_and(|c| *c == ',' || c.is_whitespace()) {
+            chars.next();
+        }
+        let key: String = chars
+            .by_ref()
+            .take_while(|c| *c != '=')
+            .collect::<String>()
+            .trim()
+            .to_string();
+        if key.is_empty() {
+            break;
+        }
+
+        while chars.peek().is_some_and(|c| c.is_whitespace()) {
+            chars.next();
+        }
+        let mut value = String::new();
+        if chars.peek() == Some(&'"') {
+            chars.next();
+            while let Some(c) = chars.next() {
+                match c {
+                    '\\' => value.extend(chars.next()),
+                    '"' => break,
+                    _ => value.push(c),
+                }
+            }
+        } else {
+            while let Some(c) = chars.peek() {
+                if *c == ',' {
+                    break;
+                }
+                value.push(*c);
+                chars.next();
+            }
+            value = value.trim().to_string();
+        }
+        params.push((key, value));
+    }
+    params
+}
+
+/// Escape `"` and `\` for a quoted-string.
+fn quote(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_digest_rfc2617_md5_qop_auth() {
+        let challenge = DigestChallenge::parse(
+            "Digest realm=\"testrealm@host.com\", qop=\"auth,auth-int\", \
+             nonce=\"dcd98b7102dd2f0e8b11d0f600bfb0c093\", \
+             opaque=\"5ccc069c403ebaf9f0171e9517f40e41\"",
+        )
+        .unwrap();
+        let header = challenge.authorization_with_cnonce(
+            "Mufasa",
+            "Circle Of Life",
+            "GET",
+            "/dir/index.html",
+            "0a4f113b",
+        );
+        assert!(header.contains("response=\"6629fae49393a05397450978507c4ef1\""));
+        assert!(header.contains("qop=auth, nc=00000001, cnonce=\"0a4f113b\""));
+        assert!(header.contains("opaque=\"5ccc069c403ebaf9f0171e9517f40e41\""));
+        assert!(!header.contains("algorithm="));
+    }
+
+    #[test]
+    fn test_digest_rfc7616_sha256() {
+        let challenge = DigestChallenge::parse(
+            "Digest realm=\"http-auth@example.org\", qop=\"auth, auth-int\", \
+             algorithm=SHA-256, \
+             nonce=\"7ypf/xlj9XXwfDPEoM4URrv/xwf94BcCAzFZH4GiTo0v\", \
+             opaque=\"FQhe/qaU925kfnzjCev0ciny7QMkPqMAFRtzCUYo5tdS\"",
+        )
+        .unwrap();
+        let header = challenge.authorization_with_cnonce(
+            "Mufasa",
+            "Circle of Life",
+            "GET",
+            "/dir/index.html",
+            "f2/wE4q74E6zIJEtWaHKaf5wv/H5QzzpXusqGemxURZJ",
+        );
+        assert!(header.contains(
+            "response=\"753927fa0e85d155564e2e272a28d1802ca10daf4496794697cf8db5856cb6c1\""
+        ));
+        assert!(header.contains("algorithm=SHA-256"));
+    }
+
+    #[test]
+    fn test_digest_parse_rejects_unsupported() {
+        assert!(DigestChallenge::parse("Basic realm=\"proxy\"").is_none());
+        assert!(DigestChallenge::parse("Digest realm=\"r\"").is_none());
+        assert!(DigestChallenge::parse("Digest nonce=\"n\", algorithm=SHA-512").is_none());
+        assert!(DigestChallenge::parse("Digest nonce=\"n\", qop=\"auth-int\"").is_none());
+        assert!(DigestChallenge::parse("digest nonce=n").is_some());
+    }
+
+    #[test]
+    fn test_digest_select_skips_basic() {
+        let headers = vec![
+            "Basic realm=\"proxy\"".to_string(),
+            "Digest realm=\"proxy\", nonce=\"abc\"".to_string(),
+        ];
+        let challenge = DigestChallenge::select(&headers).unwrap();
+        assert_eq!(challenge.nonce, "abc");
+
+        let err = DigestChallenge::select(&headers[..1]).unwrap_err();
+        assert!(matches!(
+            err,
+            AclError::OutboundError {
+                kind: OutboundErrorKind::AuthFailed,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_digest_select_combined_header() {
+        let headers = vec![
+            "Basic realm=\"a, Digest b\", Digest realm=\"proxy\", nonce=\"abc\", \
+             qop=\"auth,auth-int\", algorithm=SHA-256"
+                .to_string(),
+        ];
+        let challenge = DigestChallenge::select(&headers).unwrap();
+        assert_eq!(challenge.realm, "proxy");
+        assert_eq!(challenge.nonce, "abc");
+        assert_eq!(challenge.algorithm, Algorithm::Sha256);
+
+        assert_eq!(
+            split_challenges("Negotiate, Basic realm = \"x\",Digest nonce=n, opaque=o"),
+            vec![
+                "Negotiate",
+                "Basic realm = \"x\"",
+                "Digest nonce=n, opaque=o"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_digest_legacy_no_qop_and_quoting() {
+        let challenge =
+            DigestChallenge::parse("Digest realm=\"a \\\"b\\\"\", nonce=\"n\", algorithm=MD5-sess")
+                .unwrap();
+        assert_eq!(challenge.realm, "a \"b\"");
+        let header = challenge.authorization_with_cnonce("u\"x", "p", "CONNECT", "h:443", "c");
+        assert!(header.starts_with("Digest username=\"u\\\"x\", realm=\"a \\\"b\\\"\""));
+        assert!(header.contains("algorithm=MD5-sess"));
+        assert!(!header.contains("qop="));
+    }
+}
@@ -2,17 +2,22 @@
 //!
 //! Connects directly to the target using the local network.
 
+use std::fmt;
 use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, TcpStream, ToSocketAddrs, UdpSocket};
-use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::{mpsc, Arc};
-use std::thread;
+use std::ops::RangeInclusive;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
 use std::time::Duration;
+#[cfg(unix)]
+use std::time::Instant;
 
 use crate::error::{AclError, OutboundErrorKind, Result};
 
+use super::group::fnv1a;
 use super::{
-    build_resolve_info, try_resolve_from_ip, Addr, Outbound, ResolveInfo, StdTcpConn, TcpConn,
-    UdpConn, DEFAULT_DIALER_TIMEOUT,
+    apply_dscp, build_resolve_info, check_dscp, try_resolve_from_ip, Addr, Hosts, Outbound,
+    ResolveInfo, StdTcpConn, TcpConn, UdpConn, DEFAULT_DIALER_TIMEOUT,
 };
 
 #[cfg(feature = "async")]
@@ -25,7 +30,7 @@ use tokio::net::{TcpStream as TokioTcpStream, UdpSocket as TokioUdpSocket};
 /// IP version preference for direct connections.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
 pub enum DirectMode {
-    /// Dual-stack "happy eyeballs"-like mode (default)
+    /// Happy Eyeballs v2 (RFC 8305) over all resolved addresses (default)
     #[default]
     Auto,
     /// Use IPv6 address when available, otherwise IPv4
@@ -38,6 +43,52 @@ pub enum DirectMode {
     Only4,
 }
 
+/// How Direct picks a local source address from `bind_ips`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SourceStrategy {
+    /// Cycle through the pool in order
+    #[default]
+    RoundRobin,
+    /// Pick an address uniformly at random
+    Random,
+    /// Hash the destination address, so a destination always leaves from
+    /// the same source
+    DestinationHash,
+}
+
+impl SourceStrategy {
+    /// Configuration name of the strategy.
+    pub fn name(&self) -> &'static str {
+        match self {
+            SourceStrategy::RoundRobin => "round-robin",
+            SourceStrategy::Random => "random",
+            SourceStrategy::DestinationHash => "destination-hash",
+        }
+    }
+}
+
+impl fmt::Display for SourceStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for SourceStrategy {
+    type Err = AclError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "round-robin" => Ok(SourceStrategy::RoundRobin),
+            "random" => Ok(SourceStrategy::Random),
+            "destination-hash" => Ok(SourceStrategy::DestinationHash),
+            _ => Err(AclError::ConfigError(format!(
+                "Unknown source strategy: {}",
+                s
+            ))),
+        }
+    }
+}
+
 /// Options for creating a Direct outbound.
 #[derive(Debug, Clone)]
 pub struct DirectOptions {
@@ -47,34 +98,115 @@ pub struct DirectOptions {
     pub bind_ip4: Option<Ipv4Addr>,
     /// Bind IPv6 address for outgoing connections
     pub bind_ip6: Option<Ipv6Addr>,
-    /// Bind to a specific network device (Linux only, SO_BINDTODEVICE).
+    /// Pool of local source addresses. Each connection uses an address of
+    /// the target's family, chosen by `source_strategy`; targets of a family
+    /// with no address in the pool use the system default.
     /// Mutually exclusive with bind_ip4/bind_ip6.
+    pub bind_ips: Vec<IpAddr>,
+    /// Selection strategy for `bind_ips`. Default: round-robin.
+    pub source_strategy: SourceStrategy,
+    /// Local source port range for TCP and UDP sockets. Each socket binds
+    /// a free port from the range, starting at a random one.
+    pub port_range: Option<RangeInclusive<u16>>,
+    /// Bind to a specific network device (Linux only, SO_BINDTODEVICE).
+    /// Mutually exclusive with bind_ip4/bind_ip6/bind_ips.
     pub bind_device: Option<String>,
     /// Enable TCP Fast Open
     pub fast_open: bool,
+    /// Open TCP sockets as Multipath TCP (Linux only, IPPROTO_MPTCP). Falls
+    /// back to plain TCP when the kernel lacks or disables MPTCP, and on
+    /// other platforms.
+    pub mptcp: bool,
+    /// Packet mark for TCP and UDP sockets (Linux only, SO_MARK), used by
+    /// policy routing to select a routing table. Requires CAP_NET_ADMIN.
+    pub fwmark: Option<u32>,
+    /// DSCP value (0-63) for TCP and UDP sockets, set through `IP_TOS` or
+    /// `IPV6_TCLASS` so routers can classify this outbound's traffic.
+    pub dscp: Option<u8>,
     /// Connection timeout
     pub timeout: Option<Duration>,
     /// Enable TCP_NODELAY (disable Nagle's algorithm). Default: true.
     pub tcp_nodelay: bool,
     /// TCP keepalive interval. Default: 60s. None = don't set keepalive.
     pub tcp_keepalive: Option<Duration>,
+    /// Static hosts table consulted before system DNS.
+    pub hosts: Option<Arc<Hosts>>,
+    /// Delay before starting the next connection attempt in `Auto` mode
+    /// (RFC 8305 "Connection Attempt Delay"). Default: 250ms.
+    pub attempt_delay: Duration,
 }
 
 /// Default keepalive interval for direct connections (60 seconds).
 pub(crate) const DEFAULT_TCP_KEEPALIVE: Duration = Duration::from_secs(60);
 
+/// Default Happy Eyeballs connection attempt delay (250 ms, per RFC 8305).
+pub(crate) const DEFAULT_ATTEMPT_DELAY: Duration = Duration::from_millis(250);
+
 impl Default for DirectOptions {
     fn default() -> Self {
         Self {
             mode: DirectMode::default(),
             bind_ip4: None,
             bind_ip6: None,
+            bind_ips: Vec::new(),
+            source_strategy: SourceStrategy::default(),
+            port_range: None,
             bind_device: None,
             fast_open: false,
+            mptcp: false,
+            fwmark: None,
+            dscp: None,
             timeout: None,
             tcp_nodelay: true,
             tcp_keepalive: Some(DEFAULT_TCP_KEEPALIVE),
+            hosts: None,
+            attempt_delay: DEFAULT_ATTEMPT_DELAY,
+        }
+    }
+}
+
+/// Pool of local source addresses, split by family.
+#[derive(Debug, Clone, Default)]
+struct SourcePool {
+    v4: Vec<IpAddr>,
+    v6: Vec<IpAddr>,
+    strategy: SourceStrategy,
+    /// Round-robin cursor, shared by clones of the outbound
+    cursor: Arc<AtomicUsize>,
+}
+
+impl SourcePool {
+    fn new(ips: &[IpAddr], strategy: SourceStrategy) -> Self {
+        let (v6, v4) = ips.iter().partition(|ip| ip.is_ipv6());
+        Self {
+            v4,
+            v6,
+            strategy,
+            cursor: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.v4.is_empty() && self.v6.is_empty()
+    }
+
+    /// Pick a source address of the given family.
+    ///
+    /// Hashing needs the destination; without one it falls back to
+    /// round-robin.
+    fn pick(&self, ipv6: bool, dest: Option<IpAddr>) -> Option<IpAddr> {
+        let ips = if ipv6 { &self.v6 } else { &self.v4 };
+        if ips.is_empty() {
+            return None;
         }
+        let n = match (self.strategy, dest) {
+            (SourceStrategy::Random, _) => random_u64() as usize,
+            (SourceStrategy::DestinationHash, Some(dest)) => {
+                fnv1a(dest.to_string().as_bytes()) as usize
+            }
+            _ => self.cursor.fetch_add(1, Ordering::Relaxed),
+        };
+        Some(ips[n % ips.len()])
     }
 }
 
@@ -87,11 +219,18 @@ pub struct Direct {
     mode: DirectMode,
     bind_ip4: Option<Ipv4Addr>,
     bind_ip6: Option<Ipv6Addr>,
+    sources: SourcePool,
+    port_range: Option<RangeInclusive<u16>>,
     bind_device: Option<String>,
     fast_open: bool,
+    mptcp: bool,
+    fwmark: Option<u32>,
+    dscp: Option<u8>,
     timeout: Duration,
     tcp_nodelay: bool,
     tcp_keepalive: Option<Duration>,
+    hosts: Option<Arc<Hosts>>,
+    attempt_delay: Duration,
 }
 
 impl Direct {
@@ -111,26 +250,59 @@ impl Direct {
 
     /// Create a new Direct outbound with the given options.
     pub fn with_options(opts: DirectOptions) -> Result<Self> {
-        if opts.bind_device.is_some() && (opts.bind_ip4.is_some() || opts.bind_ip6.is_some()) {
+        let bind_ip = opts.bind_ip4.is_some() || opts.bind_ip6.is_some();
+        if opts.bind_device.is_some() && (bind_ip || !opts.bind_ips.is_empty()) {
+            return Err(AclError::ConfigError(
+                "bind_device is mutually exclusive with bind_ip4/bind_ip6/bind_ips".to_string(),
+            ));
+        }
+        if bind_ip && !opts.bind_ips.is_empty() {
             return Err(AclError::ConfigError(
-                "bind_device is mutually exclusive with bind_ip4/bind_ip6".to_string(),
+                "bind_ips is mutually exclusive with bind_ip4/bind_ip6".to_string(),
             ));
         }
+        if let Some(ref range) = opts.port_range {
+            if range.is_empty() || *range.start() == 0 {
+                return Err(AclError::ConfigError(format!(
+                    "invalid port range {}-{}",
+                    range.start(),
+                    range.end()
+                )));
+            }
+        }
+        if let Some(mark) = opts.fwmark {
+            check_fwmark(mark)?;
+        }
+        if let Some(dscp) = opts.dscp {
+            check_dscp(dscp)?;
+        }
         Ok(Self {
             mode: opts.mode,
             bind_ip4: opts.bind_ip4,
             bind_ip6: opts.bind_ip6,
+            sources: SourcePool::new(&opts.bind_ips, opts.source_strategy),
+            port_range: opts.port_range,
             bind_device: opts.bind_device,
             fast_open: opts.fast_open,
+            mptcp: opts.mptcp,
+            fwmark: opts.fwmark,
+            dscp: opts.dscp,
             timeout: opts.timeout.unwrap_or(DEFAULT_DIALER_TIMEOUT),
             tcp_nodelay: opts.tcp_nodelay,
             tcp_keepalive: opts.tcp_keepalive,
+            hosts: opts.hosts,
+            attempt_delay: opts.attempt_delay,
         })
     }
 
+    /// Answer the address from the static hosts table, if configured.
+    fn resolve_from_hosts(&self, addr: &mut Addr) -> bool {
+        self.hosts.as_ref().is_some_and(|hosts| hosts.resolve(addr))
+    }
+
     /// Resolve the address using system DNS if ResolveInfo is not available.
     fn resolve(&self, addr: &mut Addr) {
-        if try_resolve_from_ip(addr) {
+        if try_resolve_from_ip(addr) || self.resolve_from_hosts(addr) {
             return;
         }
 
@@ -147,7 +319,27 @@ impl Direct {
 
     /// Check if we need to create a socket2::Socket for custom options.
     fn needs_custom_socket(&self, ip: &IpAddr) -> bool {
-        self.get_bind_ip(ip).is_some() || self.bind_device.is_some() || self.fast_open
+        let bind_ip = match ip {
+            IpAddr::V4(_) => self.bind_ip4.is_some(),
+            IpAddr::V6(_) => self.bind_ip6.is_some(),
+        };
+        bind_ip
+            || !self.sources.is_empty()
+            || self.port_range.is_some()
+            || self.bind_device.is_some()
+            || self.fast_open
+            || self.mptcp
+            || self.fwmark.is_some()
+            || self.dscp.is_some()
+    }
+
+    /// Check if UDP sockets need to be created through socket2.
+    fn needs_custom_udp_socket(&self) -> bool {
+        !self.sources.is_empty()
+            || self.port_range.is_some()
+            || self.bind_device.is_some()
+            || self.fwmark.is_some()
+            || self.dscp.is_some()
     }
 
     /// Create and configure a TCP socket2::Socket with all custom options.
@@ -156,22 +348,12 @@ impl Direct {
             IpAddr::V4(_) => socket2::Domain::IPV4,
             IpAddr::V6(_) => socket2::Domain::IPV6,
         };
-        let socket =
-            socket2::Socket::new(domain, socket2::Type::STREAM, Some(socket2::Protocol::TCP))
-                .map_err(|e| AclError::OutboundError {
-                    kind: OutboundErrorKind::Io,
-                    message: format!("Failed to create socket: {}", e),
-                })?;
+        let socket = self.new_tcp_socket(domain)?;
 
-        // Bind to IP address
-        if let Some(bind_ip) = self.get_bind_ip(ip) {
-            let bind_addr = SocketAddr::new(bind_ip, 0);
-            socket
-                .bind(&bind_addr.into())
-                .map_err(|e| AclError::OutboundError {
-                    kind: OutboundErrorKind::Io,
-                    message: format!("Failed to bind: {}", e),
-                })?;
+        // Bind to source address and port
+        let source = self.source_ip(ip.is_ipv6(), Some(*ip));
+        if source.is_some() || self.port_range.is_some() {
+            self.bind_source(&socket, source.unwrap_or(unspecified_ip(ip.is_ipv6())))?;
         }
 
         // Bind to network device (Linux only)
@@ -185,6 +367,16 @@ impl Direct {
                 })?;
         }
 
+        // Mark packets for policy routing (Linux only)
+        #[cfg(target_os = "linux")]
+        if let Some(mark) = self.fwmark {
+            set_fwmark(&socket, mark)?;
+        }
+
+        if let Some(dscp) = self.dscp {
+            apply_dscp(&socket, ip.is_ipv6(), dscp)?;
+        }
+
         // Enable TCP Fast Open (client-side)
         if self.fast_open {
             set_tcp_fastopen(&socket)?;
@@ -193,18 +385,37 @@ impl Direct {
         Ok(socket)
     }
 
-    /// Dial TCP to a specific IP address, checking a cancellation flag first.
-    /// Returns `None` if cancelled (the caller should not attempt the connection).
-    fn dial_tcp_ip_cancellable(
-        &self,
-        ip: IpAddr,
-        port: u16,
-        cancelled: &AtomicBool,
-    ) -> Option<Result<TcpStream>> {
-        if cancelled.load(Ordering::Relaxed) {
-            return None;
+    /// Open a TCP socket, using MPTCP when enabled and available.
+    fn new_tcp_socket(&self, domain: socket2::Domain) -> Result<socket2::Socket> {
+        #[cfg(target_os = "linux")]
+        if self.mptcp {
+            match socket2::Socket::new(
+                domain,
+                socket2::Type::STREAM,
+                Some(socket2::Protocol::MPTCP),
+            ) {
+                Ok(socket) => return Ok(socket),
+                // Kernel built without MPTCP, or net.mptcp.enabled=0
+                Err(e)
+                    if matches!(
+                        e.raw_os_error(),
+                        Some(libc::EPROTONOSUPPORT | libc::ENOPROTOOPT | libc::EINVAL)
+                    ) => {}
+                Err(e) => {
+                    return Err(AclError::OutboundError {
+                        kind: OutboundErrorKind::Io,
+                        message: format!("Failed to create MPTCP socket: {}", e),
+                    })
+                }
+            }
         }
-        Some(self.dial_tcp_ip(ip, port))
+
+        socket2::Socket::new(domain, socket2::Type::STREAM, Some(socket2::Protocol::TCP)).map_err(
+            |e| AclError::OutboundError {
+                kind: OutboundErrorKind::Io,
+                message: format!("Failed to create socket: {}", e),
+            },
+        )
     }
 
     /// Dial TCP to a specific IP address.
@@ -259,11 +470,30 @@ impl Direct {
         }
     }
 
-    /// Get the bind IP for the given target IP.
-    fn get_bind_ip(&self, target: &IpAddr) -> Option<IpAddr> {
-        match target {
-            IpAddr::V4(_) => self.bind_ip4.map(IpAddr::V4),
-            IpAddr::V6(_) => self.bind_ip6.map(IpAddr::V6),
+    /// Get the source IP for a socket of the given family, if configured.
+    ///
+    /// Pool strategies advance on every call, so call once per socket.
+    fn source_ip(&self, ipv6: bool, dest: Option<IpAddr>) -> Option<IpAddr> {
+        let bind_ip = if ipv6 {
+            self.bind_ip6.map(IpAddr::V6)
+        } else {
+            self.bind_ip4.map(IpAddr::V4)
+        };
+        bind_ip.or_else(|| self.sources.pick(ipv6, dest))
+    }
+
+    /// Bind a socket to `ip`, on a port from `port_range` if configured.
+    fn bind_source(&self, socket: &socket2::Socket, ip: IpAddr) -> Result<()> {
+        match self.port_range {
+            Some(ref range) => bind_port_in_range(socket, ip, range),
+            None => {
+                socket
+                    .bind(&SocketAddr::new(ip, 0).into())
+                    .map_err(|e| AclError::OutboundError {
+                        kind: OutboundErrorKind::Io,
+                        message: format!("Failed to bind: {}", e),
+                    })
+            }
         }
     }
 
@@ -280,8 +510,9 @@ impl Direct {
         }
     }
 
-    /// Create a UDP socket with bind_device support via socket2.
-    fn create_udp_socket_with_device(&self, use_ipv6: bool) -> Result<socket2::Socket> {
+    /// Create a UDP socket with source pool, port range, bind_device and
+    /// fwmark support via socket2.
+    fn create_udp_socket(&self, use_ipv6: bool, dest: Option<IpAddr>) -> Result<socket2::Socket> {
         let domain = if use_ipv6 {
             socket2::Domain::IPV6
         } else {
@@ -294,17 +525,10 @@ impl Direct {
                     message: format!("Failed to create UDP socket: {}", e),
                 })?;
 
-        let bind_addr = if use_ipv6 {
-            SocketAddr::new(IpAddr::V6(Ipv6Addr::UNSPECIFIED), 0)
-        } else {
-            SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0)
-        };
-        socket
-            .bind(&bind_addr.into())
-            .map_err(|e| AclError::OutboundError {
-                kind: OutboundErrorKind::Io,
-                message: format!("Failed to bind UDP: {}", e),
-            })?;
+        let source = self
+            .source_ip(use_ipv6, dest)
+            .unwrap_or(unspecified_ip(use_ipv6));
+        self.bind_source(&socket, source)?;
 
         #[cfg(target_os = "linux")]
         if let Some(ref device) = self.bind_device {
@@ -316,6 +540,15 @@ impl Direct {
                 })?;
         }
 
+        #[cfg(target_os = "linux")]
+        if let Some(mark) = self.fwmark {
+            set_fwmark(&socket, mark)?;
+        }
+
+        if let Some(dscp) = self.dscp {
+            apply_dscp(&socket, use_ipv6, dscp)?;
+        }
+
         Ok(socket)
     }
 
@@ -331,77 +564,133 @@ impl Direct {
         }
     }
 
-    /// Dual-stack dial TCP, racing IPv4 and IPv6 connections.
-    ///
-    /// Spawns two threads to race IPv4 and IPv6 connections. When the first
-    /// succeeds, a shared cancellation flag is set so the other thread can
-    /// skip its connection attempt if it hasn't started yet.
+    /// Start a non-blocking TCP connect to a specific IP address.
+    #[cfg(unix)]
+    fn start_connect(&self, ip: IpAddr, port: u16) -> Result<socket2::Socket> {
+        let socket = self.create_tcp_socket(&ip)?;
+        socket
+            .set_nonblocking(true)
+            .map_err(|e| AclError::OutboundError {
+                kind: OutboundErrorKind::Io,
+                message: format!("Failed to set nonblocking: {}", e),
+            })?;
+        match socket.connect(&SocketAddr::new(ip, port).into()) {
+            Ok(()) => Ok(socket),
+            Err(e) if e.raw_os_error() == Some(libc::EINPROGRESS) => Ok(socket),
+            Err(e) => Err(connect_failed(e)),
+        }
+    }
+
+    /// Dial TCP with Happy Eyeballs v2 (RFC 8305) over all addresses.
     ///
-    /// Note: if the losing thread has already entered `connect_timeout`,
-    /// the flag cannot interrupt the in-progress syscall — the thread will
-    /// run until the OS connect times out. This is a limitation of
-    /// std::thread (no cooperative cancellation). For full cancellation,
-    /// use the async API which leverages `tokio::select!`.
-    fn dual_stack_dial_tcp(&self, ipv4: Ipv4Addr, ipv6: Ipv6Addr, port: u16) -> Result<TcpStream> {
-        let (tx, rx) = mpsc::channel();
-        let cancelled = Arc::new(AtomicBool::new(false));
-
-        let clone_v4 = self.clone();
-        let tx_v4 = tx.clone();
-        let ip_v4 = IpAddr::V4(ipv4);
-        let cancelled_v4 = cancelled.clone();
-        thread::spawn(move || {
-            if let Some(result) = clone_v4.dial_tcp_ip_cancellable(ip_v4, port, &cancelled_v4) {
-                let _ = tx_v4.send((ip_v4, result));
-            }
-        });
+    /// Attempts are non-blocking connects multiplexed with `poll(2)` on the
+    /// calling thread. They start in interleaved family order, each one
+    /// `attempt_delay` after the previous or as soon as an attempt fails,
+    /// and each is bounded by the dial timeout. The first connection wins;
+    /// the sockets of the other attempts are closed.
+    #[cfg(unix)]
+    fn happy_eyeballs_dial_tcp(&self, addrs: &[IpAddr], port: u16) -> Result<TcpStream> {
+        struct Attempt {
+            ip: IpAddr,
+            socket: socket2::Socket,
+            deadline: Instant,
+        }
 
-        let clone_v6 = self.clone();
-        let ip_v6 = IpAddr::V6(ipv6);
-        let cancelled_v6 = cancelled.clone();
-        thread::spawn(move || {
-            if let Some(result) = clone_v6.dial_tcp_ip_cancellable(ip_v6, port, &cancelled_v6) {
-                let _ = tx.send((ip_v6, result));
+        let mut remaining = interleave_families(addrs).into_iter();
+        let mut attempts: Vec<Attempt> = Vec::new();
+        let mut errors = Vec::new();
+        let mut next_start = Instant::now();
+        loop {
+            let now = Instant::now();
+            if now >= next_start || attempts.is_empty() {
+                if let Some(ip) = remaining.next() {
+                    match self.start_connect(ip, port) {
+                        Ok(socket) => {
+                            attempts.push(Attempt {
+                                ip,
+                                socket,
+                                deadline: now + self.timeout,
+                            });
+                            next_start = now + self.attempt_delay;
+                        }
+                        // Failed synchronously: move on to the next address
+                        Err(e) => errors.push((ip, e)),
+                    }
+                    continue;
+                }
+            }
+            if attempts.is_empty() {
+                break;
             }
-        });
-
-        // Get first result
-        let (first_ip, first) = rx.recv().map_err(|_| AclError::OutboundError {
-            kind: OutboundErrorKind::ConnectionFailed,
-            message: "Channel error".to_string(),
-        })?;
 
-        if first.is_ok() {
-            cancelled.store(true, Ordering::Relaxed);
-            return first;
+            let mut wake = attempts.iter().map(|a| a.deadline).min().unwrap_or(now);
+            if remaining.len() > 0 {
+                wake = wake.min(next_start);
+            }
+            let sockets: Vec<&socket2::Socket> = attempts.iter().map(|a| &a.socket).collect();
+            let ready = poll_writable(&sockets, wake.saturating_duration_since(now))?;
+
+            let now = Instant::now();
+            // Walk backwards so removals keep the remaining indices valid
+            for i in (0..attempts.len()).rev() {
+                if ready[i] {
+                    match attempts[i].socket.take_error() {
+                        Ok(None) => return self.finish_connect(attempts.swap_remove(i).socket),
+                        Ok(Some(e)) | Err(e) => {
+                            errors.push((attempts.remove(i).ip, connect_failed(e)));
+                            next_start = now;
+                        }
+                    }
+                } else if now >= attempts[i].deadline {
+                    let ip = attempts.remove(i).ip;
+                    errors.push((
+                        ip,
+                        AclError::OutboundError {
+                            kind: OutboundErrorKind::Timeout,
+                            message: "Connection timeout".to_string(),
+                        },
+                    ));
+                    next_start = now;
+                }
+            }
         }
-        let first_err = first.unwrap_err();
 
-        // First failed, try second
-        let (second_ip, second) = rx.recv().map_err(|_| AclError::OutboundError {
-            kind: OutboundErrorKind::ConnectionFailed,
-            message: "Channel error".to_string(),
-        })?;
+        Err(attempts_failed(errors))
+    }
 
-        if second.is_ok() {
-            return second;
+    /// Dial TCP over all addresses one after another.
+    ///
+    /// Fallback for platforms without `poll(2)`.
+    #[cfg(not(unix))]
+    fn happy_eyeballs_dial_tcp(&self, addrs: &[IpAddr], port: u16) -> Result<TcpStream> {
+        let mut errors = Vec::new();
+        for ip in interleave_families(addrs) {
+            match self.dial_tcp_ip(ip, port) {
+                Ok(stream) => return Ok(stream),
+                Err(e) => errors.push((ip, e)),
+            }
         }
-        let second_err = second.unwrap_err();
+        Err(attempts_failed(errors))
+    }
 
-        // Both failed — combine errors so the caller sees both attempts
-        Err(AclError::OutboundError {
-            kind: OutboundErrorKind::ConnectionFailed,
-            message: format!(
-                "dual-stack connection failed: {} ({}), {} ({})",
-                first_ip, first_err, second_ip, second_err
-            ),
-        })
+    /// Turn a connected non-blocking socket into a configured stream.
+    #[cfg(unix)]
+    fn finish_connect(&self, socket: socket2::Socket) -> Result<TcpStream> {
+        socket
+            .set_nonblocking(false)
+            .map_err(|e| AclError::OutboundError {
+                kind: OutboundErrorKind::Io,
+                message: format!("Failed to set blocking: {}", e),
+            })?;
+        let stream = TcpStream::from(socket);
+        self.apply_tcp_options_to_stream(&stream);
+        Ok(stream)
     }
 
     /// Async resolve the address using system DNS if ResolveInfo is not available.
     #[cfg(feature = "async")]
     async fn async_resolve(&self, addr: &mut Addr) {
-        if try_resolve_from_ip(addr) {
+        if try_resolve_from_ip(addr) || self.resolve_from_hosts(addr) {
             return;
         }
 
@@ -429,7 +718,7 @@ impl Direct {
             tokio::time::timeout(self.timeout, tokio_socket.connect(socket_addr))
                 .await
                 .map_err(|_| AclError::OutboundError {
-                    kind: OutboundErrorKind::Io,
+                    kind: OutboundErrorKind::Timeout,
                     message: "Connection timeout".to_string(),
                 })?
                 .map_err(|e| AclError::OutboundError {
@@ -440,7 +729,7 @@ impl Direct {
             tokio::time::timeout(self.timeout, TokioTcpStream::connect(socket_addr))
                 .await
                 .map_err(|_| AclError::OutboundError {
-                    kind: OutboundErrorKind::Io,
+                    kind: OutboundErrorKind::Timeout,
                     message: "Connection timeout".to_string(),
                 })?
                 .map_err(|e| AclError::OutboundError {
@@ -453,40 +742,56 @@ impl Direct {
         Ok(stream)
     }
 
-    /// Async dual-stack dial TCP, racing IPv4 and IPv6 connections.
+    /// Async dial TCP with Happy Eyeballs v2 (RFC 8305) over all addresses.
+    ///
+    /// Same schedule as the sync version, with attempts as tasks; the losers
+    /// are aborted as soon as one attempt connects.
     #[cfg(feature = "async")]
-    async fn async_dual_stack_dial_tcp(
+    async fn async_happy_eyeballs_dial_tcp(
         &self,
-        ipv4: Ipv4Addr,
-        ipv6: Ipv6Addr,
+        addrs: &[IpAddr],
         port: u16,
     ) -> Result<TokioTcpStream> {
-        let (first_ip, first_err, fallback_ip) = tokio::select! {
-            result = self.async_dial_tcp_ip(IpAddr::V4(ipv4), port) => {
-                if result.is_ok() {
-                    return result;
+        let mut remaining = interleave_families(addrs).into_iter();
+        // Dropping the set aborts every attempt still running
+        let mut attempts = tokio::task::JoinSet::new();
+        let start = |attempts: &mut tokio::task::JoinSet<_>, ip: IpAddr| {
+            let this = self.clone();
+            attempts.spawn(async move { (ip, this.async_dial_tcp_ip(ip, port).await) });
+        };
+
+        if let Some(ip) = remaining.next() {
+            start(&mut attempts, ip);
+        }
+        let mut errors = Vec::new();
+        while !attempts.is_empty() {
+            let more = remaining.len() > 0;
+            tokio::select! {
+                joined = attempts.join_next() => {
+                    match joined {
+                        Some(Ok((_, Ok(stream)))) => return Ok(stream),
+                        Some(Ok((ip, Err(e)))) => errors.push((ip, e)),
+                        Some(Err(e)) => {
+                            return Err(AclError::OutboundError {
+                                kind: OutboundErrorKind::ConnectionFailed,
+                                message: format!("Connection attempt failed: {}", e),
+                            })
+                        }
+                        None => break,
+                    }
+                    if let Some(ip) = remaining.next() {
+                        start(&mut attempts, ip);
+                    }
                 }
-                (IpAddr::V4(ipv4), result.unwrap_err(), IpAddr::V6(ipv6))
-            }
-            result = self.async_dial_tcp_ip(IpAddr::V6(ipv6), port) => {
-                if result.is_ok() {
-                    return result;
+                _ = tokio::time::sleep(self.attempt_delay), if more => {
+                    if let Some(ip) = remaining.next() {
+                        start(&mut attempts, ip);
+                    }
                 }
-                (IpAddr::V6(ipv6), result.unwrap_err(), IpAddr::V4(ipv4))
             }
-        };
-
-        let fallback = self.async_dial_tcp_ip(fallback_ip, port).await;
-        match fallback {
-            Ok(stream) => Ok(stream),
-            Err(second_err) => Err(AclError::OutboundError {
-                kind: OutboundErrorKind::ConnectionFailed,
-                message: format!(
-                    "dual-stack connection failed: {} ({}), {} ({})",
-                    first_ip, first_err, fallback_ip, second_err
-                ),
-            }),
         }
+
+        Err(attempts_failed(errors))
     }
 }
 
@@ -518,12 +823,9 @@ impl Outbound for Direct {
             });
         }
 
-        let stream = if self.mode == DirectMode::Auto {
-            if let (Some(ipv4), Some(ipv6)) = (info.ipv4, info.ipv6) {
-                self.dual_stack_dial_tcp(ipv4, ipv6, addr.port)?
-            } else {
-                self.dial_tcp_ip(select_ip(self.mode, info)?, addr.port)?
-            }
+        let addrs = info.addresses();
+        let stream = if self.mode == DirectMode::Auto && addrs.len() > 1 {
+            self.happy_eyeballs_dial_tcp(&addrs, addr.port)?
         } else {
             self.dial_tcp_ip(select_ip(self.mode, info)?, addr.port)?
         };
@@ -536,8 +838,12 @@ impl Outbound for Direct {
 
         let use_ipv6 = self.should_use_ipv6(addr.resolve_info.as_ref());
 
-        let socket = if self.bind_device.is_some() {
-            UdpSocket::from(self.create_udp_socket_with_device(use_ipv6)?)
+        let socket = if self.needs_custom_udp_socket() {
+            let dest = addr
+                .resolve_info
+                .as_ref()
+                .and_then(|info| select_ip(self.mode, info).ok());
+            UdpSocket::from(self.create_udp_socket(use_ipv6, dest)?)
         } else {
             UdpSocket::bind(self.udp_bind_addr(use_ipv6)).map_err(|e| AclError::OutboundError {
                 kind: OutboundErrorKind::Io,
@@ -573,14 +879,10 @@ impl AsyncOutbound for Direct {
             });
         }
 
-        let stream = if self.mode == DirectMode::Auto {
-            if let (Some(ipv4), Some(ipv6)) = (info.ipv4, info.ipv6) {
-                self.async_dual_stack_dial_tcp(ipv4, ipv6, addr.port)
-                    .await?
-            } else {
-                self.async_dial_tcp_ip(select_ip(self.mode, info)?, addr.port)
-                    .await?
-            }
+        let addrs = info.addresses();
+        let stream = if self.mode == DirectMode::Auto && addrs.len() > 1 {
+            self.async_happy_eyeballs_dial_tcp(&addrs, addr.port)
+                .await?
         } else {
             self.async_dial_tcp_ip(select_ip(self.mode, info)?, addr.port)
                 .await?
@@ -594,8 +896,12 @@ impl AsyncOutbound for Direct {
 
         let use_ipv6 = self.should_use_ipv6(addr.resolve_info.as_ref());
 
-        let socket = if self.bind_device.is_some() {
-            let socket = self.create_udp_socket_with_device(use_ipv6)?;
+        let socket = if self.needs_custom_udp_socket() {
+            let dest = addr
+                .resolve_info
+                .as_ref()
+                .and_then(|info| select_ip(self.mode, info).ok());
+            let socket = self.create_udp_socket(use_ipv6, dest)?;
             socket
                 .set_nonblocking(true)
                 .map_err(|e| AclError::OutboundError {
@@ -620,6 +926,100 @@ impl AsyncOutbound for Direct {
     }
 }
 
+/// Unspecified address of the given family.
+fn unspecified_ip(ipv6: bool) -> IpAddr {
+    if ipv6 {
+        IpAddr::V6(Ipv6Addr::UNSPECIFIED)
+    } else {
+        IpAddr::V4(Ipv4Addr::UNSPECIFIED)
+    }
+}
+
+/// Random number from the OS, falling back to the clock.
+fn random_u64() -> u64 {
+    let mut buf = [0u8; 8];
+    match getrandom::getrandom(&mut buf) {
+        Ok(()) => u64::from_ne_bytes(buf),
+        Err(_) => std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.subsec_nanos() as u64)
+            .unwrap_or(0),
+    }
+}
+
+/// Bind a socket to `ip` on a free port from `range`.
+///
+/// The scan starts at a random port so concurrent dials do not all contend
+/// for the bottom of the range.
+fn bind_port_in_range(
+    socket: &socket2::Socket,
+    ip: IpAddr,
+    range: &RangeInclusive<u16>,
+) -> Result<()> {
+    let start = *range.start() as u64;
+    let len = *range.end() as u64 - start + 1;
+    let offset = random_u64() % len;
+    for i in 0..len {
+        let port = (start + (offset + i) % len) as u16;
+        match socket.bind(&SocketAddr::new(ip, port).into()) {
+            Ok(()) => return Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::AddrInUse => continue,
+            Err(e) => {
+                return Err(AclError::OutboundError {
+                    kind: OutboundErrorKind::Io,
+                    message: format!("Failed to bind {}: {}", SocketAddr::new(ip, port), e),
+                })
+            }
+        }
+    }
+    Err(AclError::OutboundError {
+        kind: OutboundErrorKind::Io,
+        message: format!(
+            "No free source port in {}-{} on {}",
+            range.start(),
+            range.end(),
+            ip
+        ),
+    })
+}
+
+/// Set the packet mark (SO_MARK) on a socket.
+///
+/// Missing privilege is reported as `ConfigError`, since retrying cannot help.
+#[cfg(target_os = "linux")]
+fn set_fwmark(socket: &socket2::Socket, mark: u32) -> Result<()> {
+    socket.set_mark(mark).map_err(|e| {
+        if e.kind() == std::io::ErrorKind::PermissionDenied {
+            AclError::ConfigError(format!("fwmark {:#x} requires CAP_NET_ADMIN: {}", mark, e))
+        } else {
+            AclError::OutboundError {
+                kind: OutboundErrorKind::Io,
+                message: format!("Failed to set fwmark: {}", e),
+            }
+        }
+    })
+}
+
+/// Verify up front that the process may set `mark` on its sockets.
+#[cfg(target_os = "linux")]
+fn check_fwmark(mark: u32) -> Result<()> {
+    let socket =
+        socket2::Socket::new(socket2::Domain::IPV4, socket2::Type::DGRAM, None).map_err(|e| {
+            AclError::OutboundError {
+                kind: OutboundErrorKind::Io,
+                message: format!("Failed to create socket: {}", e),
+            }
+        })?;
+    set_fwmark(&socket, mark)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn check_fwmark(_mark: u32) -> Result<()> {
+    Err(AclError::ConfigError(
+        "fwmark is only supported on Linux".to_string(),
+    ))
+}
+
 /// Set TCP Fast Open on a socket.
 ///
 /// - Linux: uses `TCP_FASTOPEN_CONNECT` (enables TFO for client connect() calls)
@@ -664,10 +1064,97 @@ fn set_tcp_fastopen(_socket: &socket2::Socket) -> Result<()> {
     ))
 }
 
+/// Order addresses for Happy Eyeballs (RFC 8305 section 4): alternate
+/// families starting with the family of the first address, keeping resolver
+/// order within each family and dropping duplicates.
+fn interleave_families(addrs: &[IpAddr]) -> Vec<IpAddr> {
+    let mut unique: Vec<IpAddr> = Vec::with_capacity(addrs.len());
+    for ip in addrs {
+        if !unique.contains(ip) {
+            unique.push(*ip);
+        }
+    }
+    let Some(first) = unique.first() else {
+        return unique;
+    };
+    let first_is_v4 = first.is_ipv4();
+    let (preferred, other): (Vec<IpAddr>, Vec<IpAddr>) =
+        unique.iter().partition(|ip| ip.is_ipv4() == first_is_v4);
+
+    let mut ordered = Vec::with_capacity(unique.len());
+    let (mut preferred, mut other) = (preferred.into_iter(), other.into_iter());
+    loop {
+        match (preferred.next(), other.next()) {
+            (None, None) => return ordered,
+            (a, b) => ordered.extend(a.into_iter().chain(b)),
+        }
+    }
+}
+
+fn connect_failed(e: std::io::Error) -> AclError {
+    AclError::OutboundError {
+        kind: OutboundErrorKind::ConnectionFailed,
+        message: format!("Failed to connect: {}", e),
+    }
+}
+
+/// Wait until any of `sockets` is writable (connected or failed), or until
+/// `timeout` passes. Returns which sockets are ready.
+#[cfg(unix)]
+fn poll_writable(sockets: &[&socket2::Socket], timeout: Duration) -> Result<Vec<bool>> {
+    use std::os::unix::io::AsRawFd;
+
+    let mut fds: Vec<libc::pollfd> = sockets
+        .iter()
+        .map(|s| libc::pollfd {
+            fd: s.as_raw_fd(),
+            events: libc::POLLOUT,
+            revents: 0,
+        })
+        .collect();
+    // Round up so a sub-millisecond wait does not spin
+    let millis = timeout
+        .as_nanos()
+        .div_ceil(1_000_000)
+        .min(libc::c_int::MAX as u128);
+    // SAFETY: `fds` is a valid, initialized slice of pollfd for the whole call.
+    let rc = unsafe {
+        libc::poll(
+            fds.as_mut_ptr(),
+            fds.len() as libc::nfds_t,
+            millis as libc::c_int,
+        )
+    };
+    if rc < 0 {
+        let e = std::io::Error::last_os_error();
+        if e.kind() == std::io::ErrorKind::Interrupted {
+            return Ok(vec![false; fds.len()]);
+        }
+        return Err(AclError::OutboundError {
+            kind: OutboundErrorKind::Io,
+            message: format!("poll failed: {}", e),
+        });
+    }
+    Ok(fds.iter().map(|fd| fd.revents != 0).collect())
+}
+
+/// Combine the errors of failed connection attempts so the caller sees
+/// every address that was tried.
+fn attempts_failed(errors: Vec<(IpAddr, AclError)>) -> AclError {
+    let attempts: Vec<String> = errors
+        .iter()
+        .map(|(ip, e)| format!("{} ({})", ip, e))
+        .collect();
+    AclError::OutboundError {
+        kind: OutboundErrorKind::ConnectionFailed,
+        message: format!("all connection attempts failed: {}", attempts.join(", ")),
+    }
+}
+
 /// Select an IP address based on DirectMode preference.
 ///
 /// For Auto mode, behaves like Prefer46 (prefers IPv4, falls back to IPv6).
-/// TCP callers should handle Auto's dual-stack case separately before calling this.
+/// TCP callers should handle Auto's multi-address case separately before calling this.
 fn select_ip(mode: DirectMode, info: &ResolveInfo) -> Result<IpAddr> {
     match mode {
         DirectMode::Auto | DirectMode::Prefer46 => info
@@ -897,6 +1384,143 @@ mod tests {
         assert!(Direct::with_options(opts).is_err());
     }
 
+    #[test]
+    fn test_bind_ips_validation() {
+        let opts = DirectOptions {
+            bind_ip4: Some(Ipv4Addr::LOCALHOST),
+            bind_ips: vec![IpAddr::V4(Ipv4Addr::LOCALHOST)],
+            ..Default::default()
+        };
+        assert!(Direct::with_options(opts).is_err());
+
+        let opts = DirectOptions {
+            bind_device: Some("eth0".to_string()),
+            bind_ips: vec![IpAddr::V4(Ipv4Addr::LOCALHOST)],
+            ..Default::default()
+        };
+        assert!(Direct::with_options(opts).is_err());
+
+        #[allow(clippy::reversed_empty_ranges)]
+        for range in [0..=100, 2000..=1000] {
+            let opts = DirectOptions {
+                port_range: Some(range),
+                ..Default::default()
+            };
+            assert!(Direct::with_options(opts).is_err());
+        }
+    }
+
+    #[test]
+    fn test_source_strategy_parse() {
+        for strategy in [
+            SourceStrategy::RoundRobin,
+            SourceStrategy::Random,
+            SourceStrategy::DestinationHash,
+        ] {
+            assert_eq!(strategy.name().parse::<SourceStrategy>().unwrap(), strategy);
+        }
+        assert!("least-active".parse::<SourceStrategy>().is_err());
+    }
+
+    #[test]
+    fn test_source_pool_strategies() {
+        let v4a = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+        let v4b = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2));
+        let v6 = IpAddr::V6(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1));
+        let dest = IpAddr::V4(Ipv4Addr::new(1, 1, 1, 1));
+
+        let pool = SourcePool::new(&[v4a, v6, v4b], SourceStrategy::RoundRobin);
+        let picks: Vec<_> = (0..4).map(|_| pool.pick(false, Some(dest))).collect();
+        assert_eq!(picks, vec![Some(v4a), Some(v4b), Some(v4a), Some(v4b)]);
+        assert_eq!(pool.pick(true, None), Some(v6));
+
+        let pool = SourcePool::new(&[v4a, v4b], SourceStrategy::DestinationHash);
+        let first = pool.pick(false, Some(dest));
+        assert!((0..10).all(|_| pool.pick(false, Some(dest)) == first));
+        // No IPv6 in the pool: use the system default
+        assert_eq!(pool.pick(true, Some(v6)), None);
+
+        let pool = SourcePool::new(&[v4a, v4b], SourceStrategy::Random);
+        assert!(
+            (0..10).all(|_| matches!(pool.pick(false, None), Some(ip) if ip == v4a || ip == v4b))
+        );
+    }
+
+    // Only Linux routes all of 127.0.0.0/8 to loopback by default
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_bind_ips_rotates_source() {
+        use std::net::TcpListener;
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let sources = [
+            IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)),
+            IpAddr::V4(Ipv4Addr::new(127, 0, 0, 2)),
+        ];
+        let direct = Direct::with_options(DirectOptions {
+            bind_ips: sources.to_vec(),
+            ..Default::default()
+        })
+        .unwrap();
+
+        for expected in [sources[0], sources[1], sources[0]] {
+            let mut addr = Addr::new("127.0.0.1", port);
+            let conn = Outbound::dial_tcp(&direct, &mut addr).unwrap();
+            assert_eq!(conn.local_addr().unwrap().ip(), expected);
+            listener.accept().unwrap();
+        }
+    }
+
+    #[test]
+    fn test_port_range_binds_tcp_and_udp() {
+        use std::net::TcpListener;
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        // Start at a port the kernel just handed out, so the range is
+        // unlikely to collide with ports held by other processes
+        let start = UdpSocket::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port()
+            .min(u16::MAX - 63);
+        let range = start..=start + 63;
+        let direct = Direct::with_options(DirectOptions {
+            port_range: Some(range.clone()),
+            ..Default::default()
+        })
+        .unwrap();
+
+        let mut conns = Vec::new();
+        for _ in 0..3 {
+            let mut addr = Addr::new("127.0.0.1", port);
+            let conn = Outbound::dial_tcp(&direct, &mut addr).unwrap();
+            assert!(range.contains(&conn.local_addr().unwrap().port()));
+            conns.push(conn);
+        }
+
+        let udp = direct.create_udp_socket(false, None).unwrap();
+        let local = udp.local_addr().unwrap().as_socket().unwrap();
+        assert!(range.contains(&local.port()));
+    }
+
+    #[test]
+    fn test_port_range_exhausted() {
+        let taken = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let port = taken.local_addr().unwrap().port();
+        let direct = Direct::with_options(DirectOptions {
+            bind_ips: vec![IpAddr::V4(Ipv4Addr::LOCALHOST)],
+            port_range: Some(port..=port),
+            ..Default::default()
+        })
+        .unwrap();
+
+        let err = direct.create_udp_socket(false, None).unwrap_err();
+        assert!(err.to_string().contains("No free source port"), "{}", err);
+    }
+
     #[test]
     fn test_bind_device_without_bind_ip() {
         let opts = DirectOptions {
@@ -907,6 +1531,94 @@ mod tests {
         assert_eq!(direct.bind_device.as_deref(), Some("eth0"));
     }
 
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_fwmark_applied_to_sockets() {
+        let direct = match Direct::with_options(DirectOptions {
+            fwmark: Some(0x1234),
+            ..Default::default()
+        }) {
+            Ok(direct) => direct,
+            // Unprivileged test environment: must be a clear config error
+            Err(AclError::ConfigError(msg)) => {
+                assert!(msg.contains("CAP_NET_ADMIN"), "{}", msg);
+                return;
+            }
+            Err(e) => panic!("unexpected error: {}", e),
+        };
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let stream = direct
+            .dial_tcp_ip(IpAddr::V4(Ipv4Addr::LOCALHOST), port)
+            .unwrap();
+        assert_eq!(socket2::SockRef::from(&stream).mark().unwrap(), 0x1234);
+
+        let udp = direct.create_udp_socket(false, None).unwrap();
+        assert_eq!(udp.mark().unwrap(), 0x1234);
+    }
+
+    #[test]
+    fn test_dscp_applied_to_sockets() {
+        assert!(Direct::with_options(DirectOptions {
+            dscp: Some(64),
+            ..Default::default()
+        })
+        .is_err());
+
+        let direct = Direct::with_options(DirectOptions {
+            dscp: Some(46),
+            ..Default::default()
+        })
+        .unwrap();
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let stream = direct
+            .dial_tcp_ip(IpAddr::V4(Ipv4Addr::LOCALHOST), port)
+            .unwrap();
+        assert_eq!(socket2::SockRef::from(&stream).tos_v4().unwrap(), 46 << 2);
+
+        let udp = direct.create_udp_socket(false, None).unwrap();
+        assert_eq!(udp.tos_v4().unwrap(), 46 << 2);
+        let udp = direct.create_udp_socket(true, None).unwrap();
+        assert_eq!(udp.tclass_v6().unwrap(), 46 << 2);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_mptcp_socket() {
+        let direct = Direct::with_options(DirectOptions {
+            mptcp: true,
+            ..Default::default()
+        })
+        .unwrap();
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let stream = direct
+            .dial_tcp_ip(IpAddr::V4(Ipv4Addr::LOCALHOST), port)
+            .unwrap();
+
+        // Plain TCP is only acceptable when the kernel has MPTCP off
+        let protocol = socket2::SockRef::from(&stream).protocol().unwrap();
+        let enabled =
+            std::fs::read_to_string("/proc/sys/net/mptcp/enabled").is_ok_and(|v| v.trim() == "1");
+        if enabled {
+            assert_eq!(protocol, Some(socket2::Protocol::MPTCP));
+        } else {
+            assert_eq!(protocol, Some(socket2::Protocol::TCP));
+        }
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    #[test]
+    fn test_fwmark_unsupported() {
+        let result = Direct::with_options(DirectOptions {
+            fwmark: Some(1),
+            ..Default::default()
+        });
+        assert!(matches!(result, Err(AclError::ConfigError(_))));
+    }
+
     #[test]
     fn test_resolve_ip_address() {
         let direct = Direct::new();
@@ -921,11 +1633,10 @@ mod tests {
 
     #[test]
     fn test_select_ip_auto_both_prefers_v4() {
-        let info = ResolveInfo {
-            ipv4: Some(Ipv4Addr::new(1, 2, 3, 4)),
-            ipv6: Some(Ipv6Addr::LOCALHOST),
-            error: None,
-        };
+        let info = ResolveInfo::with_addrs(vec![
+            Ipv4Addr::new(1, 2, 3, 4).into(),
+            Ipv6Addr::LOCALHOST.into(),
+        ]);
         let ip = select_ip(DirectMode::Auto, &info).unwrap();
         assert_eq!(ip, IpAddr::V4(Ipv4Addr::new(1, 2, 3, 4)));
     }
@@ -952,11 +1663,10 @@ mod tests {
 
     #[test]
     fn test_select_ip_prefer64_both_prefers_v6() {
-        let info = ResolveInfo {
-            ipv4: Some(Ipv4Addr::new(1, 2, 3, 4)),
-            ipv6: Some(Ipv6Addr::LOCALHOST),
-            error: None,
-        };
+        let info = ResolveInfo::with_addrs(vec![
+            Ipv4Addr::new(1, 2, 3, 4).into(),
+            Ipv6Addr::LOCALHOST.into(),
+        ]);
         let ip = select_ip(DirectMode::Prefer64, &info).unwrap();
         assert_eq!(ip, IpAddr::V6(Ipv6Addr::LOCALHOST));
     }
@@ -970,11 +1680,10 @@ mod tests {
 
     #[test]
     fn test_select_ip_prefer46_both_prefers_v4() {
-        let info = ResolveInfo {
-            ipv4: Some(Ipv4Addr::new(1, 2, 3, 4)),
-            ipv6: Some(Ipv6Addr::LOCALHOST),
-            error: None,
-        };
+        let info = ResolveInfo::with_addrs(vec![
+            Ipv4Addr::new(1, 2, 3, 4).into(),
+            Ipv6Addr::LOCALHOST.into(),
+        ]);
         let ip = select_ip(DirectMode::Prefer46, &info).unwrap();
         assert_eq!(ip, IpAddr::V4(Ipv4Addr::new(1, 2, 3, 4)));
     }
@@ -1160,37 +1869,93 @@ mod tests {
         let _ = handle.join();
     }
 
+    #[cfg(unix)]
     #[test]
-    fn test_dial_tcp_ip_cancellable_skips_when_cancelled() {
+    fn test_happy_eyeballs_applies_tcp_options() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
         let direct = Direct::with_options(DirectOptions {
-            timeout: Some(Duration::from_secs(1)),
+            tcp_nodelay: true,
+            tcp_keepalive: Some(Duration::from_secs(30)),
             ..Default::default()
         })
         .unwrap();
-        let cancelled = Arc::new(AtomicBool::new(true)); // Already cancelled
 
-        // Should return None without attempting connection
-        let result =
-            direct.dial_tcp_ip_cancellable(IpAddr::V4(Ipv4Addr::LOCALHOST), 80, &cancelled);
-        assert!(result.is_none(), "Should skip connection when cancelled");
+        let stream = direct
+            .happy_eyeballs_dial_tcp(
+                &["127.0.0.2".parse().unwrap(), "127.0.0.1".parse().unwrap()],
+                port,
+            )
+            .unwrap();
+        let sock = socket2::SockRef::from(&stream);
+        assert!(sock.tcp_nodelay().unwrap());
+        assert!(sock.keepalive().unwrap());
+        // Returned to blocking mode like any other dialed stream
+        assert!(!sock.nonblocking().unwrap_or(false));
     }
 
     #[test]
-    fn test_dial_tcp_ip_cancellable_attempts_when_not_cancelled() {
+    fn test_interleave_families() {
+        let v4 = |n| IpAddr::V4(Ipv4Addr::new(10, 0, 0, n));
+        let v6 = |n| IpAddr::V6(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, n));
+        assert_eq!(
+            interleave_families(&[v6(1), v6(2), v6(3), v4(1), v4(1), v4(2)]),
+            vec![v6(1), v4(1), v6(2), v4(2), v6(3)]
+        );
+        assert_eq!(
+            interleave_families(&[v4(1), v4(2), v6(1)]),
+            vec![v4(1), v6(1), v4(2)]
+        );
+        assert!(interleave_families(&[]).is_empty());
+    }
+
+    // Only Linux routes all of 127.0.0.0/8 to loopback by default
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_happy_eyeballs_failure_starts_next_attempt() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        // Nothing listens on the other loopback addresses: refused at once,
+        // so the next attempt must not wait for the (long) attempt delay
         let direct = Direct::with_options(DirectOptions {
-            timeout: Some(Duration::from_secs(1)),
+            attempt_delay: Duration::from_secs(10),
             ..Default::default()
         })
         .unwrap();
-        let cancelled = Arc::new(AtomicBool::new(false)); // Not cancelled
+        let mut addr = Addr::new("multi.invalid", port);
+        addr.resolve_info = Some(build_resolve_info(&[
+            "127.0.0.2".parse().unwrap(),
+            "127.0.0.3".parse().unwrap(),
+            "127.0.0.1".parse().unwrap(),
+        ]));
 
-        // Should attempt connection (port 1 → connection refused)
-        let result = direct.dial_tcp_ip_cancellable(IpAddr::V4(Ipv4Addr::LOCALHOST), 1, &cancelled);
-        assert!(
-            result.is_some(),
-            "Should attempt connection when not cancelled"
-        );
-        assert!(result.unwrap().is_err(), "Port 1 should fail");
+        let start = std::time::Instant::now();
+        let conn = Outbound::dial_tcp(&direct, &mut addr).unwrap();
+        assert!(start.elapsed() < Duration::from_secs(5));
+        assert_eq!(conn.peer_addr().unwrap().port(), port);
+    }
+
+    #[test]
+    fn test_happy_eyeballs_races_stalled_attempt() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let direct = Direct::with_options(DirectOptions {
+            attempt_delay: Duration::from_millis(50),
+            timeout: Some(Duration::from_secs(10)),
+            ..Default::default()
+        })
+        .unwrap();
+        // TEST-NET-1 is never answered; the loopback attempt starts after
+        // the attempt delay instead of after the connect timeout
+        let mut addr = Addr::new("multi.invalid", port);
+        addr.resolve_info = Some(build_resolve_info(&[
+            "192.0.2.1".parse().unwrap(),
+            "127.0.0.1".parse().unwrap(),
+        ]));
+
+        let start = std::time::Instant::now();
+        assert!(Outbound::dial_tcp(&direct, &mut addr).is_ok());
+        assert!(start.elapsed() < Duration::from_secs(5));
     }
 
     #[test]
@@ -1206,11 +1971,10 @@ mod tests {
         .unwrap();
         let mut addr = Addr::new("test.invalid", 1);
         // Both loopback on port 1 → "connection refused"
-        addr.resolve_info = Some(ResolveInfo {
-            ipv4: Some(Ipv4Addr::LOCALHOST),
-            ipv6: Some(Ipv6Addr::LOCALHOST),
-            error: None,
-        });
+        addr.resolve_info = Some(ResolveInfo::with_addrs(vec![
+            Ipv4Addr::LOCALHOST.into(),
+            Ipv6Addr::LOCALHOST.into(),
+        ]));
 
         let result = Outbound::dial_tcp(&direct, &mut addr);
         match result {
@@ -1229,11 +1993,39 @@ mod tests {
             Ok(_) => panic!("Expected connection error on port 1"),
         }
     }
+
+    #[test]
+    fn test_direct_dial_uses_hosts_override() {
+        use super::Outbound;
+        use std::net::TcpListener;
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let handle = std::thread::spawn(move || {
+            let _ = listener.accept();
+        });
+
+        let hosts = Hosts::new().with_entry("*.hosts.invalid", IpAddr::V4(Ipv4Addr::LOCALHOST));
+        let direct = Direct::with_options(DirectOptions {
+            hosts: Some(Arc::new(hosts)),
+            ..Default::default()
+        })
+        .unwrap();
+
+        let mut addr = Addr::new("app.hosts.invalid", port);
+        let conn = Outbound::dial_tcp(&direct, &mut addr).unwrap();
+        assert_eq!(conn.peer_addr().unwrap().port(), port);
+        assert_eq!(addr.resolve_info.unwrap().ipv4, Some(Ipv4Addr::LOCALHOST));
+
+        drop(conn);
+        let _ = handle.join();
+    }
 }
 
 #[cfg(all(test, feature = "async"))]
 mod async_tests {
     use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
 
     #[tokio::test]
     async fn test_async_direct_resolve_ip() {
@@ -1262,6 +2054,27 @@ mod async_tests {
         accept_handle.await.ok();
     }
 
+    #[tokio::test]
+    async fn test_async_direct_dial_tcp_mptcp() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let accept_handle = tokio::spawn(async move { listener.accept().await.ok() });
+
+        let direct = Direct::with_options(DirectOptions {
+            mptcp: true,
+            ..Default::default()
+        })
+        .unwrap();
+        let mut addr = Addr::new("127.0.0.1", port);
+        let mut conn = AsyncOutbound::dial_tcp(&direct, &mut addr).await.unwrap();
+        conn.write_all(b"ping").await.unwrap();
+
+        let (mut server, _) = accept_handle.await.unwrap().unwrap();
+        let mut buf = [0u8; 4];
+        server.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"ping");
+    }
+
     #[tokio::test]
     async fn test_async_direct_dial_tcp_connection_refused() {
         let direct = Direct::new();
@@ -1271,6 +2084,47 @@ mod async_tests {
         assert!(result.is_err());
     }
 
+    #[tokio::test]
+    async fn test_async_happy_eyeballs() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let direct = Direct::with_options(DirectOptions {
+            attempt_delay: Duration::from_millis(50),
+            timeout: Some(Duration::from_secs(10)),
+            ..Default::default()
+        })
+        .unwrap();
+        let mut addr = Addr::new("multi.invalid", port);
+        addr.resolve_info = Some(build_resolve_info(&[
+            "192.0.2.1".parse().unwrap(),
+            "127.0.0.2".parse().unwrap(),
+            "127.0.0.1".parse().unwrap(),
+        ]));
+
+        let start = std::time::Instant::now();
+        let conn = AsyncOutbound::dial_tcp(&direct, &mut addr).await.unwrap();
+        assert!(start.elapsed() < Duration::from_secs(5));
+        assert_eq!(conn.peer_addr().unwrap().port(), port);
+
+        // Every attempt failing reports each address
+        drop(listener);
+        let mut addr = Addr::new("multi.invalid", port);
+        addr.resolve_info = Some(build_resolve_info(&[
+            "127.0.0.2".parse().unwrap(),
+            "127.0.0.1".parse().unwrap(),
+        ]));
+        let err = AsyncOutbound::dial_tcp(&direct, &mut addr)
+            .await
+            .err()
+            .unwrap()
+            .to_string();
+        assert!(
+            err.contains("127.0.0.2") && err.contains("127.0.0.1"),
+            "{}",
+            err
+        );
+    }
+
     #[tokio::test]
     async fn test_async_direct_dial_udp() {
         let direct = Direct::new();
@@ -1289,4 +2143,20 @@ mod async_tests {
         direct.async_resolve(&mut addr).await;
         assert!(addr.resolve_info.is_some());
     }
+
+    #[tokio::test]
+    async fn test_async_direct_resolve_uses_hosts_override() {
+        let hosts = Hosts::new().with_entry("db.hosts.invalid", "::1".parse().unwrap());
+        let direct = Direct::with_options(DirectOptions {
+            hosts: Some(Arc::new(hosts)),
+            ..Default::default()
+        })
+        .unwrap();
+
+        let mut addr = Addr::new("DB.hosts.invalid", 5432);
+        direct.async_resolve(&mut addr).await;
+        let info = addr.resolve_info.unwrap();
+        assert_eq!(info.ipv6, Some(Ipv6Addr::LOCALHOST));
+        assert!(info.ipv4.is_none());
+    }
 }
@@ -0,0 +1,622 @@
+//! Failover outbound group.
+//!
+//! [`FailoverGroup`] dials through the first healthy member in priority
+//! order. A member is marked unhealthy after consecutive connection failures
+//! or timeouts, skipped while unhealthy, and restored once a background
+//! [`HealthCheck`] probe through it succeeds again.
+
+use std::fmt;
+use std::sync::Arc;
+
+use parking_lot::Mutex;
+
+use crate::error::{AclError, OutboundErrorKind, Result};
+use crate::router::OutboundEntry;
+
+use super::group::validate_members;
+use super::health::{self, HealthCheck};
+use super::{Addr, Outbound, TcpConn, UdpConn};
+
+#[cfg(feature = "async")]
+use super::{AsyncOutbound, AsyncTcpConn, AsyncUdpConn};
+#[cfg(feature = "async")]
+use async_trait::async_trait;
+
+/// Default number of consecutive failures before a member is marked unhealthy
+pub const DEFAULT_MAX_FAILURES: u32 = 3;
+
+/// Snapshot of one member's health.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MemberStatus {
+    /// Member name
+    pub name: String,
+    /// Whether the member currently receives traffic
+    pub healthy: bool,
+    /// Connection failures since the last success
+    pub consecutive_failures: u32,
+    /// Last dial or probe error seen on this member
+    pub last_error: Option<String>,
+}
+
+#[derive(Debug, Default)]
+struct Health {
+    unhealthy: bool,
+    failures: u32,
+    last_error: Option<String>,
+}
+
+/// Outbound that fails over between members in priority order.
+///
+/// Generic over the outbound trait like [`OutboundEntry`]; use
+/// `FailoverGroup` for sync members and `AsyncFailoverGroup` for async ones.
+/// Only `ConnectionFailed` and `Timeout` errors count against a member and
+/// move the dial on to the next one; `CircuitOpen` moves on without
+/// counting, and other errors (rejected target, bad input, ...) are
+/// returned as is. When every member is unhealthy, all of them are tried
+/// in order rather than failing outright.
+pub struct FailoverGroup<T: ?Sized = dyn Outbound> {
+    members: Vec<OutboundEntry<T>>,
+    health: Vec<Mutex<Health>>,
+    health_check: HealthCheck,
+    max_failures: u32,
+}
+
+/// Async failover group (type alias for `FailoverGroup<dyn AsyncOutbound>`).
+#[cfg(feature = "async")]
+pub type AsyncFailoverGroup = FailoverGroup<dyn AsyncOutbound>;
+
+impl<T: ?Sized> fmt::Debug for FailoverGroup<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FailoverGroup")
+            .field("status", &self.status())
+            .field("health_check", &self.health_check)
+            .field("max_failures", &self.max_failures)
+            .finish()
+    }
+}
+
+impl<T: ?Sized> FailoverGroup<T> {
+    /// Create a new failover group. `members` are listed by priority.
+    ///
+    /// Returns `ConfigError` if `members` is empty or two members share a
+    /// name (compared case-insensitively, as in ACL rules).
+    pub fn new(members: Vec<OutboundEntry<T>>, health_check: HealthCheck) -> Result<Self> {
+        validate_members(&members, "Failover group")?;
+
+        let health = members.iter().map(|_| Mutex::default()).collect();
+        Ok(Self {
+            members,
+            health,
+            health_check,
+            max_failures: DEFAULT_MAX_FAILURES,
+        })
+    }
+
+    /// Set how many consecutive failures mark a member unhealthy (at least 1).
+    pub fn with_max_failures(mut self, max_failures: u32) -> Self {
+        self.max_failures = max_failures.max(1);
+        self
+    }
+
+    /// Get the group members in priority order.
+    pub fn members(&self) -> &[OutboundEntry<T>] {
+        &self.members
+    }
+
+    /// Get the health check settings.
+    pub fn health_check(&self) -> &HealthCheck {
+        &self.health_check
+    }
+
+    /// Name of the member new dials go to first, or `None` if every member
+    /// is unhealthy.
+    pub fn current(&self) -> Option<&str> {
+        self.members
+            .iter()
+            .zip(&self.health)
+            .find(|(_, h)| !h.lock().unhealthy)
+            .map(|(m, _)| m.name.as_str())
+    }
+
+    /// Get a health snapshot of every member, in priority order.
+    pub fn status(&self) -> Vec<MemberStatus> {
+        self.members
+            .iter()
+            .zip(&self.health)
+            .map(|(m, h)| {
+                let h = h.lock();
+                MemberStatus {
+                    name: m.name.clone(),
+                    healthy: !h.unhealthy,
+                    consecutive_failures: h.failures,
+                    last_error: h.last_error.clone(),
+                }
+            })
+            .collect()
+    }
+
+    /// Member indexes to try for a dial: healthy members in priority order,
+    /// or every member if none is healthy.
+    fn candidates(&self) -> Vec<usize> {
+        let healthy: Vec<usize> = (0..self.members.len())
+            .filter(|&i| !self.health[i].lock().unhealthy)
+            .collect();
+        if healthy.is_empty() {
+            (0..self.members.len()).collect()
+        } else {
+            healthy
+        }
+    }
+
+    /// Record a dial result. Returns true if the dial should fail over.
+    fn record<C>(&self, index: usize, result: &Result<C>) -> bool {
+        let mut health = self.health[index].lock();
+        match result {
+            Ok(_) => {
+                health.unhealthy = false;
+                health.failures = 0;
+                false
+            }
+            Err(err) if counts_as_failure(err) => {
+                health.failures = health.failures.saturating_add(1);
+                health.last_error = Some(err.to_string());
+                if health.failures >= self.max_failures {
+                    health.unhealthy = true;
+                }
+                true
+            }
+            // A member behind an open circuit breaker is skipped, but its
+            // own health tracking is left to the breaker
+            Err(AclError::OutboundError {
+                kind: OutboundErrorKind::CircuitOpen,
+                ..
+            }) => true,
+            Err(_) => false,
+        }
+    }
+
+    /// Record a health probe result for an unhealthy member.
+    fn record_probe(&self, index: usize, result: Result<()>) {
+        let mut health = self.health[index].lock();
+        match result {
+            Ok(()) => {
+                health.unhealthy = false;
+                health.failures = 0;
+            }
+            Err(err) => health.last_error = Some(err.to_string()),
+        }
+    }
+
+    /// Indexes of members currently marked unhealthy.
+    fn unhealthy(&self) -> Vec<usize> {
+        (0..self.members.len())
+            .filter(|&i| self.health[i].lock().unhealthy)
+            .collect()
+    }
+}
+
+/// Errors that indicate the member itself is unreachable.
+fn counts_as_failure(err: &AclError) -> bool {
+    matches!(
+        err,
+        AclError::OutboundError {
+            kind: OutboundErrorKind::ConnectionFailed | OutboundErrorKind::Timeout,
+            ..
+        }
+    )
+}
+
+impl FailoverGroup<dyn Outbound> {
+    /// Probe unhealthy members once and restore those that answer.
+    pub fn check_health(&self) {
+        for index in self.unhealthy() {
+            let result = self
+                .health_check
+                .probe(self.members[index].outbound.as_ref())
+                .map(drop);
+            self.record_probe(index, result);
+        }
+    }
+
+    /// Start probing unhealthy members from a background thread every
+    /// health check interval.
+    ///
+    /// The thread holds only a weak reference and exits once the group is
+    /// dropped.
+    pub fn spawn_health_check(self: &Arc<Self>) -> std::thread::JoinHandle<()> {
+        health::spawn_thread(
+            self,
+            self.health_check.interval(),
+            false,
+            Self::check_health,
+        )
+    }
+
+    fn dial_with<C>(
+        &self,
+        addr: &mut Addr,
+        dial: impl Fn(&dyn Outbound, &mut Addr) -> Result<C>,
+    ) -> Result<C> {
+        let mut last_err = None;
+        for index in self.candidates() {
+            // Each member dials its own copy; only the winner's resolve info
+            // is handed back to the caller
+            let mut attempt = addr.clone();
+            let result = dial(self.members[index].outbound.as_ref(), &mut attempt);
+            if result.is_ok() {
+                *addr = attempt;
+            }
+            if !self.record(index, &result) {
+                return result;
+            }
+            last_err = result.err();
+        }
+        Err(last_err.expect("failover group has at least one member"))
+    }
+}
+
+impl Outbound for FailoverGroup<dyn Outbound> {
+    fn dial_tcp(&self, addr: &mut Addr) -> Result<Box<dyn TcpConn>> {
+        self.dial_with(addr, |outbound, addr| outbound.dial_tcp(addr))
+    }
+
+    fn dial_udp(&self, addr: &mut Addr) -> Result<Box<dyn UdpConn>> {
+        self.dial_with(addr, |outbound, addr| outbound.dial_udp(addr))
+    }
+}
+
+#[cfg(feature = "async")]
+impl FailoverGroup<dyn AsyncOutbound> {
+    /// Async: Probe unhealthy members once and restore those that answer.
+    pub async fn check_health(&self) {
+        for index in self.unhealthy() {
+            let result = self
+                .health_check
+                .async_probe(self.members[index].outbound.as_ref())
+                .await
+                .map(drop);
+            self.record_probe(index, result);
+        }
+    }
+
+    /// Start probing unhealthy members from a tokio task every health check
+    /// interval. Must be called within a tokio runtime.
+    ///
+    /// The task holds only a weak reference and exits once the group is
+    /// dropped.
+    pub fn spawn_health_check(self: &Arc<Self>) -> tokio::task::JoinHandle<()> {
+        health::spawn_task(
+            self,
+            self.health_check.interval(),
+            false,
+            |group| async move { group.check_health().await },
+        )
+    }
+}
+
+#[cfg(feature = "async")]
+#[async_trait]
+impl AsyncOutbound for FailoverGroup<dyn AsyncOutbound> {
+    async fn dial_tcp(&self, addr: &mut Addr) -> Result<Box<dyn AsyncTcpConn>> {
+        let mut last_err = None;
+        for index in self.candidates() {
+            let outbound = &self.members[index].outbound;
+            let mut attempt = addr.clone();
+            let result = outbound.dial_tcp(&mut attempt).await;
+            if result.is_ok() {
+                *addr = attempt;
+            }
+            if !self.record(index, &result) {
+                return result;
+            }
+            last_err = result.err();
+        }
+        Err(last_err.expect("failover group has at least one member"))
+    }
+
+    async fn dial_udp(&self, addr: &mut Addr) -> Result<Box<dyn AsyncUdpConn>> {
+        let mut last_err = None;
+        for index in self.candidates() {
+            let outbound = &self.members[index].outbound;
+            let mut attempt = addr.clone();
+            let result = outbound.dial_udp(&mut attempt).await;
+            if result.is_ok() {
+                *addr = attempt;
+            }
+            if !self.record(index, &result) {
+                return result;
+            }
+            last_err = result.err();
+        }
+        Err(last_err.expect("failover group has at least one member"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::outbound::{Reject, ResolveInfo, StdTcpConn};
+    use std::net::{Ipv4Addr, TcpListener, TcpStream};
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    /// Member that connects to a local listener while `up`, and fails with
+    /// `ConnectionFailed` otherwise.
+    struct Switch {
+        listener: TcpListener,
+        up: AtomicBool,
+        dials: AtomicUsize,
+    }
+
+    impl Switch {
+        fn new(up: bool) -> Arc<Self> {
+            Arc::new(Self {
+                listener: TcpListener::bind("127.0.0.1:0").unwrap(),
+                up: AtomicBool::new(up),
+                dials: AtomicUsize::new(0),
+            })
+        }
+    }
+
+    impl Outbound for Switch {
+        fn dial_tcp(&self, addr: &mut Addr) -> Result<Box<dyn TcpConn>> {
+            self.dials.fetch_add(1, Ordering::Relaxed);
+            if !self.up.load(Ordering::Relaxed) {
+                addr.resolve_info = Some(ResolveInfo::from_error("down"));
+                return Err(AclError::OutboundError {
+                    kind: OutboundErrorKind::ConnectionFailed,
+                    message: "Failed to connect: connection refused".to_string(),
+                });
+            }
+            addr.resolve_info = Some(ResolveInfo::from_ipv4(Ipv4Addr::LOCALHOST));
+            let stream = TcpStream::connect(self.listener.local_addr().unwrap())?;
+            Ok(Box::new(StdTcpConn::new(stream)))
+        }
+
+        fn dial_udp(&self, addr: &mut Addr) -> Result<Box<dyn UdpConn>> {
+            Outbound::dial_udp(&Reject::new(), addr)
+        }
+    }
+
+    fn group(members: &[Arc<Switch>]) -> FailoverGroup {
+        let entries = members
+            .iter()
+            .enumerate()
+            .map(|(i, m)| OutboundEntry::new(format!("exit-{}", i), m.clone() as Arc<dyn Outbound>))
+            .collect();
+        let check = HealthCheck::new(Addr::new("probe.example", 80))
+            .with_interval(Duration::from_millis(20));
+        FailoverGroup::new(entries, check).unwrap()
+    }
+
+    #[test]
+    fn test_failover_rejects_empty_members() {
+        let empty: Vec<OutboundEntry> = vec![];
+        let check = HealthCheck::new(Addr::new("probe.example", 80));
+        assert!(matches!(
+            FailoverGroup::new(empty, check),
+            Err(AclError::ConfigError(_))
+        ));
+    }
+
+    #[test]
+    fn test_failover_marks_unhealthy_after_consecutive_failures() {
+        let members = [Switch::new(false), Switch::new(true)];
+        let group = group(&members).with_max_failures(2);
+
+        // Each dial falls through to exit-1 while exit-0 accumulates failures
+        Outbound::dial_tcp(&group, &mut Addr::new("example.com", 80)).unwrap();
+        assert_eq!(group.current(), Some("exit-0"));
+        Outbound::dial_tcp(&group, &mut Addr::new("example.com", 80)).unwrap();
+        assert_eq!(group.current(), Some("exit-1"));
+
+        let status = group.status();
+        assert!(!status[0].healthy);
+        assert_eq!(status[0].consecutive_failures, 2);
+        assert!(status[0].last_error.as_ref().unwrap().contains("refused"));
+        assert!(status[1].healthy);
+
+        // Unhealthy exit-0 is skipped
+        Outbound::dial_tcp(&group, &mut Addr::new("example.com", 80)).unwrap();
+        assert_eq!(members[0].dials.load(Ordering::Relaxed), 2);
+        assert_eq!(members[1].dials.load(Ordering::Relaxed), 3);
+    }
+
+    #[test]
+    fn test_failover_keeps_winner_resolve_info() {
+        let members = [Switch::new(false), Switch::new(true)];
+        let group = group(&members);
+        let mut addr = Addr::new("example.com", 80);
+        Outbound::dial_tcp(&group, &mut addr).unwrap();
+        let info = addr.resolve_info.unwrap();
+        assert_eq!(info.ipv4, Some(Ipv4Addr::LOCALHOST));
+        assert!(info.error.is_none());
+    }
+
+    #[test]
+    fn test_failover_success_resets_failures() {
+        let members = [Switch::new(false), Switch::new(true)];
+        let group = group(&members).with_max_failures(2);
+        Outbound::dial_tcp(&group, &mut Addr::new("example.com", 80)).unwrap();
+        members[0].up.store(true, Ordering::Relaxed);
+        Outbound::dial_tcp(&group, &mut Addr::new("example.com", 80)).unwrap();
+        assert_eq!(group.status()[0].consecutive_failures, 0);
+        assert_eq!(group.current(), Some("exit-0"));
+    }
+
+    #[test]
+    fn test_failover_does_not_fail_over_on_other_errors() {
+        let entries = vec![
+            OutboundEntry::new("reject", Arc::new(Reject::new()) as Arc<dyn Outbound>),
+            OutboundEntry::new("backup", Switch::new(true) as Arc<dyn Outbound>),
+        ];
+        let check = HealthCheck::new(Addr::new("probe.example", 80));
+        let group = FailoverGroup::new(entries, check).unwrap();
+        assert!(Outbound::dial_tcp(&group, &mut Addr::new("example.com", 80)).is_err());
+        assert!(group.status()[0].healthy);
+    }
+
+    #[test]
+    fn test_failover_skips_open_circuit_breaker() {
+        use crate::outbound::CircuitBreaker;
+
+        let primary = Switch::new(false);
+        let breaker = Arc::new(
+            CircuitBreaker::new(primary.clone() as Arc<dyn Outbound>).with_min_requests(1),
+        );
+        assert!(Outbound::dial_tcp(breaker.as_ref(), &mut Addr::new("example.com", 80)).is_err());
+
+        let entries = vec![
+            OutboundEntry::new("primary", breaker as Arc<dyn Outbound>),
+            OutboundEntry::new("backup", Switch::new(true) as Arc<dyn Outbound>),
+        ];
+        let check = HealthCheck::new(Addr::new("probe.example", 80));
+        let group = FailoverGroup::new(entries, check).unwrap();
+        Outbound::dial_tcp(&group, &mut Addr::new("example.com", 80)).unwrap();
+        assert_eq!(group.status()[0].consecutive_failures, 0);
+        assert_eq!(primary.dials.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn test_failover_all_unhealthy_tries_every_member() {
+        let members = [Switch::new(false), Switch::new(false)];
+        let group = group(&members).with_max_failures(1);
+        assert!(Outbound::dial_tcp(&group, &mut Addr::new("example.com", 80)).is_err());
+        assert_eq!(group.current(), None);
+
+        members[1].up.store(true, Ordering::Relaxed);
+        Outbound::dial_tcp(&group, &mut Addr::new("example.com", 80)).unwrap();
+        assert_eq!(group.current(), Some("exit-1"));
+    }
+
+    #[test]
+    fn test_failover_health_check_waits_one_interval() {
+        let members = [Switch::new(false), Switch::new(true)];
+        let entries = members
+            .iter()
+            .enumerate()
+            .map(|(i, m)| OutboundEntry::new(format!("exit-{}", i), m.clone() as Arc<dyn Outbound>))
+            .collect();
+        let check =
+            HealthCheck::new(Addr::new("probe.example", 80)).with_interval(Duration::from_secs(60));
+        let group = Arc::new(
+            FailoverGroup::new(entries, check)
+                .unwrap()
+                .with_max_failures(1),
+        );
+        Outbound::dial_tcp(group.as_ref(), &mut Addr::new("example.com", 80)).unwrap();
+        assert_eq!(members[0].dials.load(Ordering::Relaxed), 1);
+
+        // No probe round runs before the first interval elapses
+        let _handle = group.spawn_health_check();
+        std::thread::sleep(Duration::from_millis(100));
+        assert_eq!(members[0].dials.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn test_failover_health_check_restores_member() {
+        let members = [Switch::new(false), Switch::new(true)];
+        let group = Arc::new(group(&members).with_max_failures(1));
+        Outbound::dial_tcp(group.as_ref(), &mut Addr::new("example.com", 80)).unwrap();
+        assert_eq!(group.current(), Some("exit-1"));
+
+        // Probe fails while exit-0 is down, keeping it unhealthy
+        group.check_health();
+        assert!(!group.status()[0].healthy);
+
+        members[0].up.store(true, Ordering::Relaxed);
+        let handle = group.spawn_health_check();
+        let deadline = std::time::Instant::now() + Duration::from_secs(5);
+        while group.current() != Some("exit-0") {
+            assert!(std::time::Instant::now() < deadline, "member not restored");
+            std::thread::sleep(Duration::from_millis(10));
+        }
+
+        // The probe thread exits once the group is dropped
+        drop(group);
+        handle.join().unwrap();
+    }
+}
+
+#[cfg(all(test, feature = "async"))]
+mod async_tests {
+    use super::*;
+    use crate::outbound::TokioTcpConn;
+    use std::net::SocketAddr;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::time::Duration;
+    use tokio::net::{TcpListener, TcpStream};
+
+    struct Switch {
+        addr: SocketAddr,
+        _listener: TcpListener,
+        up: AtomicBool,
+    }
+
+    #[async_trait]
+    impl AsyncOutbound for Switch {
+        async fn dial_tcp(&self, _addr: &mut Addr) -> Result<Box<dyn AsyncTcpConn>> {
+            if !self.up.load(Ordering::Relaxed) {
+                return Err(AclError::OutboundError {
+                    kind: OutboundErrorKind::Timeout,
+                    message: "Connection timeout".to_string(),
+                });
+            }
+            let stream = TcpStream::connect(self.addr).await?;
+            Ok(Box::new(TokioTcpConn::new(stream)))
+        }
+
+        async fn dial_udp(&self, addr: &mut Addr) -> Result<Box<dyn AsyncUdpConn>> {
+            AsyncOutbound::dial_udp(&crate::outbound::Reject::new(), addr).await
+        }
+    }
+
+    async fn switch(up: bool) -> Arc<Switch> {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        Arc::new(Switch {
+            addr: listener.local_addr().unwrap(),
+            _listener: listener,
+            up: AtomicBool::new(up),
+        })
+    }
+
+    #[tokio::test]
+    async fn test_async_failover_and_restore() {
+        let primary = switch(false).await;
+        let backup = switch(true).await;
+        let entries = vec![
+            OutboundEntry::new("primary", primary.clone() as Arc<dyn AsyncOutbound>),
+            OutboundEntry::new("backup", backup as Arc<dyn AsyncOutbound>),
+        ];
+        let check = HealthCheck::new(Addr::new("probe.example", 80))
+            .with_interval(Duration::from_millis(20));
+        let group = Arc::new(
+            AsyncFailoverGroup::new(entries, check)
+                .unwrap()
+                .with_max_failures(1),
+        );
+
+        let mut addr = Addr::new("example.com", 443);
+        AsyncOutbound::dial_tcp(group.as_ref(), &mut addr)
+            .await
+            .unwrap();
+        assert_eq!(group.current(), Some("backup"));
+        assert_eq!(
+            group.status()[0].last_error.as_deref(),
+            Some("Outbound error: Connection timeout")
+        );
+
+        primary.up.store(true, Ordering::Relaxed);
+        let handle = group.spawn_health_check();
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while group.current() != Some("primary") {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("member not restored");
+
+        drop(group);
+        handle.await.unwrap();
+    }
+}
@@ -0,0 +1,710 @@
+//! Load-balancing outbound group.
+//!
+//! [`OutboundGroup`] spreads dials over a list of named members using a
+//! [`BalanceStrategy`]. The group is itself an outbound, so it can be
+//! registered under a name and referenced from ACL rules like any other exit.
+
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::error::{AclError, Result};
+use crate::router::OutboundEntry;
+
+use super::{Addr, Outbound, TcpConn, UdpConn};
+
+#[cfg(feature = "async")]
+use super::{AsyncOutbound, AsyncTcpConn, AsyncUdpConn};
+#[cfg(feature = "async")]
+use async_trait::async_trait;
+#[cfg(feature = "async")]
+use std::pin::Pin;
+#[cfg(feature = "async")]
+use std::task::{Context, Poll};
+#[cfg(feature = "async")]
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+/// Points each member occupies on the consistent-hash ring.
+const VIRTUAL_NODES: usize = 160;
+
+/// How an [`OutboundGroup`] picks a member for each dial.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BalanceStrategy {
+    /// Cycle through members in order
+    #[default]
+    RoundRobin,
+    /// Pick a member uniformly at random
+    Random,
+    /// Pick the member with the fewest open connections
+    LeastActive,
+    /// Hash the destination host, so a site sticks to one member
+    ConsistentHash,
+}
+
+impl BalanceStrategy {
+    /// Configuration name of the strategy.
+    pub fn name(&self) -> &'static str {
+        match self {
+            BalanceStrategy::RoundRobin => "round-robin",
+            BalanceStrategy::Random => "random",
+            BalanceStrategy::LeastActive => "least-active",
+            BalanceStrategy::ConsistentHash => "consistent-hash",
+        }
+    }
+}
+
+impl fmt::Display for BalanceStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for BalanceStrategy {
+    type Err = AclError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "round-robin" => Ok(BalanceStrategy::RoundRobin),
+            "random" => Ok(BalanceStrategy::Random),
+            "least-active" => Ok(BalanceStrategy::LeastActive),
+            "consistent-hash" => Ok(BalanceStrategy::ConsistentHash),
+            _ => Err(AclError::ConfigError(format!(
+                "Unknown balance strategy: {}",
+                s
+            ))),
+        }
+    }
+}
+
+/// Callback invoked with the chosen member name for every dial.
+type SelectHook = Arc<dyn Fn(&str, &Addr) + Send + Sync>;
+
+/// Outbound that balances dials over a set of named members.
+///
+/// Generic over the outbound trait like [`OutboundEntry`]; use
+/// `OutboundGroup` for sync members and `AsyncOutboundGroup` for async ones.
+/// Open connections are counted per member for the whole lifetime of the
+/// returned connection, whatever the strategy.
+pub struct OutboundGroup<T: ?Sized = dyn Outbound> {
+    members: Vec<OutboundEntry<T>>,
+    strategy: BalanceStrategy,
+    /// Round-robin cursor, also used to rotate least-active tie breaks
+    cursor: AtomicUsize,
+    /// Open connections per member
+    active: Vec<Arc<AtomicUsize>>,
+    /// Consistent-hash ring of `(point, member index)`, sorted by point
+    ring: Vec<(u64, usize)>,
+    on_select: Option<SelectHook>,
+}
+
+/// Async load-balancing group (type alias for `OutboundGroup<dyn AsyncOutbound>`).
+#[cfg(feature = "async")]
+pub type AsyncOutboundGroup = OutboundGroup<dyn AsyncOutbound>;
+
+impl<T: ?Sized> fmt::Debug for OutboundGroup<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OutboundGroup")
+            .field(
+                "members",
+                &self.members.iter().map(|m| &m.name).collect::<Vec<_>>(),
+            )
+            .field("strategy", &self.strategy)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<T: ?Sized> OutboundGroup<T> {
+    /// Create a new group.
+    ///
+    /// Returns `ConfigError` if `members` is empty or two members share a
+    /// name (compared case-insensitively, as in ACL rules).
+    pub fn new(members: Vec<OutboundEntry<T>>, strategy: BalanceStrategy) -> Result<Self> {
+        validate_members(&members, "Outbound group")?;
+
+        let ring = if strategy == BalanceStrategy::ConsistentHash {
+            build_ring(&members)
+        } else {
+            Vec::new()
+        };
+        let active = members
+            .iter()
+            .map(|_| Arc::new(AtomicUsize::new(0)))
+            .collect();
+
+        Ok(Self {
+            members,
+            strategy,
+            cursor: AtomicUsize::new(0),
+            active,
+            ring,
+            on_select: None,
+        })
+    }
+
+    /// Call `hook` with the chosen member name and destination on every dial.
+    pub fn with_select_hook(mut self, hook: impl Fn(&str, &Addr) + Send + Sync + 'static) -> Self {
+        self.on_select = Some(Arc::new(hook));
+        self
+    }
+
+    /// Get the balance strategy.
+    pub fn strategy(&self) -> BalanceStrategy {
+        self.strategy
+    }
+
+    /// Get the group members.
+    pub fn members(&self) -> &[OutboundEntry<T>] {
+        &self.members
+    }
+
+    /// Get the number of open connections per member, in member order.
+    pub fn active_connections(&self) -> Vec<(&str, usize)> {
+        self.members
+            .iter()
+            .zip(&self.active)
+            .map(|(m, n)| (m.name.as_str(), n.load(Ordering::Relaxed)))
+            .collect()
+    }
+
+    /// Pick the member for a dial to `addr`.
+    fn pick(&self, addr: &Addr) -> usize {
+        let len = self.members.len();
+        match self.strategy {
+            BalanceStrategy::RoundRobin => self.cursor.fetch_add(1, Ordering::Relaxed) % len,
+            BalanceStrategy::Random => {
+                let mut buf = [0u8; 8];
+                let n = match getrandom::getrandom(&mut buf) {
+                    Ok(()) => u64::from_ne_bytes(buf) as usize,
+                    Err(_) => self.cursor.fetch_add(1, Ordering::Relaxed),
+                };
+                n % len
+            }
+            BalanceStrategy::LeastActive => {
+                // Rotate the scan start so ties are shared out evenly
+                let start = self.cursor.fetch_add(1, Ordering::Relaxed);
+                (0..len)
+                    .map(|i| (start + i) % len)
+                    .min_by_key(|&i| self.active[i].load(Ordering::Relaxed))
+                    .unwrap_or(0)
+            }
+            BalanceStrategy::ConsistentHash => {
+                let point = fnv1a(addr.host.to_ascii_lowercase().as_bytes());
+                let pos = self.ring.partition_point(|&(p, _)| p < point);
+                self.ring[pos % self.ring.len()].1
+            }
+        }
+    }
+
+    /// Pick a member, report it and start counting the connection.
+    fn acquire(&self, addr: &Addr) -> (&Arc<T>, ActiveGuard) {
+        let index = self.pick(addr);
+        let member = &self.members[index];
+        if let Some(ref hook) = self.on_select {
+            hook(&member.name, addr);
+        }
+        (&member.outbound, ActiveGuard::new(&self.active[index]))
+    }
+}
+
+/// Reject an empty member list or two members sharing a name (compared
+/// case-insensitively, as in ACL rules). `kind` names the group in errors.
+pub(super) fn validate_members<T: ?Sized>(members: &[OutboundEntry<T>], kind: &str) -> Result<()> {
+    if members.is_empty() {
+        return Err(AclError::ConfigError(format!(
+            "{} must have at least one member",
+            kind
+        )));
+    }
+    for (i, member) in members.iter().enumerate() {
+        if members[..i]
+            .iter()
+            .any(|m| m.name.eq_ignore_ascii_case(&member.name))
+        {
+            return Err(AclError::ConfigError(format!(
+                "{} has duplicate member: {}",
+                kind, member.name
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Build the consistent-hash ring from member names.
+///
+/// Points depend only on names, so reordering members or restarting the
+/// process keeps every site on the same member.
+fn build_ring<T: ?Sized>(members: &[OutboundEntry<T>]) -> Vec<(u64, usize)> {
+    let mut ring = Vec::with_capacity(members.len() * VIRTUAL_NODES);
+    for (index, member) in members.iter().enumerate() {
+        let name = member.name.to_ascii_lowercase();
+        for vnode in 0..VIRTUAL_NODES {
+            ring.push((fnv1a(format!("{}#{}", name, vnode).as_bytes()), index));
+        }
+    }
+    ring.sort_unstable();
+    ring
+}
+
+/// 64-bit FNV-1a hash (stable across processes and platforms).
+pub(super) fn fnv1a(data: &[u8]) -> u64 {
+    data.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &b| {
+        (hash ^ b as u64).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+/// Counts one open connection on a member until dropped.
+struct ActiveGuard(Arc<AtomicUsize>);
+
+impl ActiveGuard {
+    fn new(counter: &Arc<AtomicUsize>) -> Self {
+        counter.fetch_add(1, Ordering::Relaxed);
+        Self(counter.clone())
+    }
+}
+
+impl Drop for ActiveGuard {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+impl Outbound for OutboundGroup<dyn Outbound> {
+    fn dial_tcp(&self, addr: &mut Addr) -> Result<Box<dyn TcpConn>> {
+        let (outbound, guard) = self.acquire(addr);
+        let inner = outbound.dial_tcp(addr)?;
+        Ok(Box::new(GroupTcpConn {
+            inner,
+            _guard: guard,
+        }))
+    }
+
+    fn dial_udp(&self, addr: &mut Addr) -> Result<Box<dyn UdpConn>> {
+        let (outbound, guard) = self.acquire(addr);
+        let inner = outbound.dial_udp(addr)?;
+        Ok(Box::new(GroupUdpConn {
+            inner,
+            _guard: guard,
+        }))
+    }
+}
+
+#[cfg(feature = "async")]
+#[async_trait]
+impl AsyncOutbound for OutboundGroup<dyn AsyncOutbound> {
+    async fn dial_tcp(&self, addr: &mut Addr) -> Result<Box<dyn AsyncTcpConn>> {
+        let (outbound, guard) = self.acquire(addr);
+        let inner = outbound.dial_tcp(addr).await?;
+        Ok(Box::new(AsyncGroupTcpConn {
+            inner,
+            _guard: guard,
+        }))
+    }
+
+    async fn dial_udp(&self, addr: &mut Addr) -> Result<Box<dyn AsyncUdpConn>> {
+        let (outbound, guard) = self.acquire(addr);
+        let inner = outbound.dial_udp(addr).await?;
+        Ok(Box::new(AsyncGroupUdpConn {
+            inner,
+            _guard: guard,
+        }))
+    }
+}
+
+/// TCP connection counted against its group member.
+struct GroupTcpConn {
+    inner: Box<dyn TcpConn>,
+    _guard: ActiveGuard,
+}
+
+impl Read for GroupTcpConn {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.inner.read(buf)
+    }
+}
+
+impl Write for GroupTcpConn {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl TcpConn for GroupTcpConn {
+    fn local_addr(&self) -> Result<SocketAddr> {
+        self.inner.local_addr()
+    }
+
+    fn peer_addr(&self) -> Result<SocketAddr> {
+        self.inner.peer_addr()
+    }
+
+    fn set_read_timeout(&self, dur: Option<Duration>) -> Result<()> {
+        self.inner.set_read_timeout(dur)
+    }
+
+    fn set_write_timeout(&self, dur: Option<Duration>) -> Result<()> {
+        self.inner.set_write_timeout(dur)
+    }
+
+    fn shutdown(&self, how: std::net::Shutdown) -> Result<()> {
+        self.inner.shutdown(how)
+    }
+
+    fn try_clone(&self) -> Result<Box<dyn TcpConn>> {
+        self.inner.try_clone()
+    }
+}
+
+/// UDP connection counted against its group member.
+struct GroupUdpConn {
+    inner: Box<dyn UdpConn>,
+    _guard: ActiveGuard,
+}
+
+impl UdpConn for GroupUdpConn {
+    fn read_from(&self, buf: &mut [u8]) -> Result<(usize, Addr)> {
+        self.inner.read_from(buf)
+    }
+
+    fn write_to(&self, buf: &[u8], addr: &Addr) -> Result<usize> {
+        self.inner.write_to(buf, addr)
+    }
+}
+
+/// Async TCP connection counted against its group member.
+#[cfg(feature = "async")]
+struct AsyncGroupTcpConn {
+    inner: Box<dyn AsyncTcpConn>,
+    _guard: ActiveGuard,
+}
+
+#[cfg(feature = "async")]
+impl AsyncRead for AsyncGroupTcpConn {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_read(cx, buf)
+    }
+}
+
+#[cfg(feature = "async")]
+impl AsyncWrite for AsyncGroupTcpConn {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.inner).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+#[cfg(feature = "async")]
+impl AsyncTcpConn for AsyncGroupTcpConn {
+    fn local_addr(&self) -> Result<SocketAddr> {
+        self.inner.local_addr()
+    }
+
+    fn peer_addr(&self) -> Result<SocketAddr> {
+        self.inner.peer_addr()
+    }
+}
+
+/// Async UDP connection counted against its group member.
+#[cfg(feature = "async")]
+struct AsyncGroupUdpConn {
+    inner: Box<dyn AsyncUdpConn>,
+    _guard: ActiveGuard,
+}
+
+#[cfg(feature = "async")]
+#[async_trait]
+impl AsyncUdpConn for AsyncGroupUdpConn {
+    async fn read_from(&self, buf: &mut [u8]) -> Result<(usize, Addr)> {
+        self.inner.read_from(buf).await
+    }
+
+    async fn write_to(&self, buf: &[u8], addr: &Addr) -> Result<usize> {
+        self.inner.write_to(buf, addr).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::outbound::{Direct, Reject, StdTcpConn};
+    use parking_lot::Mutex;
+    use std::net::{TcpListener, TcpStream};
+
+    /// Member that connects to a local listener and counts its dials.
+    struct Local {
+        listener: TcpListener,
+        dials: AtomicUsize,
+    }
+
+    impl Local {
+        fn new() -> Arc<Self> {
+            Arc::new(Self {
+                listener: TcpListener::bind("127.0.0.1:0").unwrap(),
+                dials: AtomicUsize::new(0),
+            })
+        }
+
+        fn dials(&self) -> usize {
+            self.dials.load(Ordering::Relaxed)
+        }
+    }
+
+    impl Outbound for Local {
+        fn dial_tcp(&self, _addr: &mut Addr) -> Result<Box<dyn TcpConn>> {
+            self.dials.fetch_add(1, Ordering::Relaxed);
+            let stream = TcpStream::connect(self.listener.local_addr().unwrap())?;
+            Ok(Box::new(StdTcpConn::new(stream)))
+        }
+
+        fn dial_udp(&self, addr: &mut Addr) -> Result<Box<dyn UdpConn>> {
+            Outbound::dial_udp(&Reject::new(), addr)
+        }
+    }
+
+    fn group(members: &[Arc<Local>], strategy: BalanceStrategy) -> OutboundGroup {
+        let entries = members
+            .iter()
+            .enumerate()
+            .map(|(i, m)| OutboundEntry::new(format!("exit-{}", i), m.clone() as Arc<dyn Outbound>))
+            .collect();
+        OutboundGroup::new(entries, strategy).unwrap()
+    }
+
+    #[test]
+    fn test_balance_strategy_from_str() {
+        for strategy in [
+            BalanceStrategy::RoundRobin,
+            BalanceStrategy::Random,
+            BalanceStrategy::LeastActive,
+            BalanceStrategy::ConsistentHash,
+        ] {
+            assert_eq!(
+                strategy.name().parse::<BalanceStrategy>().unwrap(),
+                strategy
+            );
+        }
+        assert_eq!(
+            "Least-Active".parse::<BalanceStrategy>().unwrap(),
+            BalanceStrategy::LeastActive
+        );
+        assert!(matches!(
+            "weighted".parse::<BalanceStrategy>(),
+            Err(AclError::ConfigError(_))
+        ));
+    }
+
+    #[test]
+    fn test_group_rejects_empty_and_duplicate_members() {
+        let empty: Vec<OutboundEntry> = vec![];
+        assert!(matches!(
+            OutboundGroup::new(empty, BalanceStrategy::RoundRobin),
+            Err(AclError::ConfigError(_))
+        ));
+
+        let direct: Arc<dyn Outbound> = Arc::new(Direct::new());
+        let entries = vec![
+            OutboundEntry::new("exit", direct.clone()),
+            OutboundEntry::new("EXIT", direct),
+        ];
+        assert!(matches!(
+            OutboundGroup::new(entries, BalanceStrategy::RoundRobin),
+            Err(AclError::ConfigError(_))
+        ));
+    }
+
+    #[test]
+    fn test_round_robin() {
+        let members = [Local::new(), Local::new(), Local::new()];
+        let group = group(&members, BalanceStrategy::RoundRobin);
+        for _ in 0..6 {
+            Outbound::dial_tcp(&group, &mut Addr::new("example.com", 80)).unwrap();
+        }
+        assert!(members.iter().all(|m| m.dials() == 2));
+    }
+
+    #[test]
+    fn test_random_uses_every_member() {
+        let members = [Local::new(), Local::new()];
+        let group = group(&members, BalanceStrategy::Random);
+        for _ in 0..64 {
+            Outbound::dial_tcp(&group, &mut Addr::new("example.com", 80)).unwrap();
+        }
+        assert_eq!(members[0].dials() + members[1].dials(), 64);
+        assert!(members.iter().all(|m| m.dials() > 0));
+    }
+
+    #[test]
+    fn test_least_active_tracks_open_connections() {
+        let members = [Local::new(), Local::new()];
+        let group = group(&members, BalanceStrategy::LeastActive);
+
+        let first = Outbound::dial_tcp(&group, &mut Addr::new("a.example", 80)).unwrap();
+        let _second = Outbound::dial_tcp(&group, &mut Addr::new("b.example", 80)).unwrap();
+        assert_eq!(
+            group.active_connections(),
+            vec![("exit-0", 1), ("exit-1", 1)]
+        );
+
+        // Closing the connection on exit-0 sends the next dial there
+        drop(first);
+        assert_eq!(
+            group.active_connections(),
+            vec![("exit-0", 0), ("exit-1", 1)]
+        );
+        let _third = Outbound::dial_tcp(&group, &mut Addr::new("c.example", 80)).unwrap();
+        assert_eq!(members[0].dials(), 2);
+        assert_eq!(
+            group.active_connections(),
+            vec![("exit-0", 1), ("exit-1", 1)]
+        );
+    }
+
+    #[test]
+    fn test_consistent_hash_sticks_to_member() {
+        let members = [Local::new(), Local::new(), Local::new()];
+        let group = group(&members, BalanceStrategy::ConsistentHash);
+        let picks: Vec<usize> = (0..50)
+            .map(|i| group.pick(&Addr::new(format!("site{}.example", i), 443)))
+            .collect();
+        // Same host (any case, any port) always maps to the same member
+        for (i, &pick) in picks.iter().enumerate() {
+            let host = format!("SITE{}.example", i);
+            assert_eq!(group.pick(&Addr::new(host, 80)), pick);
+        }
+        // Hosts are spread over all members
+        assert!((0..3).all(|m| picks.contains(&m)));
+
+        // Removing a member only moves the sites that were on it
+        let smaller = self::group(&members[..2], BalanceStrategy::ConsistentHash);
+        for (i, &pick) in picks.iter().enumerate() {
+            if pick < 2 {
+                let addr = Addr::new(format!("site{}.example", i), 443);
+                assert_eq!(smaller.pick(&addr), pick);
+            }
+        }
+    }
+
+    #[test]
+    fn test_select_hook_reports_member() {
+        let members = [Local::new(), Local::new()];
+        let chosen = Arc::new(Mutex::new(Vec::new()));
+        let log = chosen.clone();
+        let group = group(&members, BalanceStrategy::RoundRobin)
+            .with_select_hook(move |name, addr| log.lock().push(format!("{} -> {}", addr, name)));
+        Outbound::dial_tcp(&group, &mut Addr::new("a.example", 443)).unwrap();
+        Outbound::dial_tcp(&group, &mut Addr::new("b.example", 443)).unwrap();
+        assert_eq!(
+            *chosen.lock(),
+            vec!["a.example:443 -> exit-0", "b.example:443 -> exit-1"]
+        );
+    }
+
+    #[test]
+    fn test_failed_dial_is_not_counted() {
+        let entries = vec![OutboundEntry::new(
+            "blocked",
+            Arc::new(Reject::new()) as Arc<dyn Outbound>,
+        )];
+        let group = OutboundGroup::new(entries, BalanceStrategy::LeastActive).unwrap();
+        assert!(Outbound::dial_tcp(&group, &mut Addr::new("example.com", 80)).is_err());
+        assert_eq!(group.active_connections(), vec![("blocked", 0)]);
+    }
+
+    #[test]
+    fn test_group_as_named_rule_outbound() {
+        use crate::geo::NilGeoLoader;
+        use crate::router::{Router, RouterOptions};
+
+        let members = [Local::new(), Local::new()];
+        let group = Arc::new(group(&members, BalanceStrategy::RoundRobin));
+        let router = Router::new(
+            "pool(suffix:example.com)\nreject(all)",
+            vec![OutboundEntry::new(
+                "pool",
+                group.clone() as Arc<dyn Outbound>,
+            )],
+            &NilGeoLoader,
+            RouterOptions::new(),
+        )
+        .unwrap();
+
+        let conn = Outbound::dial_tcp(&router, &mut Addr::new("www.example.com", 443)).unwrap();
+        assert_eq!(members[0].dials(), 1);
+        assert_eq!(group.active_connections()[0].1, 1);
+        drop(conn);
+        assert_eq!(group.active_connections()[0].1, 0);
+        assert!(Outbound::dial_tcp(&router, &mut Addr::new("other.org", 443)).is_err());
+    }
+}
+
+#[cfg(all(test, feature = "async"))]
+mod async_tests {
+    use super::*;
+    use crate::outbound::TokioTcpConn;
+    use tokio::net::{TcpListener, TcpStream};
+
+    struct Local {
+        addr: SocketAddr,
+        _listener: TcpListener,
+    }
+
+    #[async_trait]
+    impl AsyncOutbound for Local {
+        async fn dial_tcp(&self, _addr: &mut Addr) -> Result<Box<dyn AsyncTcpConn>> {
+            let stream = TcpStream::connect(self.addr).await?;
+            Ok(Box::new(TokioTcpConn::new(stream)))
+        }
+
+        async fn dial_udp(&self, addr: &mut Addr) -> Result<Box<dyn AsyncUdpConn>> {
+            AsyncOutbound::dial_udp(&crate::outbound::Reject::new(), addr).await
+        }
+    }
+
+    async fn local() -> Arc<dyn AsyncOutbound> {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        Arc::new(Local {
+            addr: listener.local_addr().unwrap(),
+            _listener: listener,
+        })
+    }
+
+    #[tokio::test]
+    async fn test_async_least_active() {
+        let entries = vec![
+            OutboundEntry::new("a", local().await),
+            OutboundEntry::new("b", local().await),
+        ];
+        let group = AsyncOutboundGroup::new(entries, BalanceStrategy::LeastActive).unwrap();
+
+        let mut conns = Vec::new();
+        for _ in 0..4 {
+            let mut addr = Addr::new("example.com", 443);
+            conns.push(AsyncOutbound::dial_tcp(&group, &mut addr).await.unwrap());
+        }
+        assert_eq!(group.active_connections(), vec![("a", 2), ("b", 2)]);
+        conns.clear();
+        assert_eq!(group.active_connections(), vec![("a", 0), ("b", 0)]);
+    }
+}
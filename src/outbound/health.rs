@@ -0,0 +1,166 @@
+//! Active health probing for outbound groups.
+//!
+//! A [`HealthCheck`] describes how a member is probed: a TCP dial to a fixed
+//! target through the member, repeated on an interval. Groups drive the
+//! probes from a background thread (sync) or a tokio task (async) that holds
+//! only a weak reference and exits once the group is dropped.
+
+use std::sync::{Arc, Weak};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::error::{AclError, OutboundErrorKind, Result};
+
+use super::{Addr, Outbound};
+
+#[cfg(feature = "async")]
+use super::AsyncOutbound;
+#[cfg(feature = "async")]
+use std::future::Future;
+
+/// Default interval between health probes
+pub const DEFAULT_PROBE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Default time a health probe may take before it counts as failed
+pub const DEFAULT_PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Health check settings for outbound groups.
+#[derive(Debug, Clone)]
+pub struct HealthCheck {
+    /// Address dialed through each member
+    target: Addr,
+    /// Time between probe rounds
+    interval: Duration,
+    /// Maximum connect time for a successful probe
+    timeout: Duration,
+}
+
+impl HealthCheck {
+    /// Create a health check that dials `target` through each member.
+    pub fn new(target: Addr) -> Self {
+        Self {
+            target,
+            interval: DEFAULT_PROBE_INTERVAL,
+            timeout: DEFAULT_PROBE_TIMEOUT,
+        }
+    }
+
+    /// Set the time between probe rounds.
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Set the maximum connect time for a successful probe.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Get the probe target.
+    pub fn target(&self) -> &Addr {
+        &self.target
+    }
+
+    /// Get the time between probe rounds.
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    /// Get the probe timeout.
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    /// Dial the target through `outbound` and return the connect latency.
+    ///
+    /// Sync dials cannot be cancelled, so a probe slower than the timeout
+    /// runs to completion and is then reported as `Timeout`.
+    pub(crate) fn probe(&self, outbound: &dyn Outbound) -> Result<Duration> {
+        let start = Instant::now();
+        let conn = outbound.dial_tcp(&mut self.target.clone())?;
+        let elapsed = start.elapsed();
+        drop(conn);
+        if elapsed > self.timeout {
+            return Err(self.timeout_error());
+        }
+        Ok(elapsed)
+    }
+
+    /// Async: Dial the target through `outbound` and return the connect latency.
+    #[cfg(feature = "async")]
+    pub(crate) async fn async_probe(&self, outbound: &dyn AsyncOutbound) -> Result<Duration> {
+        let start = Instant::now();
+        let mut target = self.target.clone();
+        let conn = tokio::time::timeout(self.timeout, outbound.dial_tcp(&mut target))
+            .await
+            .map_err(|_| self.timeout_error())??;
+        let elapsed = start.elapsed();
+        drop(conn);
+        Ok(elapsed)
+    }
+
+    fn timeout_error(&self) -> AclError {
+        AclError::OutboundError {
+            kind: OutboundErrorKind::Timeout,
+            message: format!("Health check to {} timed out", self.target),
+        }
+    }
+}
+
+/// Run `round` on `state` every `interval` from a background thread,
+/// starting with an immediate round if `immediate` is set.
+///
+/// The thread exits at the first tick after the last strong reference to
+/// `state` is dropped.
+pub(crate) fn spawn_thread<S, F>(
+    state: &Arc<S>,
+    interval: Duration,
+    immediate: bool,
+    round: F,
+) -> thread::JoinHandle<()>
+where
+    S: Send + Sync + 'static + ?Sized,
+    F: Fn(&S) + Send + 'static,
+{
+    let weak: Weak<S> = Arc::downgrade(state);
+    thread::spawn(move || {
+        if !immediate {
+            thread::sleep(interval);
+        }
+        while let Some(state) = weak.upgrade() {
+            round(&state);
+            drop(state);
+            thread::sleep(interval);
+        }
+    })
+}
+
+/// Run `round` on `state` every `interval` from a tokio task, starting
+/// with an immediate round if `immediate` is set.
+///
+/// Must be called within a tokio runtime. The task exits at the first tick
+/// after the last strong reference to `state` is dropped.
+#[cfg(feature = "async")]
+pub(crate) fn spawn_task<S, F, Fut>(
+    state: &Arc<S>,
+    interval: Duration,
+    immediate: bool,
+    round: F,
+) -> tokio::task::JoinHandle<()>
+where
+    S: Send + Sync + 'static + ?Sized,
+    F: Fn(Arc<S>) -> Fut + Send + 'static,
+    Fut: Future<Output = ()> + Send,
+{
+    let weak: Weak<S> = Arc::downgrade(state);
+    tokio::spawn(async move {
+        if !immediate {
+            tokio::time::sleep(interval).await;
+        }
+        while let Some(state) = weak.upgrade() {
+            round(state).await;
+            tokio::time::sleep(interval).await;
+        }
+    })
+}
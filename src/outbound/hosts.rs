@@ -0,0 +1,306 @@
+//! Static hosts table.
+//!
+//! Answers name lookups from an in-memory table and from `/etc/hosts`-format
+//! files before the system resolver is consulted. Host names may use the same
+//! pattern syntax as ACL domain rules (`*.example.com`, `suffix:example.com`).
+
+use std::collections::HashMap;
+use std::fs;
+use std::net::IpAddr;
+use std::path::Path;
+
+use crate::error::{AclError, Result};
+use crate::matcher::{DomainMatcher, HostMatcher};
+use crate::types::HostInfo;
+
+use super::{build_resolve_info, Addr};
+
+/// Static host-to-address table.
+///
+/// Exact names are looked up first; wildcard and suffix patterns are then
+/// tried in insertion order. Each entry may map to several addresses, and
+/// repeated entries for the same name accumulate.
+#[derive(Debug, Clone, Default)]
+pub struct Hosts {
+    exact: HashMap<String, Vec<IpAddr>>,
+    patterns: Vec<(String, DomainMatcher, Vec<IpAddr>)>,
+}
+
+impl Hosts {
+    /// Create an empty hosts table.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parse a hosts table from `/etc/hosts`-format text.
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut hosts = Self::new();
+        hosts.load_str(text)?;
+        Ok(hosts)
+    }
+
+    /// Load a hosts table from an `/etc/hosts`-format file.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let mut hosts = Self::new();
+        hosts.load_file(path)?;
+        Ok(hosts)
+    }
+
+    /// Add an entry, returning `self` for chaining.
+    pub fn with_entry(mut self, host: &str, ip: IpAddr) -> Self {
+        self.insert(host, ip);
+        self
+    }
+
+    /// Add an address for a host name or pattern.
+    ///
+    /// Empty names are ignored. Duplicate addresses for the same name are
+    /// stored once.
+    pub fn insert(&mut self, host: &str, ip: IpAddr) {
+        let key = normalize_host(host);
+        if key.is_empty() {
+            return;
+        }
+
+        if key.starts_with("suffix:") || key.contains('*') {
+            if let Some((_, _, ips)) = self.patterns.iter_mut().find(|(p, _, _)| *p == key) {
+                push_unique(ips, ip);
+            } else {
+                let matcher = DomainMatcher::new(&key);
+                self.patterns.push((key, matcher, vec![ip]));
+            }
+        } else {
+            push_unique(self.exact.entry(key).or_default(), ip);
+        }
+    }
+
+    /// Merge entries from `/etc/hosts`-format text into this table.
+    ///
+    /// Each line is `address name [name...]`; `#` starts a comment.
+    /// IPv6 zone suffixes (`fe80::1%lo0`) are accepted and dropped.
+    pub fn load_str(&mut self, text: &str) -> Result<()> {
+        for (line_num, line) in text.lines().enumerate() {
+            let line = match line.find('#') {
+                Some(pos) => &line[..pos],
+                None => line,
+            };
+            let mut fields = line.split_whitespace();
+            let Some(ip_str) = fields.next() else {
+                continue;
+            };
+
+            let ip_part = ip_str.split_once('%').map_or(ip_str, |(ip, _)| ip);
+            let ip: IpAddr = ip_part.parse().map_err(|_| AclError::ParseError {
+                line: Some(line_num + 1),
+                message: format!("Invalid hosts address: {}", ip_str),
+            })?;
+
+            let mut has_name = false;
+            for name in fields {
+                self.insert(name, ip);
+                has_name = true;
+            }
+            if !has_name {
+                return Err(AclError::ParseError {
+                    line: Some(line_num + 1),
+                    message: format!("Missing host name for address: {}", ip_str),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Merge entries from an `/etc/hosts`-format file into this table.
+    pub fn load_file(&mut self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path).map_err(|e| AclError::ParseError {
+            line: None,
+            message: format!("Failed to read hosts file '{}': {}", path.display(), e),
+        })?;
+        self.load_str(&text)
+    }
+
+    /// Look up the addresses configured for a host name.
+    pub fn lookup(&self, host: &str) -> Option<&[IpAddr]> {
+        let name = normalize_host(host);
+        if name.is_empty() {
+            return None;
+        }
+        if let Some(ips) = self.exact.get(&name) {
+            return Some(ips);
+        }
+        let info = HostInfo {
+            name,
+            ipv4: None,
+            ipv6: None,
+        };
+        self.patterns
+            .iter()
+            .find(|(_, matcher, _)| matcher.matches(&info))
+            .map(|(_, _, ips)| ips.as_slice())
+    }
+
+    /// Number of distinct names and patterns in the table.
+    pub fn len(&self) -> usize {
+        self.exact.len() + self.patterns.len()
+    }
+
+    /// Returns true if the table has no entries.
+    pub fn is_empty(&self) -> bool {
+        self.exact.is_empty() && self.patterns.is_empty()
+    }
+
+    /// Fill `addr.resolve_info` from the table.
+    ///
+    /// Returns true if the address was answered by the table. Addresses that
+    /// already carry resolve info are left untouched.
+    pub(crate) fn resolve(&self, addr: &mut Addr) -> bool {
+        if addr.resolve_info.is_some() {
+            return false;
+        }
+        match self.lookup(&addr.host) {
+            Some(ips) => {
+                addr.resolve_info = Some(build_resolve_info(ips));
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// Lowercase and strip a trailing root dot (`example.com.`).
+fn normalize_host(host: &str) -> String {
+    let host = host.trim();
+    host.strip_suffix('.').unwrap_or(host).to_lowercase()
+}
+
+fn push_unique(ips: &mut Vec<IpAddr>, ip: IpAddr) {
+    if !ips.contains(&ip) {
+        ips.push(ip);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::outbound::ResolveInfo;
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    #[test]
+    fn test_hosts_exact_lookup() {
+        let hosts = Hosts::new()
+            .with_entry("Example.COM", "1.2.3.4".parse().unwrap())
+            .with_entry("example.com", "::1".parse().unwrap());
+        let ips = hosts.lookup("example.com.").unwrap();
+        assert_eq!(
+            ips,
+            &["1.2.3.4".parse::<IpAddr>().unwrap(), "::1".parse().unwrap()]
+        );
+        assert!(hosts.lookup("other.com").is_none());
+        assert_eq!(hosts.len(), 1);
+    }
+
+    #[test]
+    fn test_hosts_wildcard_and_suffix() {
+        let hosts = Hosts::new()
+            .with_entry("*.svc.local", "10.0.0.1".parse().unwrap())
+            .with_entry("suffix:corp.example", "10.0.0.2".parse().unwrap())
+            .with_entry("api.svc.local", "10.0.0.3".parse().unwrap());
+
+        // Exact entries win over patterns
+        assert_eq!(
+            hosts.lookup("api.svc.local").unwrap(),
+            &["10.0.0.3".parse::<IpAddr>().unwrap()]
+        );
+        assert_eq!(
+            hosts.lookup("db.svc.local").unwrap(),
+            &["10.0.0.1".parse::<IpAddr>().unwrap()]
+        );
+        assert_eq!(
+            hosts.lookup("corp.example").unwrap(),
+            &["10.0.0.2".parse::<IpAddr>().unwrap()]
+        );
+        assert_eq!(
+            hosts.lookup("a.b.corp.example").unwrap(),
+            &["10.0.0.2".parse::<IpAddr>().unwrap()]
+        );
+        assert!(hosts.lookup("svc.local").is_none());
+    }
+
+    #[test]
+    fn test_hosts_parse_file_format() {
+        let text = "\
+# comment line
+127.0.0.1   localhost  local.test   # trailing comment
+::1         localhost
+fe80::1%lo0 link.local
+
+10.1.1.1    *.dev.test
+";
+        let hosts = Hosts::parse(text).unwrap();
+        assert_eq!(
+            hosts.lookup("localhost").unwrap(),
+            &[
+                IpAddr::V4(Ipv4Addr::LOCALHOST),
+                IpAddr::V6(Ipv6Addr::LOCALHOST)
+            ]
+        );
+        assert_eq!(
+            hosts.lookup("local.test").unwrap(),
+            &[IpAddr::V4(Ipv4Addr::LOCALHOST)]
+        );
+        assert_eq!(
+            hosts.lookup("link.local").unwrap(),
+            &["fe80::1".parse::<IpAddr>().unwrap()]
+        );
+        assert!(hosts.lookup("x.dev.test").is_some());
+    }
+
+    #[test]
+    fn test_hosts_parse_errors_include_line() {
+        let err = Hosts::parse("127.0.0.1 ok\nnot-an-ip host\n").unwrap_err();
+        assert!(matches!(err, AclError::ParseError { line: Some(2), .. }));
+
+        let err = Hosts::parse("127.0.0.1\n").unwrap_err();
+        assert!(matches!(err, AclError::ParseError { line: Some(1), .. }));
+    }
+
+    #[test]
+    fn test_hosts_from_file() {
+        use std::io::Write;
+        let dir = std::env::temp_dir().join("acl_engine_test_hosts");
+        let _ = fs::create_dir_all(&dir);
+        let path = dir.join("hosts");
+        let mut f = fs::File::create(&path).unwrap();
+        writeln!(f, "192.0.2.10 file.test").unwrap();
+        drop(f);
+
+        let hosts = Hosts::from_file(&path).unwrap();
+        assert!(hosts.lookup("file.test").is_some());
+        assert!(Hosts::from_file(dir.join("missing")).is_err());
+
+        let _ = fs::remove_file(&path);
+        let _ = fs::remove_dir(&dir);
+    }
+
+    #[test]
+    fn test_hosts_resolve_fills_resolve_info() {
+        let hosts = Hosts::parse("192.0.2.1 a.test\n2001:db8::1 a.test").unwrap();
+
+        let mut addr = Addr::new("A.test", 80);
+        assert!(hosts.resolve(&mut addr));
+        let info = addr.resolve_info().unwrap();
+        assert_eq!(info.ipv4, Some("192.0.2.1".parse().unwrap()));
+        assert_eq!(info.ipv6, Some("2001:db8::1".parse().unwrap()));
+
+        // Existing resolve info is not overridden
+        let mut addr =
+            Addr::new("a.test", 80).with_resolve_info(ResolveInfo::from_ipv4(Ipv4Addr::LOCALHOST));
+        assert!(!hosts.resolve(&mut addr));
+        assert_eq!(addr.resolve_info().unwrap().ipv4, Some(Ipv4Addr::LOCALHOST));
+
+        let mut addr = Addr::new("b.test", 80);
+        assert!(!hosts.resolve(&mut addr));
+        assert!(addr.resolve_info().is_none());
+    }
+}
@@ -2,9 +2,23 @@
 //!
 //! This module provides various outbound connection types:
 //! - `Direct`: Direct connection with dual-stack support
-//! - `Reject`: Reject all connections
+//! - `Reject`: Reject all connections (error, drop, reset or HTTP block page)
+//! - `Socks4`: SOCKS4/SOCKS4a proxy connection
 //! - `Socks5`: SOCKS5 proxy connection
 //! - `Http`: HTTP/HTTPS proxy connection (CONNECT method)
+//! - `Shadowsocks`: Shadowsocks AEAD proxy connection
+//! - `OutboundGroup`: Load balancing over named member outbounds
+//! - `FailoverGroup`: Priority failover with background health checks
+//! - `UrlTestGroup`: Lowest-latency member selection
+//! - `Selector`: Member switchable by name at runtime
+//! - `Retry`: Retries transient dial failures with backoff
+//! - `CircuitBreaker`: Fails fast while an upstream keeps failing
+//! - `RateLimited`: Upload/download bandwidth shaping
+//!
+//! Proxy outbounds can reach their server through another outbound
+//! (`with_dialer`), forming proxy chains.
+//!
+//! [`Hosts`] provides static name overrides consulted before system DNS.
 
 use std::io::{self, Read, Write};
 use std::net::{IpAddr, SocketAddr, TcpStream};
@@ -17,15 +31,62 @@ use async_trait::async_trait;
 #[cfg(feature = "async")]
 use tokio::io::{AsyncRead, AsyncWrite};
 
+mod breaker;
+mod dialer;
+mod digest;
 mod direct;
+mod failover;
+mod group;
+mod health;
+mod hosts;
 mod http;
+mod ratelimit;
 mod reject;
+mod retry;
+mod selector;
+mod shadowsocks;
+mod socks4;
 mod socks5;
+mod tls;
+mod uot;
+mod urltest;
+
+pub use breaker::{
+    CircuitBreaker, CircuitState, DEFAULT_BREAKER_WINDOW, DEFAULT_FAILURE_RATE,
+    DEFAULT_MIN_REQUESTS, DEFAULT_OPEN_DURATION,
+};
+pub use direct::{Direct, DirectMode, DirectOptions, SourceStrategy};
+pub use failover::{FailoverGroup, MemberStatus, DEFAULT_MAX_FAILURES};
+pub use group::{BalanceStrategy, OutboundGroup};
+pub use health::{HealthCheck, DEFAULT_PROBE_INTERVAL, DEFAULT_PROBE_TIMEOUT};
+pub use hosts::Hosts;
+pub use http::{Http, HttpAuthScheme};
+pub use ratelimit::{RateLimitScope, RateLimited, RateLimiter};
+pub use reject::{Reject, RejectMode, DEFAULT_BLOCK_PAGE, DEFAULT_REJECT_DROP_TIMEOUT};
+pub use retry::{Retry, DEFAULT_RETRY_ATTEMPTS, DEFAULT_RETRY_BACKOFF, DEFAULT_RETRY_MAX_BACKOFF};
+pub use selector::Selector;
+pub use shadowsocks::{Shadowsocks, ShadowsocksCipher};
+pub use socks4::Socks4;
+pub use socks5::{Socks5, Socks5Bind};
+pub use tls::TlsOptions;
+pub use urltest::{UrlTestGroup, DEFAULT_LATENCY_TOLERANCE};
 
-pub use direct::{Direct, DirectMode, DirectOptions};
-pub use http::Http;
-pub use reject::Reject;
-pub use socks5::Socks5;
+#[cfg(feature = "async")]
+pub use breaker::AsyncCircuitBreaker;
+#[cfg(feature = "async")]
+pub use failover::AsyncFailoverGroup;
+#[cfg(feature = "async")]
+pub use group::AsyncOutboundGroup;
+#[cfg(feature = "async")]
+pub use ratelimit::AsyncRateLimited;
+#[cfg(feature = "async")]
+pub use retry::AsyncRetry;
+#[cfg(feature = "async")]
+pub use selector::AsyncSelector;
+#[cfg(feature = "async")]
+pub use socks5::AsyncSocks5Bind;
+#[cfg(feature = "async")]
+pub use urltest::AsyncUrlTestGroup;
 
 /// Default dialer timeout
 pub const DEFAULT_DIALER_TIMEOUT: Duration = Duration::from_secs(10);
@@ -172,6 +233,9 @@ pub struct ResolveInfo {
     pub ipv6: Option<std::net::Ipv6Addr>,
     /// Error message that occurred during resolution, if any
     pub error: Option<String>,
+    /// All resolved addresses in resolver order; `ipv4`/`ipv6` are the first
+    /// of each family. May be empty, in which case only `ipv4`/`ipv6` are used.
+    addrs: Vec<IpAddr>,
 }
 
 impl ResolveInfo {
@@ -184,26 +248,36 @@ impl ResolveInfo {
     pub fn from_ipv4(ipv4: std::net::Ipv4Addr) -> Self {
         Self {
             ipv4: Some(ipv4),
-            ipv6: None,
-            error: None,
+            ..Default::default()
         }
     }
 
     /// Create ResolveInfo from IPv6 address
     pub fn from_ipv6(ipv6: std::net::Ipv6Addr) -> Self {
         Self {
-            ipv4: None,
             ipv6: Some(ipv6),
+            ..Default::default()
+        }
+    }
+
+    /// Create ResolveInfo from all resolved addresses in resolver order.
+    ///
+    /// `ipv4`/`ipv6` are set to the first address of each family.
+    pub fn with_addrs(addrs: Vec<IpAddr>) -> Self {
+        let (ipv4, ipv6) = split_ipv4_ipv6(&addrs);
+        Self {
+            ipv4,
+            ipv6,
             error: None,
+            addrs,
         }
     }
 
     /// Create ResolveInfo with error
     pub fn from_error(error: impl Into<String>) -> Self {
         Self {
-            ipv4: None,
-            ipv6: None,
             error: Some(error.into()),
+            ..Default::default()
         }
     }
 
@@ -211,6 +285,16 @@ impl ResolveInfo {
     pub fn has_address(&self) -> bool {
         self.ipv4.is_some() || self.ipv6.is_some()
     }
+
+    /// All known addresses: `addrs` if set, otherwise `ipv6` then `ipv4`.
+    pub fn addresses(&self) -> Vec<IpAddr> {
+        if !self.addrs.is_empty() {
+            return self.addrs.clone();
+        }
+        let ipv6 = self.ipv6.map(IpAddr::V6);
+        let ipv4 = self.ipv4.map(IpAddr::V4);
+        ipv6.into_iter().chain(ipv4).collect()
+    }
 }
 
 /// Outbound connection interface.
@@ -249,6 +333,18 @@ pub trait TcpConn: Read + Write + Send + Sync {
 
     /// Shutdown the connection
     fn shutdown(&self, how: std::net::Shutdown) -> Result<()>;
+
+    /// Create an independent handle to the same connection, so one thread
+    /// can read while another writes.
+    ///
+    /// Returns `Unsupported` by default; connections that carry protocol
+    /// state (TLS, encryption) cannot be cloned.
+    fn try_clone(&self) -> Result<Box<dyn TcpConn>> {
+        Err(AclError::OutboundError {
+            kind: OutboundErrorKind::Unsupported,
+            message: "Connection cannot be cloned".to_string(),
+        })
+    }
 }
 
 /// Async TCP connection interface.
@@ -312,6 +408,10 @@ impl TcpConn for StdTcpConn {
     fn shutdown(&self, how: std::net::Shutdown) -> Result<()> {
         Ok(self.inner.shutdown(how)?)
     }
+
+    fn try_clone(&self) -> Result<Box<dyn TcpConn>> {
+        Ok(Box::new(StdTcpConn::new(self.inner.try_clone()?)))
+    }
 }
 
 /// UDP connection interface.
@@ -462,16 +562,66 @@ pub(crate) fn try_resolve_from_ip(addr: &mut Addr) -> bool {
 
 /// Build ResolveInfo from a list of resolved IP addresses.
 pub(crate) fn build_resolve_info(ips: &[IpAddr]) -> ResolveInfo {
-    let (ipv4, ipv6) = split_ipv4_ipv6(ips);
-    if ipv4.is_none() && ipv6.is_none() {
+    if ips.is_empty() {
         ResolveInfo::from_error("no address found")
     } else {
-        ResolveInfo {
-            ipv4,
-            ipv6,
-            error: None,
-        }
+        ResolveInfo::with_addrs(ips.to_vec())
+    }
+}
+
+/// Check that a DSCP value fits the 6-bit field.
+pub(crate) fn check_dscp(dscp: u8) -> Result<()> {
+    if dscp > 63 {
+        return Err(AclError::ConfigError(format!(
+            "DSCP value {} out of range (0-63)",
+            dscp
+        )));
     }
+    Ok(())
+}
+
+/// Mark a socket's packets with a DSCP value, via `IP_TOS` for IPv4 sockets
+/// and `IPV6_TCLASS` for IPv6 sockets.
+pub(crate) fn apply_dscp(socket: &socket2::Socket, ipv6: bool, dscp: u8) -> Result<()> {
+    check_dscp(dscp)?;
+    // DSCP is the upper six bits; the ECN bits are left to the kernel
+    let tos = (dscp as u32) << 2;
+    let result = if ipv6 {
+        set_tclass_v6(socket, tos)
+    } else {
+        socket.set_tos_v4(tos)
+    };
+    result.map_err(|e| AclError::OutboundError {
+        kind: OutboundErrorKind::Io,
+        message: format!("Failed to set DSCP: {}", e),
+    })
+}
+
+#[cfg(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "macos",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd"
+))]
+fn set_tclass_v6(socket: &socket2::Socket, tclass: u32) -> std::io::Result<()> {
+    socket.set_tclass_v6(tclass)
+}
+
+#[cfg(not(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "macos",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd"
+)))]
+fn set_tclass_v6(_socket: &socket2::Socket, _tclass: u32) -> std::io::Result<()> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "IPv6 traffic class is not supported on this platform",
+    ))
 }
 
 /// Split IP addresses into IPv4 and IPv6
@@ -751,6 +901,19 @@ mod tests {
         assert!(info.error.is_none());
     }
 
+    #[test]
+    fn test_resolve_info_with_addrs() {
+        let ips = vec![
+            IpAddr::V6(Ipv6Addr::LOCALHOST),
+            IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2)),
+            IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)),
+        ];
+        let info = ResolveInfo::with_addrs(ips.clone());
+        assert_eq!(info.ipv4, Some(Ipv4Addr::new(10, 0, 0, 2)));
+        assert_eq!(info.ipv6, Some(Ipv6Addr::LOCALHOST));
+        assert_eq!(info.addresses(), ips);
+    }
+
     // ===== Bug verification tests =====
 
     #[test]
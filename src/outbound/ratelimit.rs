@@ -0,0 +1,596 @@
+//! Bandwidth shaping for outbound connections.
+//!
+//! [`RateLimited`] wraps another outbound and throttles the connections it
+//! returns with token buckets, separately for upload (bytes written) and
+//! download (bytes read). A [`RateLimiter`] is one upload/download budget;
+//! the wrapper's [`RateLimitScope`] decides whether all connections share
+//! it, or each destination or connection gets a fresh budget at the same
+//! rates. Per-user limits are built by giving each user's outbounds a clone
+//! of that user's `RateLimiter`, since clones share the same budget.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use parking_lot::Mutex;
+
+use crate::error::Result;
+
+use super::{Addr, Outbound, TcpConn, UdpConn};
+
+#[cfg(feature = "async")]
+use super::{AsyncOutbound, AsyncTcpConn, AsyncUdpConn};
+#[cfg(feature = "async")]
+use async_trait::async_trait;
+#[cfg(feature = "async")]
+use std::future::Future;
+#[cfg(feature = "async")]
+use std::pin::Pin;
+#[cfg(feature = "async")]
+use std::task::{Context, Poll};
+#[cfg(feature = "async")]
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+/// Token bucket refilled at `rate` bytes per second, holding at most one
+/// second of traffic.
+///
+/// Consumption may drive the balance negative; the caller then waits until
+/// the debt is paid back, which keeps the long-run rate exact even for
+/// transfers larger than the bucket.
+#[derive(Debug)]
+struct TokenBucket {
+    rate: f64,
+    state: Mutex<(f64, Instant)>,
+}
+
+impl TokenBucket {
+    fn new(rate: u64) -> Self {
+        let rate = rate.max(1) as f64;
+        Self {
+            rate,
+            state: Mutex::new((rate, Instant::now())),
+        }
+    }
+
+    /// Largest chunk a single read or write should carry.
+    fn burst(&self) -> usize {
+        self.rate as usize
+    }
+
+    /// Take `n` bytes and return how long to wait before the next transfer.
+    fn consume(&self, n: usize) -> Duration {
+        let mut state = self.state.lock();
+        let now = Instant::now();
+        let (tokens, last) = *state;
+        let refilled = (tokens + now.duration_since(last).as_secs_f64() * self.rate).min(self.rate);
+        let left = refilled - n as f64;
+        *state = (left, now);
+        if left >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-left / self.rate)
+        }
+    }
+}
+
+/// An upload/download bandwidth budget in bytes per second.
+///
+/// Clones share the same budget.
+#[derive(Debug, Clone, Default)]
+pub struct RateLimiter {
+    upload: Option<Arc<TokenBucket>>,
+    download: Option<Arc<TokenBucket>>,
+}
+
+impl RateLimiter {
+    /// Create a budget; `None` leaves that direction unlimited.
+    pub fn new(upload: Option<u64>, download: Option<u64>) -> Self {
+        Self {
+            upload: upload.map(|rate| Arc::new(TokenBucket::new(rate))),
+            download: download.map(|rate| Arc::new(TokenBucket::new(rate))),
+        }
+    }
+
+    /// Upload rate in bytes per second, if limited.
+    pub fn upload(&self) -> Option<u64> {
+        self.upload.as_ref().map(|b| b.rate as u64)
+    }
+
+    /// Download rate in bytes per second, if limited.
+    pub fn download(&self) -> Option<u64> {
+        self.download.as_ref().map(|b| b.rate as u64)
+    }
+
+    /// A new, independent budget with the same rates.
+    fn fresh(&self) -> Self {
+        Self::new(self.upload(), self.download())
+    }
+
+    fn is_shared(&self) -> bool {
+        self.upload
+            .as_ref()
+            .is_some_and(|b| Arc::strong_count(b) > 1)
+            || self
+                .download
+                .as_ref()
+                .is_some_and(|b| Arc::strong_count(b) > 1)
+    }
+}
+
+/// Which connections share a [`RateLimited`] budget.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RateLimitScope {
+    /// All connections through the wrapper share one budget
+    #[default]
+    Shared,
+    /// Connections to the same destination host share a budget
+    PerDestination,
+    /// Every connection has its own budget
+    PerConnection,
+}
+
+/// Outbound wrapper that rate-limits the connections it returns.
+///
+/// Generic over the outbound trait; wrap an `Arc<dyn Outbound>` or an
+/// `Arc<dyn AsyncOutbound>`. UDP datagrams are throttled against the same
+/// budget as TCP bytes.
+pub struct RateLimited<T: ?Sized = dyn Outbound> {
+    inner: Arc<T>,
+    limiter: RateLimiter,
+    scope: RateLimitScope,
+    /// Budgets per destination host for `PerDestination`
+    destinations: Mutex<HashMap<String, RateLimiter>>,
+}
+
+/// Async rate-limit wrapper (type alias for `RateLimited<dyn AsyncOutbound>`).
+#[cfg(feature = "async")]
+pub type AsyncRateLimited = RateLimited<dyn AsyncOutbound>;
+
+impl<T: ?Sized> fmt::Debug for RateLimited<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RateLimited")
+            .field("upload", &self.limiter.upload())
+            .field("download", &self.limiter.download())
+            .field("scope", &self.scope)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<T: ?Sized> RateLimited<T> {
+    /// Wrap `inner` so all its connections share `limiter`.
+    pub fn new(inner: Arc<T>, limiter: RateLimiter) -> Self {
+        Self {
+            inner,
+            limiter,
+            scope: RateLimitScope::Shared,
+            destinations: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Set which connections share a budget.
+    ///
+    /// With `PerDestination` and `PerConnection`, `limiter` only provides the
+    /// rates for the budgets created on demand.
+    pub fn with_scope(mut self, scope: RateLimitScope) -> Self {
+        self.scope = scope;
+        self
+    }
+
+    /// Get the wrapped outbound.
+    pub fn inner(&self) -> &Arc<T> {
+        &self.inner
+    }
+
+    /// Budget for a new connection to `addr`.
+    fn limiter_for(&self, addr: &Addr) -> RateLimiter {
+        match self.scope {
+            RateLimitScope::Shared => self.limiter.clone(),
+            RateLimitScope::PerConnection => self.limiter.fresh(),
+            RateLimitScope::PerDestination => {
+                let mut destinations = self.destinations.lock();
+                // Forget destinations with no open connection left
+                destinations.retain(|_, limiter| limiter.is_shared());
+                destinations
+                    .entry(addr.host.to_ascii_lowercase())
+                    .or_insert_with(|| self.limiter.fresh())
+                    .clone()
+            }
+        }
+    }
+}
+
+/// Bytes one read or write may transfer so a single call stays within one
+/// burst of `bucket`.
+fn chunk_len(bucket: &Option<Arc<TokenBucket>>, len: usize) -> usize {
+    match bucket {
+        Some(bucket) => len.min(bucket.burst()),
+        None => len,
+    }
+}
+
+/// Charge `n` bytes to `bucket` and return the wait, if any.
+fn charge(bucket: &Option<Arc<TokenBucket>>, n: usize) -> Duration {
+    match bucket {
+        Some(bucket) if n > 0 => bucket.consume(n),
+        _ => Duration::ZERO,
+    }
+}
+
+impl Outbound for RateLimited<dyn Outbound> {
+    fn dial_tcp(&self, addr: &mut Addr) -> Result<Box<dyn TcpConn>> {
+        let limiter = self.limiter_for(addr);
+        let inner = self.inner.dial_tcp(addr)?;
+        Ok(Box::new(RateLimitedTcpConn { inner, limiter }))
+    }
+
+    fn dial_udp(&self, addr: &mut Addr) -> Result<Box<dyn UdpConn>> {
+        let limiter = self.limiter_for(addr);
+        let inner = self.inner.dial_udp(addr)?;
+        Ok(Box::new(RateLimitedUdpConn { inner, limiter }))
+    }
+}
+
+#[cfg(feature = "async")]
+#[async_trait]
+impl AsyncOutbound for RateLimited<dyn AsyncOutbound> {
+    async fn dial_tcp(&self, addr: &mut Addr) -> Result<Box<dyn AsyncTcpConn>> {
+        let limiter = self.limiter_for(addr);
+        let inner = self.inner.dial_tcp(addr).await?;
+        Ok(Box::new(AsyncRateLimitedTcpConn {
+            inner,
+            limiter,
+            read_delay: None,
+            write_delay: None,
+        }))
+    }
+
+    async fn dial_udp(&self, addr: &mut Addr) -> Result<Box<dyn AsyncUdpConn>> {
+        let limiter = self.limiter_for(addr);
+        let inner = self.inner.dial_udp(addr).await?;
+        Ok(Box::new(AsyncRateLimitedUdpConn { inner, limiter }))
+    }
+}
+
+/// Rate-limited TCP connection.
+struct RateLimitedTcpConn {
+    inner: Box<dyn TcpConn>,
+    limiter: RateLimiter,
+}
+
+impl Read for RateLimitedTcpConn {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = chunk_len(&self.limiter.download, buf.len());
+        let n = self.inner.read(&mut buf[..len])?;
+        std::thread::sleep(charge(&self.limiter.download, n));
+        Ok(n)
+    }
+}
+
+impl Write for RateLimitedTcpConn {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let len = chunk_len(&self.limiter.upload, buf.len());
+        let n = self.inner.write(&buf[..len])?;
+        std::thread::sleep(charge(&self.limiter.upload, n));
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl TcpConn for RateLimitedTcpConn {
+    fn local_addr(&self) -> Result<SocketAddr> {
+        self.inner.local_addr()
+    }
+
+    fn peer_addr(&self) -> Result<SocketAddr> {
+        self.inner.peer_addr()
+    }
+
+    fn set_read_timeout(&self, dur: Option<Duration>) -> Result<()> {
+        self.inner.set_read_timeout(dur)
+    }
+
+    fn set_write_timeout(&self, dur: Option<Duration>) -> Result<()> {
+        self.inner.set_write_timeout(dur)
+    }
+
+    fn shutdown(&self, how: std::net::Shutdown) -> Result<()> {
+        self.inner.shutdown(how)
+    }
+
+    fn try_clone(&self) -> Result<Box<dyn TcpConn>> {
+        Ok(Box::new(RateLimitedTcpConn {
+            inner: self.inner.try_clone()?,
+            limiter: self.limiter.clone(),
+        }))
+    }
+}
+
+/// Rate-limited UDP connection.
+struct RateLimitedUdpConn {
+    inner: Box<dyn UdpConn>,
+    limiter: RateLimiter,
+}
+
+impl UdpConn for RateLimitedUdpConn {
+    fn read_from(&self, buf: &mut [u8]) -> Result<(usize, Addr)> {
+        let (n, addr) = self.inner.read_from(buf)?;
+        std::thread::sleep(charge(&self.limiter.download, n));
+        Ok((n, addr))
+    }
+
+    fn write_to(&self, buf: &[u8], addr: &Addr) -> Result<usize> {
+        std::thread::sleep(charge(&self.limiter.upload, buf.len()));
+        self.inner.write_to(buf, addr)
+    }
+}
+
+/// Async rate-limited TCP connection.
+///
+/// Each transfer is charged after it completes; the resulting wait delays
+/// the next read or write in the same direction.
+#[cfg(feature = "async")]
+struct AsyncRateLimitedTcpConn {
+    inner: Box<dyn AsyncTcpConn>,
+    limiter: RateLimiter,
+    read_delay: Option<Pin<Box<tokio::time::Sleep>>>,
+    write_delay: Option<Pin<Box<tokio::time::Sleep>>>,
+}
+
+/// Wait out a pending delay, if any.
+#[cfg(feature = "async")]
+fn poll_delay(delay: &mut Option<Pin<Box<tokio::time::Sleep>>>, cx: &mut Context<'_>) -> Poll<()> {
+    if let Some(sleep) = delay {
+        std::task::ready!(sleep.as_mut().poll(cx));
+        *delay = None;
+    }
+    Poll::Ready(())
+}
+
+/// Schedule the wait for `n` transferred bytes.
+#[cfg(feature = "async")]
+fn schedule(
+    delay: &mut Option<Pin<Box<tokio::time::Sleep>>>,
+    bucket: &Option<Arc<TokenBucket>>,
+    n: usize,
+) {
+    let wait = charge(bucket, n);
+    if !wait.is_zero() {
+        *delay = Some(Box::pin(tokio::time::sleep(wait)));
+    }
+}
+
+#[cfg(feature = "async")]
+impl AsyncRead for AsyncRateLimitedTcpConn {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        std::task::ready!(poll_delay(&mut this.read_delay, cx));
+        let len = chunk_len(&this.limiter.download, buf.remaining());
+        let mut chunk = ReadBuf::new(buf.initialize_unfilled_to(len));
+        std::task::ready!(Pin::new(&mut this.inner).poll_read(cx, &mut chunk))?;
+        let n = chunk.filled().len();
+        buf.advance(n);
+        schedule(&mut this.read_delay, &this.limiter.download, n);
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(feature = "async")]
+impl AsyncWrite for AsyncRateLimitedTcpConn {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        std::task::ready!(poll_delay(&mut this.write_delay, cx));
+        let len = chunk_len(&this.limiter.upload, buf.len());
+        let n = std::task::ready!(Pin::new(&mut this.inner).poll_write(cx, &buf[..len]))?;
+        schedule(&mut this.write_delay, &this.limiter.upload, n);
+        Poll::Ready(Ok(n))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_shutdown(cx)
+    }
+}
+
+#[cfg(feature = "async")]
+impl AsyncTcpConn for AsyncRateLimitedTcpConn {
+    fn local_addr(&self) -> Result<SocketAddr> {
+        self.inner.local_addr()
+    }
+
+    fn peer_addr(&self) -> Result<SocketAddr> {
+        self.inner.peer_addr()
+    }
+}
+
+/// Async rate-limited UDP connection.
+#[cfg(feature = "async")]
+struct AsyncRateLimitedUdpConn {
+    inner: Box<dyn AsyncUdpConn>,
+    limiter: RateLimiter,
+}
+
+#[cfg(feature = "async")]
+#[async_trait]
+impl AsyncUdpConn for AsyncRateLimitedUdpConn {
+    async fn read_from(&self, buf: &mut [u8]) -> Result<(usize, Addr)> {
+        let (n, addr) = self.inner.read_from(buf).await?;
+        tokio::time::sleep(charge(&self.limiter.download, n)).await;
+        Ok((n, addr))
+    }
+
+    async fn write_to(&self, buf: &[u8], addr: &Addr) -> Result<usize> {
+        tokio::time::sleep(charge(&self.limiter.upload, buf.len())).await;
+        self.inner.write_to(buf, addr).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::outbound::Direct;
+    use std::net::TcpListener;
+
+    #[test]
+    fn test_token_bucket_debt() {
+        let bucket = TokenBucket::new(1000);
+        // Starts full: one second of traffic passes without waiting
+        assert_eq!(bucket.consume(1000), Duration::ZERO);
+        let wait = bucket.consume(500);
+        assert!(wait > Duration::from_millis(450) && wait <= Duration::from_millis(500));
+        assert!(bucket.consume(500) > Duration::from_millis(950));
+    }
+
+    #[test]
+    fn test_rate_limiter_clones_share_budget() {
+        let limiter = RateLimiter::new(Some(1000), None);
+        let clone = limiter.clone();
+        assert_eq!(limiter.upload(), Some(1000));
+        assert_eq!(limiter.download(), None);
+        assert_eq!(charge(&limiter.upload, 1000), Duration::ZERO);
+        assert!(charge(&clone.upload, 100) > Duration::ZERO);
+        assert_eq!(charge(&limiter.fresh().upload, 1000), Duration::ZERO);
+        assert_eq!(charge(&limiter.download, 1 << 20), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_per_destination_budgets() {
+        let limited = RateLimited::new(
+            Arc::new(Direct::new()) as Arc<dyn Outbound>,
+            RateLimiter::new(Some(1000), Some(1000)),
+        )
+        .with_scope(RateLimitScope::PerDestination);
+
+        let a = limited.limiter_for(&Addr::new("a.example", 80));
+        let a_upper = limited.limiter_for(&Addr::new("A.example", 443));
+        let b = limited.limiter_for(&Addr::new("b.example", 80));
+        assert!(Arc::ptr_eq(
+            a.upload.as_ref().unwrap(),
+            a_upper.upload.as_ref().unwrap()
+        ));
+        assert!(!Arc::ptr_eq(
+            a.upload.as_ref().unwrap(),
+            b.upload.as_ref().unwrap()
+        ));
+
+        // Budgets of closed connections are dropped
+        drop((a, a_upper, b));
+        limited.limiter_for(&Addr::new("c.example", 80));
+        assert_eq!(limited.destinations.lock().len(), 1);
+    }
+
+    #[test]
+    fn test_rate_limited_upload() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut received = Vec::new();
+            stream.read_to_end(&mut received).unwrap();
+            received.len()
+        });
+
+        let limited = RateLimited::new(
+            Arc::new(Direct::new()) as Arc<dyn Outbound>,
+            RateLimiter::new(Some(10_000), None),
+        );
+        let mut conn = Outbound::dial_tcp(&limited, &mut Addr::new("127.0.0.1", port)).unwrap();
+        let start = Instant::now();
+        // 10 KB burst, then 3 KB of debt at 10 KB/s
+        conn.write_all(&[0u8; 13_000]).unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(250));
+        conn.shutdown(std::net::Shutdown::Write).unwrap();
+        assert_eq!(server.join().unwrap(), 13_000);
+    }
+
+    #[test]
+    fn test_rate_limited_read_capped_to_burst() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            stream.write_all(&[7u8; 8192]).unwrap();
+        });
+
+        let limited = RateLimited::new(
+            Arc::new(Direct::new()) as Arc<dyn Outbound>,
+            RateLimiter::new(None, Some(1000)),
+        );
+        let mut conn = Outbound::dial_tcp(&limited, &mut Addr::new("127.0.0.1", port)).unwrap();
+        server.join().unwrap();
+        let mut buf = [0u8; 65536];
+        let start = Instant::now();
+        assert_eq!(conn.read(&mut buf).unwrap(), 1000);
+        // The first burst is free; nothing is borrowed against the budget
+        assert!(start.elapsed() < Duration::from_millis(500));
+    }
+}
+
+#[cfg(all(test, feature = "async"))]
+mod async_tests {
+    use super::*;
+    use crate::outbound::Direct;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    #[tokio::test]
+    async fn test_async_rate_limited_download() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            stream.write_all(&[7u8; 13_000]).await.unwrap();
+        });
+
+        let limited = AsyncRateLimited::new(
+            Arc::new(Direct::new()) as Arc<dyn AsyncOutbound>,
+            RateLimiter::new(None, Some(10_000)),
+        );
+        let mut addr = Addr::new("127.0.0.1", port);
+        let mut conn = AsyncOutbound::dial_tcp(&limited, &mut addr).await.unwrap();
+        server.await.unwrap();
+
+        let start = Instant::now();
+        let mut received = Vec::new();
+        conn.read_to_end(&mut received).await.unwrap();
+        assert_eq!(received.len(), 13_000);
+        assert!(start.elapsed() >= Duration::from_millis(250));
+    }
+
+    #[tokio::test]
+    async fn test_async_rate_limited_read_capped_to_burst() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            stream.write_all(&[7u8; 8192]).await.unwrap();
+        });
+
+        let limited = AsyncRateLimited::new(
+            Arc::new(Direct::new()) as Arc<dyn AsyncOutbound>,
+            RateLimiter::new(None, Some(1000)),
+        );
+        let mut addr = Addr::new("127.0.0.1", port);
+        let mut conn = AsyncOutbound::dial_tcp(&limited, &mut addr).await.unwrap();
+        server.await.unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        let mut buf = vec![0u8; 65536];
+        assert_eq!(conn.read(&mut buf).await.unwrap(), 1000);
+    }
+}
@@ -1,6 +1,13 @@
 //! Reject outbound implementation.
 //!
-//! Rejects all connection attempts.
+//! Rejects all connection attempts, either with an immediate error or by
+//! handing out a fake connection (silent, reset, or serving a block page).
+
+use std::io::{self, Read, Write};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::time::{Duration, Instant};
+
+use parking_lot::Mutex;
 
 use crate::error::{AclError, OutboundErrorKind, Result};
 
@@ -8,62 +15,376 @@ use crate::error::{AclError, OutboundErrorKind, Result};
 use super::{AsyncOutbound, AsyncTcpConn, AsyncUdpConn};
 #[cfg(feature = "async")]
 use async_trait::async_trait;
+#[cfg(feature = "async")]
+use std::future::Future;
+#[cfg(feature = "async")]
+use std::pin::Pin;
+#[cfg(feature = "async")]
+use std::task::{Context, Poll};
+#[cfg(feature = "async")]
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
 
 use super::{Addr, Outbound, TcpConn, UdpConn};
 
+/// Default time a dropped connection stays silent before closing.
+pub const DEFAULT_REJECT_DROP_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Default body served by [`RejectMode::BlockPage`].
+pub const DEFAULT_BLOCK_PAGE: &str =
+    "<html><body><h1>403 Forbidden</h1><p>Access to this site is blocked.</p></body></html>\n";
+
+/// How [`Reject`] refuses a connection.
+///
+/// Each mode is available in rule text under its built-in outbound name.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum RejectMode {
+    /// Fail the dial immediately (`reject`)
+    #[default]
+    Error,
+    /// Return a connection that discards writes and never yields data,
+    /// closing after the timeout (`reject-drop`)
+    Drop(Duration),
+    /// Return a connection that fails every read and write with
+    /// `ConnectionReset` (`reject-reset`)
+    Reset,
+    /// Answer TCP port 80 with an HTTP 403 response carrying this body;
+    /// other traffic fails like `Error` (`reject-page`)
+    BlockPage(String),
+}
+
 /// Reject outbound that rejects all connections.
-pub struct Reject;
+#[derive(Debug, Default)]
+pub struct Reject {
+    mode: RejectMode,
+}
 
 impl Reject {
-    /// Create a new Reject outbound.
+    /// Create a new Reject outbound that fails dials immediately.
     pub fn new() -> Self {
-        Self
+        Self::default()
+    }
+
+    /// Create a new Reject outbound with the given mode.
+    pub fn with_mode(mode: RejectMode) -> Self {
+        Self { mode }
+    }
+
+    /// Get the reject mode.
+    pub fn mode(&self) -> &RejectMode {
+        &self.mode
+    }
+
+    /// Fake connection to hand out for a TCP dial, or `None` to fail it.
+    fn tcp_behavior(&self, addr: &Addr) -> Option<Rejected> {
+        match self.mode {
+            RejectMode::Error => None,
+            RejectMode::Drop(timeout) => Some(Rejected::Silent(Instant::now() + timeout)),
+            RejectMode::Reset => Some(Rejected::Reset),
+            RejectMode::BlockPage(ref body) if addr.port == 80 => {
+                Some(Rejected::Page(io::Cursor::new(block_page(body))))
+            }
+            RejectMode::BlockPage(_) => None,
+        }
+    }
+
+    /// Fake connection to hand out for a UDP dial, or `None` to fail it.
+    fn udp_behavior(&self) -> Option<Rejected> {
+        match self.mode {
+            RejectMode::Drop(timeout) => Some(Rejected::Silent(Instant::now() + timeout)),
+            RejectMode::Reset => Some(Rejected::Reset),
+            RejectMode::Error | RejectMode::BlockPage(_) => None,
+        }
     }
 }
 
-impl Default for Reject {
-    fn default() -> Self {
-        Self::new()
+fn rejected() -> AclError {
+    AclError::OutboundError {
+        kind: OutboundErrorKind::Unsupported,
+        message: "Connection rejected".to_string(),
     }
 }
 
+fn reset() -> io::Error {
+    io::Error::new(io::ErrorKind::ConnectionReset, "Connection rejected")
+}
+
+/// Build the full HTTP 403 response for `body`.
+fn block_page(body: &str) -> Vec<u8> {
+    format!(
+        "HTTP/1.1 403 Forbidden\r\n\
+         Content-Type: text/html; charset=utf-8\r\n\
+         Content-Length: {}\r\n\
+         Connection: close\r\n\
+         \r\n\
+         {}",
+        body.len(),
+        body
+    )
+    .into_bytes()
+}
+
+/// Peer address reported by fake connections: the target if it is known.
+fn peer_of(addr: &Addr) -> Option<SocketAddr> {
+    let ip = addr.host.parse::<IpAddr>().ok().or_else(|| {
+        let info = addr.resolve_info.as_ref()?;
+        info.ipv4
+            .map(IpAddr::V4)
+            .or_else(|| info.ipv6.map(IpAddr::V6))
+    })?;
+    Some(SocketAddr::new(ip, addr.port))
+}
+
+fn peer_result(peer: Option<SocketAddr>) -> Result<SocketAddr> {
+    peer.ok_or_else(|| AclError::OutboundError {
+        kind: OutboundErrorKind::Unsupported,
+        message: "Rejected connection has no peer address".to_string(),
+    })
+}
+
+const UNSPECIFIED: SocketAddr = SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0);
+
+/// Behavior of a fake rejected connection.
+enum Rejected {
+    /// Swallow writes; reads wait until the instant, then report EOF
+    Silent(Instant),
+    /// Fail all I/O with `ConnectionReset`
+    Reset,
+    /// Swallow writes; reads return the response, then EOF
+    Page(io::Cursor<Vec<u8>>),
+}
+
 impl Outbound for Reject {
-    fn dial_tcp(&self, _addr: &mut Addr) -> Result<Box<dyn TcpConn>> {
-        Err(AclError::OutboundError {
-            kind: OutboundErrorKind::Unsupported,
-            message: "Connection rejected".to_string(),
-        })
+    fn dial_tcp(&self, addr: &mut Addr) -> Result<Box<dyn TcpConn>> {
+        let behavior = self.tcp_behavior(addr).ok_or_else(rejected)?;
+        Ok(Box::new(RejectedTcpConn {
+            behavior,
+            read_timeout: Mutex::new(None),
+            peer: peer_of(addr),
+        }))
+    }
+
+    fn dial_udp(&self, addr: &mut Addr) -> Result<Box<dyn UdpConn>> {
+        let behavior = self.udp_behavior().ok_or_else(rejected)?;
+        Ok(Box::new(RejectedUdpConn {
+            behavior,
+            peer: addr.clone(),
+        }))
+    }
+}
+
+/// Fake TCP connection returned by non-error reject modes.
+struct RejectedTcpConn {
+    behavior: Rejected,
+    read_timeout: Mutex<Option<Duration>>,
+    peer: Option<SocketAddr>,
+}
+
+impl Read for RejectedTcpConn {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self.behavior {
+            Rejected::Silent(until) => {
+                let remaining = until.saturating_duration_since(Instant::now());
+                match *self.read_timeout.get_mut() {
+                    Some(timeout) if timeout < remaining => {
+                        std::thread::sleep(timeout);
+                        Err(io::ErrorKind::WouldBlock.into())
+                    }
+                    _ => {
+                        std::thread::sleep(remaining);
+                        Ok(0)
+                    }
+                }
+            }
+            Rejected::Reset => Err(reset()),
+            Rejected::Page(ref mut response) => response.read(buf),
+        }
+    }
+}
+
+impl Write for RejectedTcpConn {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self.behavior {
+            Rejected::Reset => Err(reset()),
+            _ => Ok(buf.len()),
+        }
     }
 
-    fn dial_udp(&self, _addr: &mut Addr) -> Result<Box<dyn UdpConn>> {
-        Err(AclError::OutboundError {
-            kind: OutboundErrorKind::Unsupported,
-            message: "Connection rejected".to_string(),
-        })
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl TcpConn for RejectedTcpConn {
+    fn local_addr(&self) -> Result<SocketAddr> {
+        Ok(UNSPECIFIED)
+    }
+
+    fn peer_addr(&self) -> Result<SocketAddr> {
+        peer_result(self.peer)
+    }
+
+    fn set_read_timeout(&self, dur: Option<Duration>) -> Result<()> {
+        *self.read_timeout.lock() = dur;
+        Ok(())
+    }
+
+    fn set_write_timeout(&self, _dur: Option<Duration>) -> Result<()> {
+        Ok(())
+    }
+
+    fn shutdown(&self, _how: std::net::Shutdown) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Fake UDP connection returned by non-error reject modes.
+struct RejectedUdpConn {
+    behavior: Rejected,
+    peer: Addr,
+}
+
+impl RejectedUdpConn {
+    fn write(&self, buf: &[u8]) -> Result<usize> {
+        match self.behavior {
+            Rejected::Reset => Err(reset().into()),
+            _ => Ok(buf.len()),
+        }
+    }
+
+    /// Time to stay silent before failing a read.
+    fn silence(&self) -> Result<Duration> {
+        match self.behavior {
+            Rejected::Silent(until) => Ok(until.saturating_duration_since(Instant::now())),
+            _ => Err(reset().into()),
+        }
+    }
+
+    fn timed_out(&self) -> AclError {
+        AclError::OutboundError {
+            kind: OutboundErrorKind::Timeout,
+            message: format!("No response from {}", self.peer),
+        }
+    }
+}
+
+impl UdpConn for RejectedUdpConn {
+    fn read_from(&self, _buf: &mut [u8]) -> Result<(usize, Addr)> {
+        std::thread::sleep(self.silence()?);
+        Err(self.timed_out())
+    }
+
+    fn write_to(&self, buf: &[u8], _addr: &Addr) -> Result<usize> {
+        self.write(buf)
     }
 }
 
 #[cfg(feature = "async")]
 #[async_trait]
 impl AsyncOutbound for Reject {
-    async fn dial_tcp(&self, _addr: &mut Addr) -> Result<Box<dyn AsyncTcpConn>> {
-        Err(AclError::OutboundError {
-            kind: OutboundErrorKind::Unsupported,
-            message: "Connection rejected".to_string(),
-        })
+    async fn dial_tcp(&self, addr: &mut Addr) -> Result<Box<dyn AsyncTcpConn>> {
+        let behavior = self.tcp_behavior(addr).ok_or_else(rejected)?;
+        let silence = match behavior {
+            Rejected::Silent(until) => Some(Box::pin(tokio::time::sleep_until(until.into()))),
+            _ => None,
+        };
+        Ok(Box::new(AsyncRejectedTcpConn {
+            behavior,
+            silence,
+            peer: peer_of(addr),
+        }))
     }
 
-    async fn dial_udp(&self, _addr: &mut Addr) -> Result<Box<dyn AsyncUdpConn>> {
-        Err(AclError::OutboundError {
-            kind: OutboundErrorKind::Unsupported,
-            message: "Connection rejected".to_string(),
-        })
+    async fn dial_udp(&self, addr: &mut Addr) -> Result<Box<dyn AsyncUdpConn>> {
+        let behavior = self.udp_behavior().ok_or_else(rejected)?;
+        Ok(Box::new(RejectedUdpConn {
+            behavior,
+            peer: addr.clone(),
+        }))
+    }
+}
+
+/// Async fake TCP connection returned by non-error reject modes.
+#[cfg(feature = "async")]
+struct AsyncRejectedTcpConn {
+    behavior: Rejected,
+    silence: Option<Pin<Box<tokio::time::Sleep>>>,
+    peer: Option<SocketAddr>,
+}
+
+#[cfg(feature = "async")]
+impl AsyncRead for AsyncRejectedTcpConn {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        match this.behavior {
+            Rejected::Silent(_) => match this.silence {
+                Some(ref mut sleep) => sleep.as_mut().poll(cx).map(Ok),
+                None => Poll::Ready(Ok(())),
+            },
+            Rejected::Reset => Poll::Ready(Err(reset())),
+            Rejected::Page(ref mut response) => {
+                let n = response.read(buf.initialize_unfilled())?;
+                buf.advance(n);
+                Poll::Ready(Ok(()))
+            }
+        }
+    }
+}
+
+#[cfg(feature = "async")]
+impl AsyncWrite for AsyncRejectedTcpConn {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        match self.behavior {
+            Rejected::Reset => Poll::Ready(Err(reset())),
+            _ => Poll::Ready(Ok(buf.len())),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(feature = "async")]
+impl AsyncTcpConn for AsyncRejectedTcpConn {
+    fn local_addr(&self) -> Result<SocketAddr> {
+        Ok(UNSPECIFIED)
+    }
+
+    fn peer_addr(&self) -> Result<SocketAddr> {
+        peer_result(self.peer)
+    }
+}
+
+#[cfg(feature = "async")]
+#[async_trait]
+impl AsyncUdpConn for RejectedUdpConn {
+    async fn read_from(&self, _buf: &mut [u8]) -> Result<(usize, Addr)> {
+        tokio::time::sleep(self.silence()?).await;
+        Err(self.timed_out())
+    }
+
+    async fn write_to(&self, buf: &[u8], _addr: &Addr) -> Result<usize> {
+        self.write(buf)
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{Addr, Outbound, Reject};
+    use super::{Addr, Outbound, Reject, RejectMode};
+    use std::io::{ErrorKind, Read, Write};
+    use std::time::{Duration, Instant};
 
     #[test]
     fn test_reject_tcp() {
@@ -80,11 +401,74 @@ mod tests {
         let result = reject.dial_udp(&mut addr);
         assert!(result.is_err());
     }
+
+    #[test]
+    fn test_reject_drop() {
+        let reject = Reject::with_mode(RejectMode::Drop(Duration::from_millis(200)));
+        let mut conn = reject.dial_tcp(&mut Addr::new("1.2.3.4", 443)).unwrap();
+        assert_eq!(conn.peer_addr().unwrap(), "1.2.3.4:443".parse().unwrap());
+        assert_eq!(conn.write(b"hello").unwrap(), 5);
+
+        conn.set_read_timeout(Some(Duration::from_millis(50)))
+            .unwrap();
+        let mut buf = [0u8; 16];
+        assert_eq!(
+            conn.read(&mut buf).unwrap_err().kind(),
+            ErrorKind::WouldBlock
+        );
+
+        conn.set_read_timeout(None).unwrap();
+        let start = Instant::now();
+        assert_eq!(conn.read(&mut buf).unwrap(), 0);
+        assert!(start.elapsed() >= Duration::from_millis(100));
+
+        let udp = reject.dial_udp(&mut Addr::new("1.2.3.4", 53)).unwrap();
+        assert_eq!(
+            udp.write_to(b"query", &Addr::new("1.2.3.4", 53)).unwrap(),
+            5
+        );
+        assert!(udp.read_from(&mut buf).is_err());
+    }
+
+    #[test]
+    fn test_reject_reset() {
+        let reject = Reject::with_mode(RejectMode::Reset);
+        let mut conn = reject.dial_tcp(&mut Addr::new("example.com", 443)).unwrap();
+        assert!(conn.peer_addr().is_err());
+        let mut buf = [0u8; 16];
+        assert_eq!(
+            conn.read(&mut buf).unwrap_err().kind(),
+            ErrorKind::ConnectionReset
+        );
+        assert_eq!(
+            conn.write(b"x").unwrap_err().kind(),
+            ErrorKind::ConnectionReset
+        );
+    }
+
+    #[test]
+    fn test_reject_block_page() {
+        let reject = Reject::with_mode(RejectMode::BlockPage("blocked".to_string()));
+        let mut conn = reject.dial_tcp(&mut Addr::new("example.com", 80)).unwrap();
+        conn.write_all(b"GET / HTTP/1.1\r\nHost: example.com\r\n\r\n")
+            .unwrap();
+        let mut response = String::new();
+        conn.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 403 Forbidden\r\n"));
+        assert!(response.contains("Content-Length: 7\r\n"));
+        assert!(response.ends_with("\r\n\r\nblocked"));
+
+        // Only plain HTTP gets the page
+        assert!(reject.dial_tcp(&mut Addr::new("example.com", 443)).is_err());
+        assert!(reject.dial_udp(&mut Addr::new("example.com", 80)).is_err());
+    }
 }
 
 #[cfg(all(test, feature = "async"))]
 mod async_tests {
-    use super::{Addr, AsyncOutbound, Reject};
+    use super::{Addr, AsyncOutbound, Reject, RejectMode};
+    use std::time::Duration;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
 
     #[tokio::test]
     async fn test_async_reject_tcp() {
@@ -109,4 +493,39 @@ mod async_tests {
             Ok(_) => panic!("Expected error"),
         }
     }
+
+    #[tokio::test]
+    async fn test_async_reject_drop() {
+        let reject = Reject::with_mode(RejectMode::Drop(Duration::from_millis(200)));
+        let mut conn = reject
+            .dial_tcp(&mut Addr::new("example.com", 443))
+            .await
+            .unwrap();
+        conn.write_all(b"hello").await.unwrap();
+
+        let mut buf = [0u8; 16];
+        let silent = tokio::time::timeout(Duration::from_millis(50), conn.read(&mut buf)).await;
+        assert!(silent.is_err(), "dropped connection should stay silent");
+        assert_eq!(conn.read(&mut buf).await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_async_reject_page_and_reset() {
+        let reject = Reject::with_mode(RejectMode::BlockPage("nope".to_string()));
+        let mut conn = reject
+            .dial_tcp(&mut Addr::new("example.com", 80))
+            .await
+            .unwrap();
+        let mut response = String::new();
+        conn.read_to_string(&mut response).await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 403 Forbidden"));
+        assert!(response.ends_with("nope"));
+
+        let reject = Reject::with_mode(RejectMode::Reset);
+        let mut conn = reject
+            .dial_tcp(&mut Addr::new("example.com", 80))
+            .await
+            .unwrap();
+        assert!(conn.read(&mut [0u8; 4]).await.is_err());
+    }
 }
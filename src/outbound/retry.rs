@@ -0,0 +1,419 @@
+//! Retry wrapper for outbounds.
+//!
+//! [`Retry`] retries failed dials on the wrapped outbound when the error is
+//! transient (`ConnectionFailed`, `Timeout`, `DnsFailed`), waiting an
+//! exponentially growing, jittered delay between attempts and giving up at
+//! an optional overall deadline. Other errors, such as `AuthFailed` or
+//! `InvalidInput`, are returned immediately.
+
+use std::fmt;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::error::{AclError, OutboundErrorKind, Result};
+
+use super::{Addr, Outbound, TcpConn, UdpConn};
+
+#[cfg(feature = "async")]
+use super::{AsyncOutbound, AsyncTcpConn, AsyncUdpConn};
+#[cfg(feature = "async")]
+use async_trait::async_trait;
+
+/// Default number of attempts, including the first one
+pub const DEFAULT_RETRY_ATTEMPTS: u32 = 3;
+
+/// Default delay before the first retry
+pub const DEFAULT_RETRY_BACKOFF: Duration = Duration::from_millis(100);
+
+/// Default upper bound for the delay between attempts
+pub const DEFAULT_RETRY_MAX_BACKOFF: Duration = Duration::from_secs(2);
+
+/// Outbound wrapper that retries transient dial failures.
+///
+/// Generic over the outbound trait; wrap an `Arc<dyn Outbound>` or an
+/// `Arc<dyn AsyncOutbound>`. The delay before retry `n` is
+/// `backoff * 2^(n-1)`, capped at `max_backoff`; with jitter enabled a
+/// random value between half and the full delay is used instead.
+pub struct Retry<T: ?Sized = dyn Outbound> {
+    inner: Arc<T>,
+    max_attempts: u32,
+    backoff: Duration,
+    max_backoff: Duration,
+    jitter: bool,
+    deadline: Option<Duration>,
+}
+
+/// Async retry wrapper (type alias for `Retry<dyn AsyncOutbound>`).
+#[cfg(feature = "async")]
+pub type AsyncRetry = Retry<dyn AsyncOutbound>;
+
+impl<T: ?Sized> fmt::Debug for Retry<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Retry")
+            .field("max_attempts", &self.max_attempts)
+            .field("backoff", &self.backoff)
+            .field("max_backoff", &self.max_backoff)
+            .field("jitter", &self.jitter)
+            .field("deadline", &self.deadline)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<T: ?Sized> Retry<T> {
+    /// Wrap `inner` with the default retry policy.
+    pub fn new(inner: Arc<T>) -> Self {
+        Self {
+            inner,
+            max_attempts: DEFAULT_RETRY_ATTEMPTS,
+            backoff: DEFAULT_RETRY_BACKOFF,
+            max_backoff: DEFAULT_RETRY_MAX_BACKOFF,
+            jitter: true,
+            deadline: None,
+        }
+    }
+
+    /// Set the total number of attempts, including the first (at least 1).
+    pub fn with_max_attempts(mut self, attempts: u32) -> Self {
+        self.max_attempts = attempts.max(1);
+        self
+    }
+
+    /// Set the initial delay and its upper bound.
+    pub fn with_backoff(mut self, initial: Duration, max: Duration) -> Self {
+        self.backoff = initial;
+        self.max_backoff = max.max(initial);
+        self
+    }
+
+    /// Enable or disable jitter (enabled by default).
+    pub fn with_jitter(mut self, jitter: bool) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    /// Set an overall deadline across all attempts and delays.
+    ///
+    /// No retry is started that would begin after the deadline. Async dials
+    /// are also cancelled when the deadline passes; sync dials cannot be, so
+    /// the last one may run over.
+    pub fn with_deadline(mut self, deadline: Duration) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// Get the wrapped outbound.
+    pub fn inner(&self) -> &Arc<T> {
+        &self.inner
+    }
+
+    /// Delay before retry number `retry` (1-based).
+    fn delay(&self, retry: u32) -> Duration {
+        let factor = 1u32.checked_shl(retry - 1).unwrap_or(u32::MAX);
+        let delay = self.backoff.saturating_mul(factor).min(self.max_backoff);
+        if !self.jitter {
+            return delay;
+        }
+        let mut buf = [0u8; 8];
+        if getrandom::getrandom(&mut buf).is_err() {
+            return delay;
+        }
+        // Equal jitter: keep half the delay, randomize the other half
+        let half = delay / 2;
+        let spread = half.as_nanos() as u64 + 1;
+        half + Duration::from_nanos(u64::from_ne_bytes(buf) % spread)
+    }
+
+    /// Decide whether to retry after `err` on attempt `attempt` (1-based),
+    /// returning the delay to wait first.
+    fn next_delay(&self, err: &AclError, attempt: u32, start: Instant) -> Option<Duration> {
+        if attempt >= self.max_attempts || !is_retryable(err) {
+            return None;
+        }
+        let delay = self.delay(attempt);
+        match self.deadline {
+            Some(deadline) if start.elapsed() + delay >= deadline => None,
+            _ => Some(delay),
+        }
+    }
+
+    /// Time left before the deadline, if one is set.
+    #[cfg(feature = "async")]
+    fn remaining(&self, start: Instant) -> Option<Duration> {
+        self.deadline
+            .map(|deadline| deadline.saturating_sub(start.elapsed()))
+    }
+}
+
+/// Errors worth retrying: the failure may not happen on the next attempt.
+fn is_retryable(err: &AclError) -> bool {
+    matches!(
+        err,
+        AclError::OutboundError {
+            kind: OutboundErrorKind::ConnectionFailed
+                | OutboundErrorKind::Timeout
+                | OutboundErrorKind::DnsFailed,
+            ..
+        }
+    )
+}
+
+impl Retry<dyn Outbound> {
+    fn run<C>(&self, mut dial: impl FnMut() -> Result<C>) -> Result<C> {
+        let start = Instant::now();
+        let mut attempt = 1;
+        loop {
+            let err = match dial() {
+                Ok(conn) => return Ok(conn),
+                Err(err) => err,
+            };
+            match self.next_delay(&err, attempt, start) {
+                Some(delay) => std::thread::sleep(delay),
+                None => return Err(err),
+            }
+            attempt += 1;
+        }
+    }
+}
+
+impl Outbound for Retry<dyn Outbound> {
+    fn dial_tcp(&self, addr: &mut Addr) -> Result<Box<dyn TcpConn>> {
+        self.run(|| self.inner.dial_tcp(addr))
+    }
+
+    fn dial_udp(&self, addr: &mut Addr) -> Result<Box<dyn UdpConn>> {
+        self.run(|| self.inner.dial_udp(addr))
+    }
+}
+
+#[cfg(feature = "async")]
+impl Retry<dyn AsyncOutbound> {
+    /// Bound one attempt by the time left before the deadline.
+    async fn attempt<C>(
+        &self,
+        start: Instant,
+        dial: impl std::future::Future<Output = Result<C>>,
+    ) -> Result<C> {
+        match self.remaining(start) {
+            Some(remaining) => tokio::time::timeout(remaining, dial).await.map_err(|_| {
+                AclError::OutboundError {
+                    kind: OutboundErrorKind::Timeout,
+                    message: "Retry deadline exceeded".to_string(),
+                }
+            })?,
+            None => dial.await,
+        }
+    }
+}
+
+#[cfg(feature = "async")]
+#[async_trait]
+impl AsyncOutbound for Retry<dyn AsyncOutbound> {
+    async fn dial_tcp(&self, addr: &mut Addr) -> Result<Box<dyn AsyncTcpConn>> {
+        let start = Instant::now();
+        let mut attempt = 1;
+        loop {
+            let err = match self.attempt(start, self.inner.dial_tcp(addr)).await {
+                Ok(conn) => return Ok(conn),
+                Err(err) => err,
+            };
+            match self.next_delay(&err, attempt, start) {
+                Some(delay) => tokio::time::sleep(delay).await,
+                None => return Err(err),
+            }
+            attempt += 1;
+        }
+    }
+
+    async fn dial_udp(&self, addr: &mut Addr) -> Result<Box<dyn AsyncUdpConn>> {
+        let start = Instant::now();
+        let mut attempt = 1;
+        loop {
+            let err = match self.attempt(start, self.inner.dial_udp(addr)).await {
+                Ok(conn) => return Ok(conn),
+                Err(err) => err,
+            };
+            match self.next_delay(&err, attempt, start) {
+                Some(delay) => tokio::time::sleep(delay).await,
+                None => return Err(err),
+            }
+            attempt += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::outbound::{Reject, StdTcpConn};
+    use parking_lot::Mutex;
+    use std::net::{TcpListener, TcpStream};
+
+    /// Member that fails with the queued error kinds, then connects.
+    struct Flaky {
+        listener: TcpListener,
+        failures: Mutex<Vec<OutboundErrorKind>>,
+        dials: Mutex<Vec<Instant>>,
+    }
+
+    impl Flaky {
+        fn new(failures: &[OutboundErrorKind]) -> Arc<Self> {
+            Arc::new(Self {
+                listener: TcpListener::bind("127.0.0.1:0").unwrap(),
+                failures: Mutex::new(failures.iter().rev().copied().collect()),
+                dials: Mutex::new(Vec::new()),
+            })
+        }
+
+        fn dials(&self) -> usize {
+            self.dials.lock().len()
+        }
+    }
+
+    impl Outbound for Flaky {
+        fn dial_tcp(&self, _addr: &mut Addr) -> Result<Box<dyn TcpConn>> {
+            self.dials.lock().push(Instant::now());
+            if let Some(kind) = self.failures.lock().pop() {
+                return Err(AclError::OutboundError {
+                    kind,
+                    message: format!("{:?}", kind),
+                });
+            }
+            let stream = TcpStream::connect(self.listener.local_addr().unwrap())?;
+            Ok(Box::new(StdTcpConn::new(stream)))
+        }
+
+        fn dial_udp(&self, addr: &mut Addr) -> Result<Box<dyn UdpConn>> {
+            Outbound::dial_udp(&Reject::new(), addr)
+        }
+    }
+
+    fn retry(inner: &Arc<Flaky>) -> Retry {
+        Retry::new(inner.clone() as Arc<dyn Outbound>)
+            .with_backoff(Duration::from_millis(10), Duration::from_millis(40))
+    }
+
+    #[test]
+    fn test_retry_backoff_delays() {
+        let flaky = Flaky::new(&[]);
+        let plain = retry(&flaky).with_jitter(false);
+        let ms = |v| Duration::from_millis(v);
+        assert_eq!(plain.delay(1), ms(10));
+        assert_eq!(plain.delay(2), ms(20));
+        assert_eq!(plain.delay(3), ms(40));
+        assert_eq!(plain.delay(4), ms(40));
+        assert_eq!(plain.delay(64), ms(40));
+
+        let jittered = retry(&flaky);
+        for n in 1..6 {
+            let (delay, full) = (jittered.delay(n), plain.delay(n));
+            assert!(delay >= full / 2 && delay <= full, "{:?}", delay);
+        }
+    }
+
+    #[test]
+    fn test_retry_recovers_from_transient_errors() {
+        let flaky = Flaky::new(&[
+            OutboundErrorKind::ConnectionFailed,
+            OutboundErrorKind::DnsFailed,
+        ]);
+        let retry = retry(&flaky).with_jitter(false);
+        assert!(Outbound::dial_tcp(&retry, &mut Addr::new("example.com", 80)).is_ok());
+
+        let dials = flaky.dials.lock();
+        assert_eq!(dials.len(), 3);
+        assert!(dials[1] - dials[0] >= Duration::from_millis(10));
+        assert!(dials[2] - dials[1] >= Duration::from_millis(20));
+    }
+
+    #[test]
+    fn test_retry_gives_up_after_max_attempts() {
+        let flaky = Flaky::new(&[OutboundErrorKind::Timeout; 5]);
+        let retry = retry(&flaky).with_max_attempts(2);
+        let err = Outbound::dial_tcp(&retry, &mut Addr::new("example.com", 80))
+            .err()
+            .unwrap();
+        assert!(matches!(
+            err,
+            AclError::OutboundError {
+                kind: OutboundErrorKind::Timeout,
+                ..
+            }
+        ));
+        assert_eq!(flaky.dials(), 2);
+    }
+
+    #[test]
+    fn test_retry_never_retries_auth_or_input_errors() {
+        for kind in [
+            OutboundErrorKind::AuthFailed,
+            OutboundErrorKind::InvalidInput,
+        ] {
+            let flaky = Flaky::new(&[kind]);
+            let retry = retry(&flaky);
+            assert!(Outbound::dial_tcp(&retry, &mut Addr::new("example.com", 80)).is_err());
+            assert_eq!(flaky.dials(), 1);
+        }
+    }
+
+    #[test]
+    fn test_retry_deadline_stops_retries() {
+        let flaky = Flaky::new(&[OutboundErrorKind::ConnectionFailed; 10]);
+        let retry = retry(&flaky)
+            .with_max_attempts(10)
+            .with_jitter(false)
+            .with_deadline(Duration::from_millis(50));
+        assert!(Outbound::dial_tcp(&retry, &mut Addr::new("example.com", 80)).is_err());
+        // 10ms + 20ms fit in the deadline; the next 40ms delay does not
+        assert_eq!(flaky.dials(), 3);
+    }
+}
+
+#[cfg(all(test, feature = "async"))]
+mod async_tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Member whose dials never complete.
+    struct Hung(AtomicUsize);
+
+    #[async_trait]
+    impl AsyncOutbound for Hung {
+        async fn dial_tcp(&self, _addr: &mut Addr) -> Result<Box<dyn AsyncTcpConn>> {
+            self.0.fetch_add(1, Ordering::Relaxed);
+            std::future::pending().await
+        }
+
+        async fn dial_udp(&self, _addr: &mut Addr) -> Result<Box<dyn AsyncUdpConn>> {
+            self.0.fetch_add(1, Ordering::Relaxed);
+            Err(AclError::OutboundError {
+                kind: OutboundErrorKind::ConnectionFailed,
+                message: "refused".to_string(),
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_async_retry_deadline_cancels_dial() {
+        let hung = Arc::new(Hung(AtomicUsize::new(0)));
+        let retry = AsyncRetry::new(hung.clone() as Arc<dyn AsyncOutbound>)
+            .with_deadline(Duration::from_millis(50));
+        let mut addr = Addr::new("example.com", 443);
+        let err = AsyncOutbound::dial_tcp(&retry, &mut addr)
+            .await
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("Retry deadline exceeded"));
+        assert_eq!(hung.0.load(Ordering::Relaxed), 1);
+    }
+
+    #[tokio::test]
+    async fn test_async_retry_udp_attempts() {
+        let hung = Arc::new(Hung(AtomicUsize::new(0)));
+        let retry = AsyncRetry::new(hung.clone() as Arc<dyn AsyncOutbound>)
+            .with_backoff(Duration::from_millis(1), Duration::from_millis(1))
+            .with_max_attempts(4);
+        let mut addr = Addr::new("example.com", 53);
+        assert!(AsyncOutbound::dial_udp(&retry, &mut addr).await.is_err());
+        assert_eq!(hung.0.load(Ordering::Relaxed), 4);
+    }
+}
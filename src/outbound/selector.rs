@@ -0,0 +1,215 @@
+//! Runtime-switchable selector outbound.
+//!
+//! [`Selector`] forwards dials to one member chosen by name, and the choice
+//! can be changed at runtime (e.g. from an admin UI) through a shared
+//! reference while ACL rules keep referring to the selector's own name.
+
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use crate::error::{AclError, Result};
+use crate::router::OutboundEntry;
+
+use super::group::validate_members;
+use super::{Addr, Outbound, TcpConn, UdpConn};
+
+#[cfg(feature = "async")]
+use super::{AsyncOutbound, AsyncTcpConn, AsyncUdpConn};
+#[cfg(feature = "async")]
+use async_trait::async_trait;
+
+/// Outbound that dials through a member selected by name.
+///
+/// Generic over the outbound trait like [`OutboundEntry`]; use `Selector`
+/// for sync members and `AsyncSelector` for async ones. Switching only
+/// affects new dials; connections already open stay on their member.
+pub struct Selector<T: ?Sized = dyn Outbound> {
+    members: Vec<OutboundEntry<T>>,
+    /// Index of the member new dials go to
+    selected: AtomicUsize,
+}
+
+/// Async selector (type alias for `Selector<dyn AsyncOutbound>`).
+#[cfg(feature = "async")]
+pub type AsyncSelector = Selector<dyn AsyncOutbound>;
+
+impl<T: ?Sized> fmt::Debug for Selector<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Selector")
+            .field(
+                "members",
+                &self.members.iter().map(|m| &m.name).collect::<Vec<_>>(),
+            )
+            .field("selected", &self.selected())
+            .finish()
+    }
+}
+
+impl<T: ?Sized> Selector<T> {
+    /// Create a new selector. The first member is selected initially.
+    ///
+    /// Returns `ConfigError` if `members` is empty or two members share a
+    /// name (compared case-insensitively, as in ACL rules).
+    pub fn new(members: Vec<OutboundEntry<T>>) -> Result<Self> {
+        validate_members(&members, "Selector")?;
+
+        Ok(Self {
+            members,
+            selected: AtomicUsize::new(0),
+        })
+    }
+
+    /// Set the initially selected member.
+    ///
+    /// Returns `UnknownOutbound` if `name` is not a member.
+    pub fn with_selected(self, name: &str) -> Result<Self> {
+        self.select(name)?;
+        Ok(self)
+    }
+
+    /// Send new dials to the member called `name` (case-insensitive).
+    ///
+    /// Returns `UnknownOutbound` and keeps the current member if `name` is
+    /// not a member.
+    pub fn select(&self, name: &str) -> Result<()> {
+        let index = self
+            .members
+            .iter()
+            .position(|m| m.name.eq_ignore_ascii_case(name))
+            .ok_or_else(|| AclError::UnknownOutbound(name.to_string()))?;
+        self.selected.store(index, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Name of the member new dials go to.
+    pub fn selected(&self) -> &str {
+        &self.members[self.selected.load(Ordering::Relaxed)].name
+    }
+
+    /// Get the selector members.
+    pub fn members(&self) -> &[OutboundEntry<T>] {
+        &self.members
+    }
+
+    fn current(&self) -> &Arc<T> {
+        &self.members[self.selected.load(Ordering::Relaxed)].outbound
+    }
+}
+
+impl Outbound for Selector<dyn Outbound> {
+    fn dial_tcp(&self, addr: &mut Addr) -> Result<Box<dyn TcpConn>> {
+        self.current().dial_tcp(addr)
+    }
+
+    fn dial_udp(&self, addr: &mut Addr) -> Result<Box<dyn UdpConn>> {
+        self.current().dial_udp(addr)
+    }
+}
+
+#[cfg(feature = "async")]
+#[async_trait]
+impl AsyncOutbound for Selector<dyn AsyncOutbound> {
+    async fn dial_tcp(&self, addr: &mut Addr) -> Result<Box<dyn AsyncTcpConn>> {
+        self.current().dial_tcp(addr).await
+    }
+
+    async fn dial_udp(&self, addr: &mut Addr) -> Result<Box<dyn AsyncUdpConn>> {
+        self.current().dial_udp(addr).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geo::NilGeoLoader;
+    use crate::outbound::{Direct, Reject};
+    use crate::router::{Router, RouterOptions};
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+
+    fn selector() -> Selector {
+        Selector::new(vec![
+            OutboundEntry::new("direct", Arc::new(Direct::new()) as Arc<dyn Outbound>),
+            OutboundEntry::new("blocked", Arc::new(Reject::new()) as Arc<dyn Outbound>),
+        ])
+        .unwrap()
+    }
+
+    #[test]
+    fn test_selector_select() {
+        let selector = selector();
+        assert_eq!(selector.selected(), "direct");
+        selector.select("BLOCKED").unwrap();
+        assert_eq!(selector.selected(), "blocked");
+
+        let err = selector.select("missing").unwrap_err();
+        assert!(matches!(err, AclError::UnknownOutbound(ref name) if name == "missing"));
+        assert_eq!(selector.selected(), "blocked");
+
+        assert_eq!(
+            selector.with_selected("direct").unwrap().selected(),
+            "direct"
+        );
+        assert!(Selector::new(Vec::<OutboundEntry>::new()).is_err());
+    }
+
+    #[test]
+    fn test_selector_switch_keeps_existing_connections() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut buf = [0u8; 4];
+            stream.read_exact(&mut buf).unwrap();
+            stream.write_all(&buf).unwrap();
+        });
+
+        let selector = Arc::new(selector());
+        let router = Router::new(
+            "pick(all)",
+            vec![OutboundEntry::new(
+                "pick",
+                selector.clone() as Arc<dyn Outbound>,
+            )],
+            &NilGeoLoader,
+            RouterOptions::new(),
+        )
+        .unwrap();
+
+        let mut conn = Outbound::dial_tcp(&router, &mut Addr::new("127.0.0.1", port)).unwrap();
+        // Switch from an admin path while the connection is open
+        selector.select("blocked").unwrap();
+        assert!(Outbound::dial_tcp(&router, &mut Addr::new("127.0.0.1", port)).is_err());
+
+        conn.write_all(b"ping").unwrap();
+        let mut buf = [0u8; 4];
+        conn.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"ping");
+        server.join().unwrap();
+    }
+}
+
+#[cfg(all(test, feature = "async"))]
+mod async_tests {
+    use super::*;
+    use crate::outbound::{Direct, Reject};
+
+    #[tokio::test]
+    async fn test_async_selector_switch() {
+        let selector = AsyncSelector::new(vec![
+            OutboundEntry::new("blocked", Arc::new(Reject::new()) as Arc<dyn AsyncOutbound>),
+            OutboundEntry::new("direct", Arc::new(Direct::new()) as Arc<dyn AsyncOutbound>),
+        ])
+        .unwrap();
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let mut addr = Addr::new("127.0.0.1", port);
+        assert!(AsyncOutbound::dial_tcp(&selector, &mut addr).await.is_err());
+
+        selector.select("direct").unwrap();
+        let mut addr = Addr::new("127.0.0.1", port);
+        assert!(AsyncOutbound::dial_tcp(&selector, &mut addr).await.is_ok());
+    }
+}
@@ -0,0 +1,1412 @@
+//! Shadowsocks AEAD outbound implementation.
+//!
+//! Connects to targets through a Shadowsocks server using the AEAD
+//! construction (`aes-128-gcm`, `aes-256-gcm`, `chacha20-ietf-poly1305`).
+//! TCP is carried as length-prefixed encrypted chunks and UDP as
+//! individually encrypted packets, both keyed per session via HKDF-SHA1.
+
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use md5::{Digest, Md5};
+use ring::aead::{self, Aad, LessSafeKey, Nonce, UnboundKey};
+use ring::hkdf;
+
+use crate::error::{AclError, OutboundErrorKind, Result};
+
+use super::dialer::{udp_not_chainable, ProxyDialer};
+use super::socks5::{addr_to_socks5, parse_bound_addr};
+use super::{Addr, Outbound, TcpConn, UdpConn, DEFAULT_DIALER_TIMEOUT};
+
+#[cfg(feature = "async")]
+use super::{AsyncOutbound, AsyncTcpConn, AsyncUdpConn};
+#[cfg(feature = "async")]
+use async_trait::async_trait;
+#[cfg(feature = "async")]
+use tokio::io::AsyncWriteExt;
+#[cfg(feature = "async")]
+use tokio::net::UdpSocket as TokioUdpSocket;
+
+/// HKDF info string for per-session subkeys.
+const SUBKEY_INFO: &[u8] = b"ss-subkey";
+
+/// Maximum payload carried by one TCP chunk.
+const MAX_PAYLOAD_LEN: usize = 0x3FFF;
+
+const TAG_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+
+/// Maximum UDP datagram size.
+const MAX_PACKET_LEN: usize = 65535;
+
+/// Socket read size for the TCP decoder.
+const READ_BUF_LEN: usize = 16 * 1024;
+
+const SS_REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Shadowsocks AEAD cipher.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShadowsocksCipher {
+    /// `aes-128-gcm`
+    Aes128Gcm,
+    /// `aes-256-gcm`
+    Aes256Gcm,
+    /// `chacha20-ietf-poly1305`
+    Chacha20IetfPoly1305,
+}
+
+impl ShadowsocksCipher {
+    /// Cipher name as used in `ss://` URLs and server configs.
+    pub fn name(self) -> &'static str {
+        match self {
+            ShadowsocksCipher::Aes128Gcm => "aes-128-gcm",
+            ShadowsocksCipher::Aes256Gcm => "aes-256-gcm",
+            ShadowsocksCipher::Chacha20IetfPoly1305 => "chacha20-ietf-poly1305",
+        }
+    }
+
+    /// Key length in bytes. The salt has the same length.
+    pub fn key_len(self) -> usize {
+        match self {
+            ShadowsocksCipher::Aes128Gcm => 16,
+            ShadowsocksCipher::Aes256Gcm | ShadowsocksCipher::Chacha20IetfPoly1305 => 32,
+        }
+    }
+
+    fn algorithm(self) -> &'static aead::Algorithm {
+        match self {
+            ShadowsocksCipher::Aes128Gcm => &aead::AES_128_GCM,
+            ShadowsocksCipher::Aes256Gcm => &aead::AES_256_GCM,
+            ShadowsocksCipher::Chacha20IetfPoly1305 => &aead::CHACHA20_POLY1305,
+        }
+    }
+}
+
+impl fmt::Display for ShadowsocksCipher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for ShadowsocksCipher {
+    type Err = AclError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "aes-128-gcm" => Ok(ShadowsocksCipher::Aes128Gcm),
+            "aes-256-gcm" => Ok(ShadowsocksCipher::Aes256Gcm),
+            "chacha20-ietf-poly1305" | "chacha20-poly1305" => {
+                Ok(ShadowsocksCipher::Chacha20IetfPoly1305)
+            }
+            other => Err(AclError::ConfigError(format!(
+                "Unsupported Shadowsocks cipher: {}",
+                other
+            ))),
+        }
+    }
+}
+
+/// Derive the master key from a password (OpenSSL `EVP_BytesToKey` with MD5).
+fn evp_bytes_to_key(password: &[u8], key_len: usize) -> Vec<u8> {
+    let mut key = Vec::with_capacity(key_len + 16);
+    let mut prev: Vec<u8> = Vec::new();
+    while key.len() < key_len {
+        let mut hasher = Md5::new();
+        hasher.update(&prev);
+        hasher.update(password);
+        prev = hasher.finalize().to_vec();
+        key.extend_from_slice(&prev);
+    }
+    key.truncate(key_len);
+    key
+}
+
+/// Output length marker for ring's HKDF.
+struct OkmLen(usize);
+
+impl hkdf::KeyType for OkmLen {
+    fn len(&self) -> usize {
+        self.0
+    }
+}
+
+/// HKDF-SHA1 extract-and-expand into `out`.
+fn hkdf_sha1(ikm: &[u8], salt: &[u8], info: &[u8], out: &mut [u8]) {
+    let info = [info];
+    hkdf::Salt::new(hkdf::HKDF_SHA1_FOR_LEGACY_USE_ONLY, salt)
+        .extract(ikm)
+        .expand(&info, OkmLen(out.len()))
+        .and_then(|okm| okm.fill(out))
+        .expect("HKDF-SHA1 output length is within limits");
+}
+
+fn random_salt(len: usize) -> io::Result<Vec<u8>> {
+    let mut salt = vec![0u8; len];
+    getrandom::getrandom(&mut salt).map_err(|e| io::Error::other(e.to_string()))?;
+    Ok(salt)
+}
+
+/// AEAD state for one direction of a session: subkey plus nonce counter.
+struct AeadSession {
+    key: LessSafeKey,
+    nonce: [u8; NONCE_LEN],
+}
+
+impl AeadSession {
+    fn new(cipher: ShadowsocksCipher, master_key: &[u8], salt: &[u8]) -> Self {
+        let mut subkey = vec![0u8; cipher.key_len()];
+        hkdf_sha1(master_key, salt, SUBKEY_INFO, &mut subkey);
+        let key =
+            UnboundKey::new(cipher.algorithm(), &subkey).expect("subkey length matches the cipher");
+        Self {
+            key: LessSafeKey::new(key),
+            nonce: [0u8; NONCE_LEN],
+        }
+    }
+
+    /// Take the current nonce and advance the little-endian counter.
+    fn next_nonce(&mut self) -> Nonce {
+        let nonce = Nonce::assume_unique_for_key(self.nonce);
+        for byte in self.nonce.iter_mut() {
+            *byte = byte.wrapping_add(1);
+            if *byte != 0 {
+                break;
+            }
+        }
+        nonce
+    }
+
+    /// Encrypt `data` and append ciphertext plus tag to `out`.
+    fn seal(&mut self, data: &[u8], out: &mut Vec<u8>) {
+        let nonce = self.next_nonce();
+        let start = out.len();
+        out.extend_from_slice(data);
+        let tag = self
+            .key
+            .seal_in_place_separate_tag(nonce, Aad::empty(), &mut out[start..])
+            .expect("payload length is within AEAD limits");
+        out.extend_from_slice(tag.as_ref());
+    }
+
+    /// Decrypt `buf` (ciphertext plus tag) in place, returning the plaintext length.
+    fn open(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let nonce = self.next_nonce();
+        self.key
+            .open_in_place(nonce, Aad::empty(), buf)
+            .map(|plain| plain.len())
+            .map_err(|_| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "Shadowsocks AEAD authentication failed",
+                )
+            })
+    }
+}
+
+/// Encrypts the outgoing TCP stream into length-prefixed chunks.
+struct StreamEncoder {
+    session: AeadSession,
+}
+
+impl StreamEncoder {
+    /// Create an encoder with a fresh salt, which is written to `out`.
+    fn new(cipher: ShadowsocksCipher, master_key: &[u8], out: &mut Vec<u8>) -> io::Result<Self> {
+        let salt = random_salt(cipher.key_len())?;
+        out.extend_from_slice(&salt);
+        Ok(Self {
+            session: AeadSession::new(cipher, master_key, &salt),
+        })
+    }
+
+    /// Append `data` to `out` as one or more encrypted chunks.
+    fn encode(&mut self, data: &[u8], out: &mut Vec<u8>) {
+        for chunk in data.chunks(MAX_PAYLOAD_LEN) {
+            self.session.seal(&(chunk.len() as u16).to_be_bytes(), out);
+            self.session.seal(chunk, out);
+        }
+    }
+}
+
+/// Decrypts the incoming TCP stream: salt, then length and payload chunks.
+struct StreamDecoder {
+    cipher: ShadowsocksCipher,
+    master_key: Arc<[u8]>,
+    session: Option<AeadSession>,
+    /// Undecoded bytes received from the server
+    buf: Vec<u8>,
+    /// Payload length of the chunk being read, once its length block is decoded
+    payload_len: Option<usize>,
+}
+
+impl StreamDecoder {
+    fn new(cipher: ShadowsocksCipher, master_key: Arc<[u8]>) -> Self {
+        Self {
+            cipher,
+            master_key,
+            session: None,
+            buf: Vec::new(),
+            payload_len: None,
+        }
+    }
+
+    fn feed(&mut self, data: &[u8]) {
+        self.buf.extend_from_slice(data);
+    }
+
+    /// Returns true if no partial salt or chunk is buffered.
+    fn at_boundary(&self) -> bool {
+        self.buf.is_empty() && self.payload_len.is_none()
+    }
+
+    /// Decode the next complete payload chunk, if enough bytes are buffered.
+    fn next_chunk(&mut self) -> io::Result<Option<Vec<u8>>> {
+        loop {
+            let Some(session) = self.session.as_mut() else {
+                let salt_len = self.cipher.key_len();
+                if self.buf.len() < salt_len {
+                    return Ok(None);
+                }
+                let salt: Vec<u8> = self.buf.drain(..salt_len).collect();
+                self.session = Some(AeadSession::new(self.cipher, &self.master_key, &salt));
+                continue;
+            };
+
+            match self.payload_len {
+                None => {
+                    if self.buf.len() < 2 + TAG_LEN {
+                        return Ok(None);
+                    }
+                    session.open(&mut self.buf[..2 + TAG_LEN])?;
+                    let len = u16::from_be_bytes([self.buf[0], self.buf[1]]) as usize;
+                    self.buf.drain(..2 + TAG_LEN);
+                    if len == 0 || len > MAX_PAYLOAD_LEN {
+                        return Err(io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            format!("Invalid Shadowsocks chunk length: {}", len),
+                        ));
+                    }
+                    self.payload_len = Some(len);
+                }
+                Some(len) => {
+                    if self.buf.len() < len + TAG_LEN {
+                        return Ok(None);
+                    }
+                    session.open(&mut self.buf[..len + TAG_LEN])?;
+                    let payload = self.buf[..len].to_vec();
+                    self.buf.drain(..len + TAG_LEN);
+                    self.payload_len = None;
+                    return Ok(Some(payload));
+                }
+            }
+        }
+    }
+}
+
+/// Encode a target as a SOCKS address: ATYP + ADDR + PORT.
+fn encode_target(addr: &Addr) -> Result<Vec<u8>> {
+    let (atyp, host) = addr_to_socks5(&addr.host)?;
+    let mut data = vec![atyp];
+    data.extend(host);
+    data.extend(addr.port.to_be_bytes());
+    Ok(data)
+}
+
+/// Decode a SOCKS address, returning it and the number of bytes consumed.
+fn decode_target(data: &[u8]) -> Result<(Addr, usize)> {
+    let Some((&atyp, rest)) = data.split_first() else {
+        return Err(AclError::OutboundError {
+            kind: OutboundErrorKind::Protocol,
+            message: "Empty Shadowsocks address".to_string(),
+        });
+    };
+    let (host, port, consumed) = parse_bound_addr(atyp, rest)?;
+    Ok((Addr::new(host, port), 1 + consumed))
+}
+
+/// Encrypt a UDP packet: salt + AEAD(target address + payload).
+fn encrypt_packet(
+    cipher: ShadowsocksCipher,
+    master_key: &[u8],
+    addr: &Addr,
+    payload: &[u8],
+) -> Result<Vec<u8>> {
+    let mut plain = encode_target(addr)?;
+    plain.extend_from_slice(payload);
+
+    let salt = random_salt(cipher.key_len()).map_err(|e| AclError::OutboundError {
+        kind: OutboundErrorKind::Io,
+        message: format!("Failed to generate salt: {}", e),
+    })?;
+    let mut packet = salt.clone();
+    AeadSession::new(cipher, master_key, &salt).seal(&plain, &mut packet);
+    Ok(packet)
+}
+
+/// Decrypt a UDP packet in place, returning the source address and the
+/// payload range within `packet`.
+fn decrypt_packet(
+    cipher: ShadowsocksCipher,
+    master_key: &[u8],
+    packet: &mut [u8],
+) -> Result<(Addr, std::ops::Range<usize>)> {
+    let salt_len = cipher.key_len();
+    if packet.len() < salt_len + TAG_LEN {
+        return Err(AclError::OutboundError {
+            kind: OutboundErrorKind::Protocol,
+            message: "Shadowsocks packet too short".to_string(),
+        });
+    }
+    let (salt, body) = packet.split_at_mut(salt_len);
+    let plain_len = AeadSession::new(cipher, master_key, salt)
+        .open(body)
+        .map_err(|e| AclError::OutboundError {
+            kind: OutboundErrorKind::Protocol,
+            message: e.to_string(),
+        })?;
+    let (addr, header_len) = decode_target(&body[..plain_len])?;
+    Ok((addr, salt_len + header_len..salt_len + plain_len))
+}
+
+/// Shadowsocks AEAD outbound.
+///
+/// Like [`Socks5`](super::Socks5), the target is sent as Host (domain or IP)
+/// and ResolveInfo in Addr is ignored; the server resolves domains itself.
+pub struct Shadowsocks {
+    /// Server address
+    addr: String,
+    /// AEAD cipher
+    cipher: ShadowsocksCipher,
+    /// Master key derived from the password
+    key: Arc<[u8]>,
+    /// Connection timeout
+    timeout: Duration,
+    /// How the server is reached
+    dialer: ProxyDialer,
+}
+
+impl fmt::Debug for Shadowsocks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Shadowsocks")
+            .field("addr", &self.addr)
+            .field("cipher", &self.cipher)
+            .field("timeout", &self.timeout)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Shadowsocks {
+    /// Create a new Shadowsocks outbound.
+    pub fn new(addr: impl Into<String>, cipher: ShadowsocksCipher, password: &str) -> Self {
+        Self {
+            addr: addr.into(),
+            cipher,
+            key: evp_bytes_to_key(password.as_bytes(), cipher.key_len()).into(),
+            timeout: DEFAULT_DIALER_TIMEOUT,
+            dialer: ProxyDialer::default(),
+        }
+    }
+
+    /// Create a new Shadowsocks outbound from an `ss://` URL.
+    ///
+    /// Accepts SIP002 URLs (`ss://base64url(method:password)@host:port` or
+    /// with percent-encoded plain `method:password` user info) and the legacy
+    /// form `ss://base64(method:password@host:port)`. A `#tag` fragment is
+    /// ignored; plugins are not supported.
+    pub fn from_url(url: &str) -> Result<Self> {
+        let url = url.trim();
+        let rest = url
+            .strip_prefix("ss://")
+            .ok_or_else(|| AclError::ConfigError("Shadowsocks URL must start with ss://".into()))?;
+        let rest = rest.split_once('#').map_or(rest, |(r, _)| r);
+        let (rest, query) = match rest.split_once('?') {
+            Some((r, q)) => (r, Some(q)),
+            None => (rest, None),
+        };
+        if query.is_some_and(|q| q.split('&').any(|p| p.starts_with("plugin="))) {
+            return Err(AclError::ConfigError(
+                "Shadowsocks plugins are not supported".to_string(),
+            ));
+        }
+        let rest = rest.trim_end_matches('/');
+
+        let (user_info, host_port) = match rest.rsplit_once('@') {
+            Some((user_info, host_port)) => {
+                let user_info = decode_base64(user_info)
+                    .filter(|s| s.contains(':'))
+                    .map_or_else(|| percent_decode(user_info), Ok)?;
+                (user_info, host_port.to_string())
+            }
+            None => {
+                let decoded = decode_base64(rest)
+                    .ok_or_else(|| AclError::ConfigError("Invalid Shadowsocks URL".into()))?;
+                let (user_info, host_port) = decoded
+                    .rsplit_once('@')
+                    .ok_or_else(|| AclError::ConfigError("Invalid Shadowsocks URL".into()))?;
+                (user_info.to_string(), host_port.to_string())
+            }
+        };
+
+        let (method, password) = user_info.split_once(':').ok_or_else(|| {
+            AclError::ConfigError("Shadowsocks URL is missing method:password".into())
+        })?;
+        if password.is_empty() {
+            return Err(AclError::ConfigError(
+                "Shadowsocks password must not be empty".to_string(),
+            ));
+        }
+        let has_port = host_port
+            .rsplit_once(':')
+            .is_some_and(|(host, port)| !host.is_empty() && port.parse::<u16>().is_ok());
+        if !has_port {
+            return Err(AclError::ConfigError(format!(
+                "Invalid Shadowsocks server address: {}",
+                host_port
+            )));
+        }
+
+        Ok(Self::new(host_port, method.parse()?, password))
+    }
+
+    /// Set connection timeout.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Mark packets to the proxy server with a DSCP value (0-63) for QoS.
+    ///
+    /// Applies to the server connection and the UDP relay socket. The
+    /// server connection is not marked when reached through a dialer
+    /// outbound; set DSCP on that outbound instead.
+    /// Returns `ConfigError` if the value is above 63.
+    pub fn with_dscp(mut self, dscp: u8) -> Result<Self> {
+        self.dialer.set_dscp(dscp)?;
+        Ok(self)
+    }
+
+    /// Reach the server through another outbound (proxy chaining).
+    ///
+    /// Applies to the sync [`Outbound`] impl. Only TCP is chained; UDP
+    /// dials fail with `Unsupported` while a dialer is set.
+    pub fn with_dialer(mut self, dialer: Arc<dyn Outbound>) -> Self {
+        self.dialer.set(dialer);
+        self
+    }
+
+    /// Reach the server through another async outbound.
+    ///
+    /// Applies to the [`AsyncOutbound`] impl.
+    #[cfg(feature = "async")]
+    pub fn with_async_dialer(mut self, dialer: Arc<dyn AsyncOutbound>) -> Self {
+        self.dialer.set_async(dialer);
+        self
+    }
+
+    /// Resolve the server address.
+    fn server_addr(&self) -> Result<SocketAddr> {
+        self.addr
+            .to_socket_addrs()
+            .map_err(|e| AclError::OutboundError {
+                kind: OutboundErrorKind::DnsFailed,
+                message: format!("Failed to resolve proxy address: {}", e),
+            })?
+            .next()
+            .ok_or_else(|| AclError::OutboundError {
+                kind: OutboundErrorKind::DnsFailed,
+                message: "No address resolved for proxy".to_string(),
+            })
+    }
+
+    /// Async: resolve the server address with tokio DNS.
+    #[cfg(feature = "async")]
+    async fn async_server_addr(&self) -> Result<SocketAddr> {
+        tokio::net::lookup_host(&self.addr)
+            .await
+            .map_err(|e| AclError::OutboundError {
+                kind: OutboundErrorKind::DnsFailed,
+                message: format!("Failed to resolve proxy address: {}", e),
+            })?
+            .next()
+            .ok_or_else(|| AclError::OutboundError {
+                kind: OutboundErrorKind::DnsFailed,
+                message: "No address resolved for proxy".to_string(),
+            })
+    }
+
+    /// Build the salt and first chunk carrying the target address.
+    fn handshake(&self, addr: &Addr) -> Result<(StreamEncoder, Vec<u8>)> {
+        let target = encode_target(addr)?;
+        let mut out = Vec::new();
+        let mut encoder = StreamEncoder::new(self.cipher, &self.key, &mut out).map_err(|e| {
+            AclError::OutboundError {
+                kind: OutboundErrorKind::Io,
+                message: format!("Failed to generate salt: {}", e),
+            }
+        })?;
+        encoder.encode(&target, &mut out);
+        Ok((encoder, out))
+    }
+}
+
+/// Bind address matching the family of `server`.
+fn udp_bind_addr(server: &SocketAddr) -> &'static str {
+    if server.is_ipv6() {
+        "[::]:0"
+    } else {
+        "0.0.0.0:0"
+    }
+}
+
+impl Outbound for Shadowsocks {
+    fn dial_tcp(&self, addr: &mut Addr) -> Result<Box<dyn TcpConn>> {
+        let (encoder, header) = self.handshake(addr)?;
+        let mut stream = self.dialer.dial(&self.addr, self.timeout)?;
+        stream.set_write_timeout(Some(SS_REQUEST_TIMEOUT)).ok();
+        stream
+            .write_all(&header)
+            .map_err(|e| AclError::OutboundError {
+                kind: OutboundErrorKind::Io,
+                message: format!("Failed to send request: {}", e),
+            })?;
+        stream.set_write_timeout(None).ok();
+
+        Ok(Box::new(ShadowsocksTcpConn {
+            stream,
+            encoder,
+            decoder: StreamDecoder::new(self.cipher, self.key.clone()),
+            plain: Vec::new(),
+            plain_pos: 0,
+            write_failed: false,
+        }))
+    }
+
+    fn dial_udp(&self, _addr: &mut Addr) -> Result<Box<dyn UdpConn>> {
+        if self.dialer.is_chained() {
+            return Err(udp_not_chainable("Shadowsocks"));
+        }
+        let server = self.server_addr()?;
+        let socket =
+            UdpSocket::bind(udp_bind_addr(&server)).map_err(|e| AclError::OutboundError {
+                kind: OutboundErrorKind::ConnectionFailed,
+                message: format!("Failed to bind UDP: {}", e),
+            })?;
+        self.dialer.mark_udp(&socket, server.is_ipv6())?;
+        socket
+            .connect(server)
+            .map_err(|e| AclError::OutboundError {
+                kind: OutboundErrorKind::ConnectionFailed,
+                message: format!("Failed to connect UDP: {}", e),
+            })?;
+
+        Ok(Box::new(ShadowsocksUdpConn {
+            socket,
+            cipher: self.cipher,
+            key: self.key.clone(),
+        }))
+    }
+}
+
+#[cfg(feature = "async")]
+#[async_trait]
+impl AsyncOutbound for Shadowsocks {
+    async fn dial_tcp(&self, addr: &mut Addr) -> Result<Box<dyn AsyncTcpConn>> {
+        let (encoder, header) = self.handshake(addr)?;
+        let mut stream = self.dialer.async_dial(&self.addr, self.timeout).await?;
+        tokio::time::timeout(SS_REQUEST_TIMEOUT, stream.write_all(&header))
+            .await
+            .map_err(|_| AclError::OutboundError {
+                kind: OutboundErrorKind::Timeout,
+                message: "Request timeout".to_string(),
+            })?
+            .map_err(|e| AclError::OutboundError {
+                kind: OutboundErrorKind::Io,
+                message: format!("Failed to send request: {}", e),
+            })?;
+
+        Ok(Box::new(AsyncShadowsocksTcpConn {
+            stream,
+            encoder,
+            decoder: StreamDecoder::new(self.cipher, self.key.clone()),
+            plain: Vec::new(),
+            plain_pos: 0,
+            pending: Vec::new(),
+            pending_pos: 0,
+        }))
+    }
+
+    async fn dial_udp(&self, _addr: &mut Addr) -> Result<Box<dyn AsyncUdpConn>> {
+        if self.dialer.is_async_chained() {
+            return Err(udp_not_chainable("Shadowsocks"));
+        }
+        let server = self.async_server_addr().await?;
+        let socket = TokioUdpSocket::bind(udp_bind_addr(&server))
+            .await
+            .map_err(|e| AclError::OutboundError {
+                kind: OutboundErrorKind::ConnectionFailed,
+                message: format!("Failed to bind UDP: {}", e),
+            })?;
+        self.dialer.mark_udp(&socket, server.is_ipv6())?;
+        socket
+            .connect(server)
+            .await
+            .map_err(|e| AclError::OutboundError {
+                kind: OutboundErrorKind::ConnectionFailed,
+                message: format!("Failed to connect UDP: {}", e),
+            })?;
+
+        Ok(Box::new(AsyncShadowsocksUdpConn {
+            socket,
+            cipher: self.cipher,
+            key: self.key.clone(),
+        }))
+    }
+}
+
+/// Shadowsocks TCP stream.
+struct ShadowsocksTcpConn {
+    stream: Box<dyn TcpConn>,
+    encoder: StreamEncoder,
+    decoder: StreamDecoder,
+    /// Decrypted payload not yet returned to the reader
+    plain: Vec<u8>,
+    plain_pos: usize,
+    /// Set once a write fails. The encoder nonce has already advanced for
+    /// a chunk the server may not have received, so later chunks would not
+    /// decrypt.
+    write_failed: bool,
+}
+
+impl Read for ShadowsocksTcpConn {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        loop {
+            if self.plain_pos < self.plain.len() {
+                let n = (self.plain.len() - self.plain_pos).min(buf.len());
+                buf[..n].copy_from_slice(&self.plain[self.plain_pos..self.plain_pos + n]);
+                self.plain_pos += n;
+                return Ok(n);
+            }
+            if let Some(chunk) = self.decoder.next_chunk()? {
+                self.plain = chunk;
+                self.plain_pos = 0;
+                continue;
+            }
+
+            let mut tmp = [0u8; READ_BUF_LEN];
+            let n = self.stream.read(&mut tmp)?;
+            if n == 0 {
+                if self.decoder.at_boundary() {
+                    return Ok(0);
+                }
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
+            self.decoder.feed(&tmp[..n]);
+        }
+    }
+}
+
+impl Write for ShadowsocksTcpConn {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        if self.write_failed {
+            return Err(io::Error::new(
+                io::ErrorKind::BrokenPipe,
+                "Shadowsocks stream is out of sync after a failed write",
+            ));
+        }
+        let n = buf.len().min(MAX_PAYLOAD_LEN);
+        let mut out = Vec::with_capacity(n + 2 + 2 * TAG_LEN);
+        self.encoder.encode(&buf[..n], &mut out);
+        if let Err(e) = self.stream.write_all(&out) {
+            self.write_failed = true;
+            return Err(e);
+        }
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stream.flush()
+    }
+}
+
+impl TcpConn for ShadowsocksTcpConn {
+    fn local_addr(&self) -> Result<SocketAddr> {
+        self.stream.local_addr()
+    }
+
+    fn peer_addr(&self) -> Result<SocketAddr> {
+        self.stream.peer_addr()
+    }
+
+    fn set_read_timeout(&self, dur: Option<Duration>) -> Result<()> {
+        self.stream.set_read_timeout(dur)
+    }
+
+    fn set_write_timeout(&self, dur: Option<Duration>) -> Result<()> {
+        self.stream.set_write_timeout(dur)
+    }
+
+    fn shutdown(&self, how: std::net::Shutdown) -> Result<()> {
+        self.stream.shutdown(how)
+    }
+}
+
+/// Async Shadowsocks TCP stream.
+#[cfg(feature = "async")]
+struct AsyncShadowsocksTcpConn {
+    stream: Box<dyn AsyncTcpConn>,
+    encoder: StreamEncoder,
+    decoder: StreamDecoder,
+    /// Decrypted payload not yet returned to the reader
+    plain: Vec<u8>,
+    plain_pos: usize,
+    /// Encrypted bytes not yet written to `stream`
+    pending: Vec<u8>,
+    pending_pos: usize,
+}
+
+#[cfg(feature = "async")]
+impl AsyncShadowsocksTcpConn {
+    /// Write out any pending encrypted bytes.
+    fn poll_pending(&mut self, cx: &mut std::task::Context<'_>) -> std::task::Poll<io::Result<()>> {
+        while self.pending_pos < self.pending.len() {
+            let n = std::task::ready!(tokio::io::AsyncWrite::poll_write(
+                std::pin::Pin::new(&mut self.stream),
+                cx,
+                &self.pending[self.pending_pos..]
+            ))?;
+            if n == 0 {
+                return std::task::Poll::Ready(Err(io::ErrorKind::WriteZero.into()));
+            }
+            self.pending_pos += n;
+        }
+        self.pending.clear();
+        self.pending_pos = 0;
+        std::task::Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(feature = "async")]
+impl tokio::io::AsyncRead for AsyncShadowsocksTcpConn {
+    fn poll_read(
+        self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+        buf: &mut tokio::io::ReadBuf<'_>,
+    ) -> std::task::Poll<io::Result<()>> {
+        let this = self.get_mut();
+        loop {
+            if this.plain_pos < this.plain.len() {
+                let n = (this.plain.len() - this.plain_pos).min(buf.remaining());
+                buf.put_slice(&this.plain[this.plain_pos..this.plain_pos + n]);
+                this.plain_pos += n;
+                return std::task::Poll::Ready(Ok(()));
+            }
+            if let Some(chunk) = this.decoder.next_chunk()? {
+                this.plain = chunk;
+                this.plain_pos = 0;
+                continue;
+            }
+
+            let mut tmp = [0u8; READ_BUF_LEN];
+            let mut read_buf = tokio::io::ReadBuf::new(&mut tmp);
+            std::task::ready!(std::pin::Pin::new(&mut this.stream).poll_read(cx, &mut read_buf))?;
+            let filled = read_buf.filled();
+            if filled.is_empty() {
+                if this.decoder.at_boundary() {
+                    return std::task::Poll::Ready(Ok(()));
+                }
+                return std::task::Poll::Ready(Err(io::ErrorKind::UnexpectedEof.into()));
+            }
+            this.decoder.feed(filled);
+        }
+    }
+}
+
+#[cfg(feature = "async")]
+impl tokio::io::AsyncWrite for AsyncShadowsocksTcpConn {
+    fn poll_write(
+        self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+        buf: &[u8],
+    ) -> std::task::Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        std::task::ready!(this.poll_pending(cx))?;
+        if buf.is_empty() {
+            return std::task::Poll::Ready(Ok(0));
+        }
+        let n = buf.len().min(MAX_PAYLOAD_LEN);
+        this.encoder.encode(&buf[..n], &mut this.pending);
+        std::task::Poll::Ready(Ok(n))
+    }
+
+    fn poll_flush(
+        self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<io::Result<()>> {
+        let this = self.get_mut();
+        std::task::ready!(this.poll_pending(cx))?;
+        std::pin::Pin::new(&mut this.stream).poll_flush(cx)
+    }
+
+    fn poll_shutdown(
+        self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<io::Result<()>> {
+        let this = self.get_mut();
+        std::task::ready!(this.poll_pending(cx))?;
+        std::pin::Pin::new(&mut this.stream).poll_shutdown(cx)
+    }
+}
+
+#[cfg(feature = "async")]
+impl AsyncTcpConn for AsyncShadowsocksTcpConn {
+    fn local_addr(&self) -> Result<SocketAddr> {
+        self.stream.local_addr()
+    }
+
+    fn peer_addr(&self) -> Result<SocketAddr> {
+        self.stream.peer_addr()
+    }
+}
+
+/// Shadowsocks UDP relay connection.
+struct ShadowsocksUdpConn {
+    socket: UdpSocket,
+    cipher: ShadowsocksCipher,
+    key: Arc<[u8]>,
+}
+
+impl UdpConn for ShadowsocksUdpConn {
+    fn read_from(&self, buf: &mut [u8]) -> Result<(usize, Addr)> {
+        let mut packet = vec![0u8; MAX_PACKET_LEN];
+        let n = self
+            .socket
+            .recv(&mut packet)
+            .map_err(|e| AclError::OutboundError {
+                kind: OutboundErrorKind::Io,
+                message: format!("UDP recv error: {}", e),
+            })?;
+        let (addr, payload) = decrypt_packet(self.cipher, &self.key, &mut packet[..n])?;
+        let copy_len = payload.len().min(buf.len());
+        buf[..copy_len].copy_from_slice(&packet[payload.start..payload.start + copy_len]);
+        Ok((copy_len, addr))
+    }
+
+    fn write_to(&self, buf: &[u8], addr: &Addr) -> Result<usize> {
+        let packet = encrypt_packet(self.cipher, &self.key, addr, buf)?;
+        self.socket
+            .send(&packet)
+            .map_err(|e| AclError::OutboundError {
+                kind: OutboundErrorKind::Io,
+                message: format!("UDP send error: {}", e),
+            })?;
+        Ok(buf.len())
+    }
+}
+
+/// Async Shadowsocks UDP relay connection.
+#[cfg(feature = "async")]
+struct AsyncShadowsocksUdpConn {
+    socket: TokioUdpSocket,
+    cipher: ShadowsocksCipher,
+    key: Arc<[u8]>,
+}
+
+#[cfg(feature = "async")]
+#[async_trait]
+impl AsyncUdpConn for AsyncShadowsocksUdpConn {
+    async fn read_from(&self, buf: &mut [u8]) -> Result<(usize, Addr)> {
+        let mut packet = vec![0u8; MAX_PACKET_LEN];
+        let n = self
+            .socket
+            .recv(&mut packet)
+            .await
+            .map_err(|e| AclError::OutboundError {
+                kind: OutboundErrorKind::Io,
+                message: format!("UDP recv error: {}", e),
+            })?;
+        let (addr, payload) = decrypt_packet(self.cipher, &self.key, &mut packet[..n])?;
+        let copy_len = payload.len().min(buf.len());
+        buf[..copy_len].copy_from_slice(&packet[payload.start..payload.start + copy_len]);
+        Ok((copy_len, addr))
+    }
+
+    async fn write_to(&self, buf: &[u8], addr: &Addr) -> Result<usize> {
+        let packet = encrypt_packet(self.cipher, &self.key, addr, buf)?;
+        self.socket
+            .send(&packet)
+            .await
+            .map_err(|e| AclError::OutboundError {
+                kind: OutboundErrorKind::Io,
+                message: format!("UDP send error: {}", e),
+            })?;
+        Ok(buf.len())
+    }
+}
+
+/// Decode standard or URL-safe base64, with or without padding, as UTF-8.
+fn decode_base64(s: &str) -> Option<String> {
+    use base64::Engine;
+    let trimmed = s.trim_end_matches('=');
+    base64::engine::general_purpose::URL_SAFE_NO_PAD
+        .decode(trimmed)
+        .or_else(|_| base64::engine::general_purpose::STANDARD_NO_PAD.decode(trimmed))
+        .ok()
+        .and_then(|bytes| String::from_utf8(bytes).ok())
+}
+
+/// Decode `%XX` escapes.
+fn percent_decode(s: &str) -> Result<String> {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = s
+                .get(i + 1..i + 3)
+                .and_then(|h| u8::from_str_radix(h, 16).ok())
+                .ok_or_else(|| {
+                    AclError::ConfigError(format!("Invalid percent-encoding in: {}", s))
+                })?;
+            out.push(hex);
+            i += 3;
+        } else {
+            out.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(out).map_err(|_| AclError::ConfigError(format!("Invalid UTF-8 in: {}", s)))
+}
+
+/// In-process Shadowsocks server used by the tests.
+#[cfg(test)]
+mod test_server {
+    use super::*;
+    use std::net::TcpListener;
+    use std::thread::JoinHandle;
+
+    /// Accept one TCP connection, read the target address, then echo every
+    /// payload back encrypted. Returns the target the client asked for, or
+    /// `None` if the stream failed to authenticate.
+    pub(super) fn spawn_tcp(
+        cipher: ShadowsocksCipher,
+        password: &str,
+    ) -> (u16, JoinHandle<Option<String>>) {
+        let key: Arc<[u8]> = evp_bytes_to_key(password.as_bytes(), cipher.key_len()).into();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let handle = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut decoder = StreamDecoder::new(cipher, key.clone());
+            let mut out = Vec::new();
+            let mut encoder = StreamEncoder::new(cipher, &key, &mut out).unwrap();
+            let mut target = None;
+            let mut buf = [0u8; 4096];
+            loop {
+                loop {
+                    let chunk = match decoder.next_chunk() {
+                        Ok(Some(chunk)) => chunk,
+                        Ok(None) => break,
+                        // Wrong key or tampered stream: drop the connection
+                        Err(_) => return None,
+                    };
+                    let data = match target {
+                        None => {
+                            let (addr, used) = decode_target(&chunk).unwrap();
+                            target = Some(format!("{}:{}", addr.host, addr.port));
+                            chunk[used..].to_vec()
+                        }
+                        Some(_) => chunk,
+                    };
+                    if !data.is_empty() {
+                        encoder.encode(&data, &mut out);
+                    }
+                }
+                if !out.is_empty() {
+                    stream.write_all(&out).unwrap();
+                    out.clear();
+                }
+                let n = stream.read(&mut buf).unwrap();
+                if n == 0 {
+                    break;
+                }
+                decoder.feed(&buf[..n]);
+            }
+            target
+        });
+        (port, handle)
+    }
+
+    /// Receive one UDP packet and answer with the payload reversed, from a
+    /// rewritten source address. Returns the target the client asked for.
+    pub(super) fn spawn_udp(
+        cipher: ShadowsocksCipher,
+        password: &str,
+    ) -> (u16, JoinHandle<String>) {
+        let key = evp_bytes_to_key(password.as_bytes(), cipher.key_len());
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let port = socket.local_addr().unwrap().port();
+        let handle = std::thread::spawn(move || {
+            let mut packet = [0u8; MAX_PACKET_LEN];
+            let (n, peer) = socket.recv_from(&mut packet).unwrap();
+            let (addr, payload) = decrypt_packet(cipher, &key, &mut packet[..n]).unwrap();
+            let mut reply = packet[payload].to_vec();
+            reply.reverse();
+            let from = Addr::new("198.51.100.7", 5353);
+            let out = encrypt_packet(cipher, &key, &from, &reply).unwrap();
+            socket.send_to(&out, peer).unwrap();
+            format!("{}:{}", addr.host, addr.port)
+        });
+        (port, handle)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    #[test]
+    fn test_evp_bytes_to_key() {
+        // openssl enc -aes-256-cbc -k foobar -nosalt -P -md md5
+        assert_eq!(
+            hex(&evp_bytes_to_key(b"foobar", 32)),
+            "3858f62230ac3c915f300c664312c63f568378529614d22ddb49237d2f60bfdf"
+        );
+        assert_eq!(
+            hex(&evp_bytes_to_key(b"foobar", 16)),
+            "3858f62230ac3c915f300c664312c63f"
+        );
+    }
+
+    #[test]
+    fn test_hkdf_sha1_rfc5869_case4() {
+        let ikm = [0x0b; 11];
+        let salt: Vec<u8> = (0x00..=0x0c).collect();
+        let info: Vec<u8> = (0xf0..=0xf9).collect();
+        let mut okm = [0u8; 42];
+        hkdf_sha1(&ikm, &salt, &info, &mut okm);
+        assert_eq!(
+            hex(&okm),
+            "085a01ea1b10f36933068b56efa5ad81a4f14b822f5b091568a9\
+             cdd4f155fda2c22e422478d305f3f896"
+        );
+    }
+
+    #[test]
+    fn test_cipher_from_str() {
+        assert_eq!(
+            "AES-128-GCM".parse::<ShadowsocksCipher>().unwrap(),
+            ShadowsocksCipher::Aes128Gcm
+        );
+        assert_eq!(
+            "chacha20-ietf-poly1305"
+                .parse::<ShadowsocksCipher>()
+                .unwrap(),
+            ShadowsocksCipher::Chacha20IetfPoly1305
+        );
+        assert!(matches!(
+            "rc4-md5".parse::<ShadowsocksCipher>(),
+            Err(AclError::ConfigError(_))
+        ));
+        assert_eq!(ShadowsocksCipher::Aes256Gcm.to_string(), "aes-256-gcm");
+    }
+
+    #[test]
+    fn test_nonce_increments_little_endian() {
+        let mut session = AeadSession::new(ShadowsocksCipher::Aes128Gcm, &[0u8; 16], &[0u8; 16]);
+        session.nonce[0] = 0xff;
+        session.next_nonce();
+        assert_eq!(&session.nonce[..2], &[0x00, 0x01]);
+    }
+
+    #[test]
+    fn test_stream_round_trip_and_tamper() {
+        for cipher in [
+            ShadowsocksCipher::Aes128Gcm,
+            ShadowsocksCipher::Aes256Gcm,
+            ShadowsocksCipher::Chacha20IetfPoly1305,
+        ] {
+            let key: Arc<[u8]> = evp_bytes_to_key(b"secret", cipher.key_len()).into();
+            let mut wire = Vec::new();
+            let mut encoder = StreamEncoder::new(cipher, &key, &mut wire).unwrap();
+            let big = vec![7u8; MAX_PAYLOAD_LEN + 10];
+            encoder.encode(b"hello", &mut wire);
+            encoder.encode(&big, &mut wire);
+
+            let mut decoder = StreamDecoder::new(cipher, key.clone());
+            // Feed byte by byte to exercise partial buffering
+            let mut chunks = Vec::new();
+            for b in &wire {
+                decoder.feed(std::slice::from_ref(b));
+                while let Some(chunk) = decoder.next_chunk().unwrap() {
+                    chunks.push(chunk);
+                }
+            }
+            assert!(decoder.at_boundary());
+            assert_eq!(chunks.len(), 3);
+            assert_eq!(chunks[0], b"hello");
+            assert_eq!(chunks[1].len() + chunks[2].len(), big.len());
+
+            let last = wire.len() - 1;
+            wire[last] ^= 1;
+            let mut decoder = StreamDecoder::new(cipher, key);
+            decoder.feed(&wire);
+            let mut result = Ok(None);
+            for _ in 0..3 {
+                result = decoder.next_chunk();
+                if result.is_err() {
+                    break;
+                }
+            }
+            assert_eq!(result.unwrap_err().kind(), io::ErrorKind::InvalidData);
+        }
+    }
+
+    #[test]
+    fn test_tcp_conn_fails_writes_after_write_error() {
+        use crate::outbound::StdTcpConn;
+
+        // The server never reads, so writes eventually time out
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let stream = std::net::TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let _server = listener.accept().unwrap();
+        stream
+            .set_write_timeout(Some(Duration::from_millis(50)))
+            .unwrap();
+
+        let cipher = ShadowsocksCipher::Aes128Gcm;
+        let key: Arc<[u8]> = evp_bytes_to_key(b"secret", cipher.key_len()).into();
+        let mut salt = Vec::new();
+        let mut conn = ShadowsocksTcpConn {
+            stream: Box::new(StdTcpConn::new(stream)),
+            encoder: StreamEncoder::new(cipher, &key, &mut salt).unwrap(),
+            decoder: StreamDecoder::new(cipher, key),
+            plain: Vec::new(),
+            plain_pos: 0,
+            write_failed: false,
+        };
+        let chunk = vec![0u8; MAX_PAYLOAD_LEN];
+        let err = loop {
+            if let Err(e) = conn.write(&chunk) {
+                break e;
+            }
+        };
+        assert_ne!(err.kind(), io::ErrorKind::BrokenPipe);
+
+        // Even a write that would fit must not go out with a skipped nonce
+        let err = conn.write(b"x").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::BrokenPipe);
+    }
+
+    #[test]
+    fn test_packet_round_trip() {
+        let cipher = ShadowsocksCipher::Aes256Gcm;
+        let key = evp_bytes_to_key(b"secret", cipher.key_len());
+        let mut packet =
+            encrypt_packet(cipher, &key, &Addr::new("example.com", 53), b"query").unwrap();
+        let (addr, payload) = decrypt_packet(cipher, &key, &mut packet).unwrap();
+        assert_eq!(addr.host, "example.com");
+        assert_eq!(addr.port, 53);
+        assert_eq!(&packet[payload], b"query");
+
+        let wrong = evp_bytes_to_key(b"other", cipher.key_len());
+        let mut packet = encrypt_packet(cipher, &key, &Addr::new("1.2.3.4", 53), b"query").unwrap();
+        assert!(decrypt_packet(cipher, &wrong, &mut packet).is_err());
+    }
+
+    #[test]
+    fn test_from_url_sip002() {
+        use base64::Engine;
+        let user_info =
+            base64::engine::general_purpose::URL_SAFE_NO_PAD.encode("aes-256-gcm:p@ss:word");
+        let ss = Shadowsocks::from_url(&format!(
+            "ss://{}@ss.example.com:8388/?foo=bar#My%20Server",
+            user_info
+        ))
+        .unwrap();
+        assert_eq!(ss.addr, "ss.example.com:8388");
+        assert_eq!(ss.cipher, ShadowsocksCipher::Aes256Gcm);
+        assert_eq!(&*ss.key, &evp_bytes_to_key(b"p@ss:word", 32)[..]);
+    }
+
+    #[test]
+    fn test_from_url_plain_user_info_and_legacy() {
+        let ss = Shadowsocks::from_url("ss://chacha20-ietf-poly1305:pa%20ss@[::1]:8388").unwrap();
+        assert_eq!(ss.addr, "[::1]:8388");
+        assert_eq!(ss.cipher, ShadowsocksCipher::Chacha20IetfPoly1305);
+        assert_eq!(&*ss.key, &evp_bytes_to_key(b"pa ss", 32)[..]);
+
+        use base64::Engine;
+        let legacy =
+            base64::engine::general_purpose::STANDARD.encode("aes-128-gcm:test@192.0.2.1:443");
+        let ss = Shadowsocks::from_url(&format!("ss://{}#tag", legacy)).unwrap();
+        assert_eq!(ss.addr, "192.0.2.1:443");
+        assert_eq!(ss.cipher, ShadowsocksCipher::Aes128Gcm);
+    }
+
+    #[test]
+    fn test_from_url_errors() {
+        for url in [
+            "http://aes-128-gcm:pw@host:1",
+            "ss://aes-128-gcm:pw@host",
+            "ss://aes-128-gcm:@host:8388",
+            "ss://rc4-md5:pw@host:8388",
+            "ss://aes-128-gcm:pw@host:8388/?plugin=obfs-local",
+            "ss://!!!",
+        ] {
+            assert!(
+                matches!(Shadowsocks::from_url(url), Err(AclError::ConfigError(_))),
+                "{}",
+                url
+            );
+        }
+    }
+
+    #[test]
+    fn test_shadowsocks_tcp_echo() {
+        let (port, server) = test_server::spawn_tcp(ShadowsocksCipher::Aes128Gcm, "pw");
+        let ss = Shadowsocks::new(
+            format!("127.0.0.1:{}", port),
+            ShadowsocksCipher::Aes128Gcm,
+            "pw",
+        );
+
+        let mut addr = Addr::new("example.com", 443);
+        let mut conn = Outbound::dial_tcp(&ss, &mut addr).unwrap();
+        let data = vec![0x5a; 40_000];
+        conn.write_all(&data).unwrap();
+        let mut echoed = vec![0u8; data.len()];
+        conn.read_exact(&mut echoed).unwrap();
+        assert_eq!(echoed, data);
+
+        conn.shutdown(std::net::Shutdown::Write).unwrap();
+        let mut rest = Vec::new();
+        conn.read_to_end(&mut rest).unwrap();
+        assert!(rest.is_empty());
+        assert_eq!(server.join().unwrap().as_deref(), Some("example.com:443"));
+    }
+
+    #[test]
+    fn test_shadowsocks_chained_through_dialer() {
+        use crate::outbound::dialer::test_relay::spawn_socks4_relay;
+        use crate::outbound::Socks4;
+
+        let (port, server) = test_server::spawn_tcp(ShadowsocksCipher::Aes128Gcm, "pw");
+        let (relay_port, relay) = spawn_socks4_relay();
+        let ss = Shadowsocks::new(
+            format!("127.0.0.1:{}", port),
+            ShadowsocksCipher::Aes128Gcm,
+            "pw",
+        )
+        .with_dialer(Arc::new(Socks4::new(format!("127.0.0.1:{}", relay_port))));
+
+        let mut addr = Addr::new("example.com", 443);
+        let mut conn = Outbound::dial_tcp(&ss, &mut addr).unwrap();
+        conn.write_all(b"hello").unwrap();
+        let mut echoed = [0u8; 5];
+        conn.read_exact(&mut echoed).unwrap();
+        assert_eq!(&echoed, b"hello");
+        drop(conn);
+
+        assert_eq!(server.join().unwrap().as_deref(), Some("example.com:443"));
+        assert_eq!(relay.join().unwrap(), format!("127.0.0.1:{}", port));
+
+        let err = Outbound::dial_udp(&ss, &mut addr).err().unwrap();
+        assert!(err.to_string().contains("cannot be chained"));
+    }
+
+    #[test]
+    fn test_shadowsocks_tcp_wrong_password() {
+        let (port, server) = test_server::spawn_tcp(ShadowsocksCipher::Aes256Gcm, "right");
+        let ss = Shadowsocks::new(
+            format!("127.0.0.1:{}", port),
+            ShadowsocksCipher::Aes256Gcm,
+            "wrong",
+        );
+        let mut addr = Addr::new("example.com", 80);
+        // The server cannot authenticate the first chunk and drops the
+        // connection; the client sees an error or EOF, never data.
+        let mut conn = Outbound::dial_tcp(&ss, &mut addr).unwrap();
+        let _ = conn.write_all(b"ping");
+        let mut buf = [0u8; 4];
+        assert!(conn.read_exact(&mut buf).is_err());
+        assert!(server.join().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_shadowsocks_udp_relay() {
+        let cipher = ShadowsocksCipher::Chacha20IetfPoly1305;
+        let (port, server) = test_server::spawn_udp(cipher, "pw");
+        let ss = Shadowsocks::new(format!("127.0.0.1:{}", port), cipher, "pw");
+
+        let mut addr = Addr::new("8.8.8.8", 53);
+        let conn = Outbound::dial_udp(&ss, &mut addr).unwrap();
+        conn.write_to(b"abc", &addr).unwrap();
+        let mut buf = [0u8; 64];
+        let (n, from) = conn.read_from(&mut buf).unwrap();
+        assert_eq!(&buf[..n], b"cba");
+        assert_eq!(from.host, "198.51.100.7");
+        assert_eq!(from.port, 5353);
+        assert_eq!(server.join().unwrap(), "8.8.8.8:53");
+    }
+}
+
+#[cfg(all(test, feature = "async"))]
+mod async_tests {
+    use super::*;
+    use tokio::io::AsyncReadExt;
+
+    #[tokio::test]
+    async fn test_async_shadowsocks_tcp_echo() {
+        let cipher = ShadowsocksCipher::Chacha20IetfPoly1305;
+        let (port, server) = test_server::spawn_tcp(cipher, "pw");
+        let ss = Shadowsocks::new(format!("127.0.0.1:{}", port), cipher, "pw");
+
+        let mut addr = Addr::new("::1", 8080);
+        let mut conn = AsyncOutbound::dial_tcp(&ss, &mut addr).await.unwrap();
+        let data = vec![0xa5; 20_000];
+        conn.write_all(&data).await.unwrap();
+        conn.flush().await.unwrap();
+        let mut echoed = vec![0u8; data.len()];
+        conn.read_exact(&mut echoed).await.unwrap();
+        assert_eq!(echoed, data);
+        conn.shutdown().await.unwrap();
+        drop(conn);
+
+        let target = tokio::task::spawn_blocking(move || server.join().unwrap())
+            .await
+            .unwrap();
+        assert_eq!(target.as_deref(), Some("::1:8080"));
+    }
+
+    #[tokio::test]
+    async fn test_async_shadowsocks_udp_relay() {
+        let cipher = ShadowsocksCipher::Aes128Gcm;
+        let (port, server) = test_server::spawn_udp(cipher, "pw");
+        let ss = Shadowsocks::new(format!("127.0.0.1:{}", port), cipher, "pw");
+
+        let mut addr = Addr::new("dns.example", 53);
+        let conn = AsyncOutbound::dial_udp(&ss, &mut addr).await.unwrap();
+        conn.write_to(b"xyz", &addr).await.unwrap();
+        let mut buf = [0u8; 64];
+        let (n, _) = conn.read_from(&mut buf).await.unwrap();
+        assert_eq!(&buf[..n], b"zyx");
+
+        let target = tokio::task::spawn_blocking(move || server.join().unwrap())
+            .await
+            .unwrap();
+        assert_eq!(target, "dns.example:53");
+    }
+}
@@ -0,0 +1,481 @@
+//! SOCKS4/SOCKS4a proxy outbound implementation.
+//!
+//! Connects to targets through a SOCKS4 proxy server. IPv4 targets use plain
+//! SOCKS4; domain targets use the SOCKS4a extension so the proxy resolves them.
+
+use std::io::{Read, Write};
+use std::net::{IpAddr, Ipv4Addr};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::error::{AclError, OutboundErrorKind, Result};
+
+use super::dialer::ProxyDialer;
+use super::{Addr, Outbound, TcpConn, UdpConn, DEFAULT_DIALER_TIMEOUT};
+
+#[cfg(feature = "async")]
+use super::{AsyncOutbound, AsyncTcpConn, AsyncUdpConn};
+#[cfg(feature = "async")]
+use async_trait::async_trait;
+#[cfg(feature = "async")]
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+const SOCKS4_VERSION: u8 = 0x04;
+const SOCKS4_CMD_CONNECT: u8 = 0x01;
+
+/// Reply version byte; servers answer with 0, not 4.
+const SOCKS4_REPLY_VERSION: u8 = 0x00;
+const SOCKS4_REP_GRANTED: u8 = 0x5A;
+
+const SOCKS4_REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Maximum domain length accepted for SOCKS4a targets.
+const SOCKS4A_MAX_DOMAIN_LEN: usize = 255;
+
+/// Convert SOCKS4 reply code to human-readable string.
+fn socks4_rep_to_string(rep: u8) -> &'static str {
+    match rep {
+        0x5A => "request granted",
+        0x5B => "request rejected or failed",
+        0x5C => "client identd unreachable",
+        0x5D => "client identd user-id mismatch",
+        _ => "undefined",
+    }
+}
+
+/// Validate the 8-byte SOCKS4 reply: VN + CD + DSTPORT + DSTIP.
+fn validate_socks4_response(resp: &[u8; 8]) -> Result<()> {
+    if resp[0] != SOCKS4_REPLY_VERSION {
+        return Err(AclError::OutboundError {
+            kind: OutboundErrorKind::Protocol,
+            message: format!("Invalid SOCKS4 reply version: {}", resp[0]),
+        });
+    }
+    if resp[1] != SOCKS4_REP_GRANTED {
+        return Err(AclError::OutboundError {
+            kind: OutboundErrorKind::Protocol,
+            message: format!("SOCKS4 request failed: {}", socks4_rep_to_string(resp[1])),
+        });
+    }
+    Ok(())
+}
+
+/// Build a SOCKS4 CONNECT request:
+/// VN + CD + DSTPORT + DSTIP + USERID + NULL [+ DOMAIN + NULL for SOCKS4a].
+fn build_socks4_request(addr: &Addr, user_id: &str) -> Result<Vec<u8>> {
+    let mut req = vec![SOCKS4_VERSION, SOCKS4_CMD_CONNECT];
+    req.extend(addr.port.to_be_bytes());
+
+    match addr.host.parse::<IpAddr>() {
+        Ok(IpAddr::V4(ip)) => {
+            req.extend(ip.octets());
+            req.extend(user_id.as_bytes());
+            req.push(0x00);
+        }
+        Ok(IpAddr::V6(_)) => {
+            return Err(AclError::OutboundError {
+                kind: OutboundErrorKind::InvalidInput,
+                message: "SOCKS4 does not support IPv6 targets".to_string(),
+            });
+        }
+        Err(_) => {
+            if addr.host.is_empty() || addr.host.len() > SOCKS4A_MAX_DOMAIN_LEN {
+                return Err(AclError::OutboundError {
+                    kind: OutboundErrorKind::InvalidInput,
+                    message: format!("Invalid SOCKS4a domain length: {} bytes", addr.host.len()),
+                });
+            }
+            // SOCKS4a: DSTIP 0.0.0.x with x != 0 signals that a domain follows
+            req.extend(Ipv4Addr::new(0, 0, 0, 1).octets());
+            req.extend(user_id.as_bytes());
+            req.push(0x00);
+            req.extend(addr.host.as_bytes());
+            req.push(0x00);
+        }
+    }
+    Ok(req)
+}
+
+/// SOCKS4/SOCKS4a proxy outbound.
+///
+/// Only CONNECT is supported; UDP is not available over SOCKS4. Domain
+/// targets are sent with SOCKS4a so the proxy resolves them, and IPv6
+/// targets are rejected. Like [`Socks5`](super::Socks5), ResolveInfo in
+/// Addr is ignored and only Host is used.
+pub struct Socks4 {
+    /// Proxy server address
+    addr: String,
+    /// USERID field sent with every request
+    user_id: String,
+    /// Connection timeout
+    timeout: Duration,
+    /// How the proxy server is reached
+    dialer: ProxyDialer,
+}
+
+impl Socks4 {
+    /// Create a new SOCKS4 outbound with an empty user-id.
+    pub fn new(addr: impl Into<String>) -> Self {
+        Self {
+            addr: addr.into(),
+            user_id: String::new(),
+            timeout: DEFAULT_DIALER_TIMEOUT,
+            dialer: ProxyDialer::default(),
+        }
+    }
+
+    /// Set the USERID field.
+    ///
+    /// Returns an error if the user-id contains a NUL byte, which would
+    /// terminate the field early.
+    pub fn with_user_id(mut self, user_id: impl Into<String>) -> Result<Self> {
+        let user_id = user_id.into();
+        if user_id.contains('\0') {
+            return Err(AclError::OutboundError {
+                kind: OutboundErrorKind::InvalidInput,
+                message: "SOCKS4 user-id must not contain NUL bytes".to_string(),
+            });
+        }
+        self.user_id = user_id;
+        Ok(self)
+    }
+
+    /// Set connection timeout.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Mark packets to the proxy server with a DSCP value (0-63) for QoS.
+    ///
+    /// Ignored when the server is reached through a dialer outbound; set
+    /// DSCP on that outbound instead.
+    /// Returns `ConfigError` if the value is above 63.
+    pub fn with_dscp(mut self, dscp: u8) -> Result<Self> {
+        self.dialer.set_dscp(dscp)?;
+        Ok(self)
+    }
+
+    /// Reach the proxy server through another outbound (proxy chaining).
+    ///
+    /// Applies to the sync [`Outbound`] impl.
+    pub fn with_dialer(mut self, dialer: Arc<dyn Outbound>) -> Self {
+        self.dialer.set(dialer);
+        self
+    }
+
+    /// Reach the proxy server through another async outbound.
+    ///
+    /// Applies to the [`AsyncOutbound`] impl.
+    #[cfg(feature = "async")]
+    pub fn with_async_dialer(mut self, dialer: Arc<dyn AsyncOutbound>) -> Self {
+        self.dialer.set_async(dialer);
+        self
+    }
+
+    /// Connect to the proxy and issue a CONNECT request.
+    fn connect(&self, addr: &Addr) -> Result<Box<dyn TcpConn>> {
+        let req = build_socks4_request(addr, &self.user_id)?;
+
+        let mut stream = self.dialer.dial(&self.addr, self.timeout)?;
+
+        stream.set_read_timeout(Some(SOCKS4_REQUEST_TIMEOUT)).ok();
+        stream.set_write_timeout(Some(SOCKS4_REQUEST_TIMEOUT)).ok();
+
+        stream
+            .write_all(&req)
+            .map_err(|e| AclError::OutboundError {
+                kind: OutboundErrorKind::Io,
+                message: format!("Failed to send request: {}", e),
+            })?;
+
+        let mut resp = [0u8; 8];
+        stream
+            .read_exact(&mut resp)
+            .map_err(|e| AclError::OutboundError {
+                kind: OutboundErrorKind::Io,
+                message: format!("Failed to read response: {}", e),
+            })?;
+        validate_socks4_response(&resp)?;
+
+        // Reset timeout
+        stream.set_read_timeout(None).ok();
+        stream.set_write_timeout(None).ok();
+
+        Ok(stream)
+    }
+
+    /// Async: Connect to the proxy and issue a CONNECT request.
+    #[cfg(feature = "async")]
+    async fn async_connect(&self, addr: &Addr) -> Result<Box<dyn AsyncTcpConn>> {
+        let req = build_socks4_request(addr, &self.user_id)?;
+
+        let mut stream = self.dialer.async_dial(&self.addr, self.timeout).await?;
+
+        tokio::time::timeout(SOCKS4_REQUEST_TIMEOUT, stream.write_all(&req))
+            .await
+            .map_err(|_| AclError::OutboundError {
+                kind: OutboundErrorKind::Timeout,
+                message: "Request timeout".to_string(),
+            })?
+            .map_err(|e| AclError::OutboundError {
+                kind: OutboundErrorKind::Io,
+                message: format!("Failed to send request: {}", e),
+            })?;
+
+        let mut resp = [0u8; 8];
+        tokio::time::timeout(SOCKS4_REQUEST_TIMEOUT, stream.read_exact(&mut resp))
+            .await
+            .map_err(|_| AclError::OutboundError {
+                kind: OutboundErrorKind::Timeout,
+                message: "Request timeout".to_string(),
+            })?
+            .map_err(|e| AclError::OutboundError {
+                kind: OutboundErrorKind::Io,
+                message: format!("Failed to read response: {}", e),
+            })?;
+        validate_socks4_response(&resp)?;
+
+        Ok(stream)
+    }
+}
+
+impl Outbound for Socks4 {
+    fn dial_tcp(&self, addr: &mut Addr) -> Result<Box<dyn TcpConn>> {
+        self.connect(addr)
+    }
+
+    fn dial_udp(&self, _addr: &mut Addr) -> Result<Box<dyn UdpConn>> {
+        Err(AclError::OutboundError {
+            kind: OutboundErrorKind::Unsupported,
+            message: "UDP not supported by SOCKS4 proxy".to_string(),
+        })
+    }
+}
+
+#[cfg(feature = "async")]
+#[async_trait]
+impl AsyncOutbound for Socks4 {
+    async fn dial_tcp(&self, addr: &mut Addr) -> Result<Box<dyn AsyncTcpConn>> {
+        self.async_connect(addr).await
+    }
+
+    async fn dial_udp(&self, _addr: &mut Addr) -> Result<Box<dyn AsyncUdpConn>> {
+        Err(AclError::OutboundError {
+            kind: OutboundErrorKind::Unsupported,
+            message: "UDP not supported by SOCKS4 proxy".to_string(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+
+    /// Spawn a SOCKS4 server that reads one request, answers with `rep`,
+    /// echoes once on success, and returns the raw request bytes.
+    pub(super) fn spawn_socks4_server(rep: u8) -> (u16, std::thread::JoinHandle<Vec<u8>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let handle = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut req = vec![0u8; 8];
+            stream.read_exact(&mut req).unwrap();
+            // USERID, then the SOCKS4a domain when DSTIP is 0.0.0.x
+            let fields = if req[4..7] == [0, 0, 0] && req[7] != 0 {
+                2
+            } else {
+                1
+            };
+            let mut byte = [0u8; 1];
+            for _ in 0..fields {
+                loop {
+                    stream.read_exact(&mut byte).unwrap();
+                    req.push(byte[0]);
+                    if byte[0] == 0 {
+                        break;
+                    }
+                }
+            }
+            stream.write_all(&[0, rep, 0, 0, 0, 0, 0, 0]).unwrap();
+            if rep == SOCKS4_REP_GRANTED {
+                let mut buf = [0u8; 4];
+                stream.read_exact(&mut buf).unwrap();
+                stream.write_all(&buf).unwrap();
+            }
+            req
+        });
+        (port, handle)
+    }
+
+    #[test]
+    fn test_build_socks4_request_ipv4() {
+        let req = build_socks4_request(&Addr::new("192.168.1.10", 8080), "bob").unwrap();
+        assert_eq!(
+            req,
+            vec![4, 1, 0x1F, 0x90, 192, 168, 1, 10, b'b', b'o', b'b', 0]
+        );
+    }
+
+    #[test]
+    fn test_build_socks4a_request_domain() {
+        let req = build_socks4_request(&Addr::new("example.com", 80), "").unwrap();
+        let mut expected = vec![4, 1, 0, 80, 0, 0, 0, 1, 0];
+        expected.extend(b"example.com\0");
+        assert_eq!(req, expected);
+    }
+
+    #[test]
+    fn test_build_socks4_request_rejects_ipv6() {
+        let result = build_socks4_request(&Addr::new("::1", 80), "");
+        assert!(matches!(
+            result,
+            Err(AclError::OutboundError {
+                kind: OutboundErrorKind::InvalidInput,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_socks4_with_user_id_rejects_nul() {
+        assert!(Socks4::new("127.0.0.1:1080").with_user_id("ok").is_ok());
+        assert!(Socks4::new("127.0.0.1:1080")
+            .with_user_id("bad\0id")
+            .is_err());
+    }
+
+    #[test]
+    fn test_validate_socks4_response() {
+        assert!(validate_socks4_response(&[0, 0x5A, 0, 0, 0, 0, 0, 0]).is_ok());
+        let err = validate_socks4_response(&[0, 0x5B, 0, 0, 0, 0, 0, 0]).unwrap_err();
+        assert!(err.to_string().contains("rejected"));
+        assert!(validate_socks4_response(&[5, 0x5A, 0, 0, 0, 0, 0, 0]).is_err());
+    }
+
+    #[test]
+    fn test_socks4_dial_tcp_domain() {
+        let (port, server) = spawn_socks4_server(SOCKS4_REP_GRANTED);
+        let socks4 = Socks4::new(format!("127.0.0.1:{}", port))
+            .with_user_id("gw")
+            .unwrap();
+
+        let mut addr = Addr::new("example.com", 443);
+        let mut conn = Outbound::dial_tcp(&socks4, &mut addr).unwrap();
+        conn.write_all(b"ping").unwrap();
+        let mut buf = [0u8; 4];
+        conn.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"ping");
+
+        let req = server.join().unwrap();
+        assert_eq!(&req[..8], &[4, 1, 0x01, 0xBB, 0, 0, 0, 1]);
+        assert_eq!(&req[8..], b"gw\0example.com\0");
+    }
+
+    #[test]
+    fn test_socks4_dial_tcp_rejected() {
+        let (port, server) = spawn_socks4_server(0x5B);
+        let socks4 = Socks4::new(format!("127.0.0.1:{}", port));
+
+        let mut addr = Addr::new("10.0.0.1", 22);
+        let result = Outbound::dial_tcp(&socks4, &mut addr);
+        assert!(matches!(
+            result,
+            Err(AclError::OutboundError {
+                kind: OutboundErrorKind::Protocol,
+                ..
+            })
+        ));
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn test_socks4_chained_through_dialer() {
+        use crate::outbound::dialer::test_relay::spawn_socks4_relay;
+
+        let (port, server) = spawn_socks4_server(SOCKS4_REP_GRANTED);
+        let (relay_port, relay) = spawn_socks4_relay();
+        let socks4 = Socks4::new(format!("127.0.0.1:{}", port))
+            .with_dialer(Arc::new(Socks4::new(format!("127.0.0.1:{}", relay_port))));
+
+        let mut addr = Addr::new("example.com", 443);
+        let mut conn = Outbound::dial_tcp(&socks4, &mut addr).unwrap();
+        conn.write_all(b"ping").unwrap();
+        let mut buf = [0u8; 4];
+        conn.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"ping");
+        drop(conn);
+
+        assert_eq!(&server.join().unwrap()[9..], b"example.com\0");
+        assert_eq!(relay.join().unwrap(), format!("127.0.0.1:{}", port));
+    }
+
+    #[test]
+    fn test_socks4_udp_not_supported() {
+        let socks4 = Socks4::new("127.0.0.1:1080");
+        let mut addr = Addr::new("example.com", 53);
+        assert!(matches!(
+            Outbound::dial_udp(&socks4, &mut addr),
+            Err(AclError::OutboundError {
+                kind: OutboundErrorKind::Unsupported,
+                ..
+            })
+        ));
+    }
+}
+
+#[cfg(all(test, feature = "async"))]
+mod async_tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_async_socks4_dial_tcp_ipv4() {
+        let (port, server) = super::tests::spawn_socks4_server(SOCKS4_REP_GRANTED);
+        let socks4 = Socks4::new(format!("127.0.0.1:{}", port));
+
+        let mut addr = Addr::new("10.1.2.3", 80);
+        let mut conn = AsyncOutbound::dial_tcp(&socks4, &mut addr).await.unwrap();
+        conn.write_all(b"pong").await.unwrap();
+        let mut buf = [0u8; 4];
+        conn.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"pong");
+
+        let req = tokio::task::spawn_blocking(move || server.join().unwrap())
+            .await
+            .unwrap();
+        assert_eq!(req, vec![4, 1, 0, 80, 10, 1, 2, 3, 0]);
+    }
+
+    #[tokio::test]
+    async fn test_async_socks4_chained_through_dialer() {
+        use crate::outbound::dialer::test_relay::spawn_socks4_relay;
+
+        let (port, server) = super::tests::spawn_socks4_server(SOCKS4_REP_GRANTED);
+        let (relay_port, relay) = spawn_socks4_relay();
+        let socks4 = Socks4::new(format!("127.0.0.1:{}", port))
+            .with_async_dialer(Arc::new(Socks4::new(format!("127.0.0.1:{}", relay_port))));
+
+        let mut addr = Addr::new("10.1.2.3", 80);
+        let mut conn = AsyncOutbound::dial_tcp(&socks4, &mut addr).await.unwrap();
+        conn.write_all(b"pong").await.unwrap();
+        let mut buf = [0u8; 4];
+        conn.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"pong");
+        drop(conn);
+
+        let (req, target) =
+            tokio::task::spawn_blocking(move || (server.join().unwrap(), relay.join().unwrap()))
+                .await
+                .unwrap();
+        assert_eq!(req, vec![4, 1, 0, 80, 10, 1, 2, 3, 0]);
+        assert_eq!(target, format!("127.0.0.1:{}", port));
+    }
+
+    #[tokio::test]
+    async fn test_async_socks4_dial_tcp_connection_refused() {
+        let socks4 = Socks4::new("127.0.0.1:59997");
+        let mut addr = Addr::new("example.com", 80);
+        assert!(AsyncOutbound::dial_tcp(&socks4, &mut addr).await.is_err());
+    }
+}
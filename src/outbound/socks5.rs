@@ -3,21 +3,26 @@
 //! Connects to targets through a SOCKS5 proxy server.
 
 use std::io::{Read, Write};
-use std::net::{IpAddr, SocketAddr, TcpStream, ToSocketAddrs, UdpSocket};
+use std::net::{IpAddr, UdpSocket};
+use std::sync::Arc;
 use std::time::Duration;
 
 use crate::error::{AclError, OutboundErrorKind, Result};
 
-use super::{Addr, Outbound, StdTcpConn, TcpConn, UdpConn, DEFAULT_DIALER_TIMEOUT};
+use super::dialer::{udp_not_chainable, ProxyDialer};
+use super::uot::{self, UotUdpConn};
+use super::{Addr, Outbound, TcpConn, UdpConn, DEFAULT_DIALER_TIMEOUT};
 
 #[cfg(feature = "async")]
-use super::{AsyncOutbound, AsyncTcpConn, AsyncUdpConn, TokioTcpConn};
+use super::uot::AsyncUotUdpConn;
+#[cfg(feature = "async")]
+use super::{AsyncOutbound, AsyncTcpConn, AsyncUdpConn};
 #[cfg(feature = "async")]
 use async_trait::async_trait;
 #[cfg(feature = "async")]
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 #[cfg(feature = "async")]
-use tokio::net::{TcpStream as TokioTcpStream, UdpSocket as TokioUdpSocket};
+use tokio::net::UdpSocket as TokioUdpSocket;
 
 const SOCKS5_VERSION: u8 = 0x05;
 const SOCKS5_AUTH_NONE: u8 = 0x00;
@@ -25,6 +30,7 @@ const SOCKS5_AUTH_PASSWORD: u8 = 0x02;
 const SOCKS5_AUTH_NO_ACCEPTABLE: u8 = 0xFF;
 
 const SOCKS5_CMD_CONNECT: u8 = 0x01;
+const SOCKS5_CMD_BIND: u8 = 0x02;
 const SOCKS5_CMD_UDP_ASSOCIATE: u8 = 0x03;
 
 const SOCKS5_ATYP_IPV4: u8 = 0x01;
@@ -94,7 +100,7 @@ fn build_auth_request(username: &str, password: &str) -> Vec<u8> {
 
 /// Parse a SOCKS5 bound address from a byte buffer (after the 4-byte response header).
 /// Returns (host, port, bytes_consumed).
-fn parse_bound_addr(atyp: u8, data: &[u8]) -> Result<(String, u16, usize)> {
+pub(super) fn parse_bound_addr(atyp: u8, data: &[u8]) -> Result<(String, u16, usize)> {
     match atyp {
         SOCKS5_ATYP_IPV4 => {
             if data.len() < 6 {
@@ -162,7 +168,7 @@ fn bound_addr_fixed_size(atyp: u8) -> Option<Result<usize>> {
 }
 
 /// Convert address to SOCKS5 format (free function).
-fn addr_to_socks5(host: &str) -> Result<(u8, Vec<u8>)> {
+pub(super) fn addr_to_socks5(host: &str) -> Result<(u8, Vec<u8>)> {
     if let Ok(ip) = host.parse::<IpAddr>() {
         match ip {
             IpAddr::V4(v4) => Ok((SOCKS5_ATYP_IPV4, v4.octets().to_vec())),
@@ -198,6 +204,10 @@ pub struct Socks5 {
     password: Option<String>,
     /// Connection timeout
     timeout: Duration,
+    /// Carry UDP inside a TCP CONNECT stream instead of UDP ASSOCIATE
+    udp_over_tcp: bool,
+    /// How the proxy server is reached
+    dialer: ProxyDialer,
 }
 
 impl Socks5 {
@@ -208,6 +218,8 @@ impl Socks5 {
             username: None,
             password: None,
             timeout: DEFAULT_DIALER_TIMEOUT,
+            udp_over_tcp: false,
+            dialer: ProxyDialer::default(),
         }
     }
 
@@ -244,6 +256,8 @@ impl Socks5 {
             username: Some(username),
             password: Some(password),
             timeout: DEFAULT_DIALER_TIMEOUT,
+            udp_over_tcp: false,
+            dialer: ProxyDialer::default(),
         })
     }
 
@@ -253,28 +267,106 @@ impl Socks5 {
         self
     }
 
-    /// Connect to the proxy and perform negotiation.
-    fn dial_and_negotiate(&self) -> Result<TcpStream> {
-        let addr: SocketAddr = self
-            .addr
-            .to_socket_addrs()
-            .map_err(|e| AclError::OutboundError {
-                kind: OutboundErrorKind::DnsFailed,
-                message: format!("Failed to resolve proxy address: {}", e),
-            })?
-            .next()
-            .ok_or_else(|| AclError::OutboundError {
-                kind: OutboundErrorKind::DnsFailed,
-                message: "No address resolved for proxy".to_string(),
-            })?;
+    /// Mark packets to the proxy server with a DSCP value (0-63) for QoS.
+    ///
+    /// Applies to the server connection and the UDP relay socket. The
+    /// server connection is not marked when reached through a dialer
+    /// outbound; set DSCP on that outbound instead.
+    /// Returns `ConfigError` if the value is above 63.
+    pub fn with_dscp(mut self, dscp: u8) -> Result<Self> {
+        self.dialer.set_dscp(dscp)?;
+        Ok(self)
+    }
 
-        let mut stream = TcpStream::connect_timeout(&addr, self.timeout).map_err(|e| {
-            AclError::OutboundError {
-                kind: OutboundErrorKind::ConnectionFailed,
-                message: format!("Failed to connect to proxy: {}", e),
-            }
-        })?;
+    /// Enable UDP-over-TCP (UoT v2) for `dial_udp`.
+    ///
+    /// Instead of UDP ASSOCIATE, datagrams are framed inside a CONNECT stream
+    /// to the magic host `sp.v2.udp-over-tcp.arpa`. This works behind NAT or
+    /// firewalls that block the UDP relay path, but requires a proxy that
+    /// understands the UoT convention (e.g. sing-box).
+    pub fn with_udp_over_tcp(mut self, enabled: bool) -> Self {
+        self.udp_over_tcp = enabled;
+        self
+    }
+
+    /// Reach the proxy server through another outbound (proxy chaining).
+    ///
+    /// Applies to the sync [`Outbound`] impl. UDP ASSOCIATE cannot be
+    /// chained; enable [`with_udp_over_tcp`](Self::with_udp_over_tcp) to
+    /// carry UDP through the chain instead. UoT needs a dialer whose
+    /// connections can be cloned into separate read and write handles;
+    /// through HTTPS or Shadowsocks dialers `dial_udp` fails with
+    /// `ConfigError`.
+    pub fn with_dialer(mut self, dialer: Arc<dyn Outbound>) -> Self {
+        self.dialer.set(dialer);
+        self
+    }
+
+    /// Reach the proxy server through another async outbound.
+    ///
+    /// Applies to the [`AsyncOutbound`] impl; see [`with_dialer`](Self::with_dialer).
+    #[cfg(feature = "async")]
+    pub fn with_async_dialer(mut self, dialer: Arc<dyn AsyncOutbound>) -> Self {
+        self.dialer.set_async(dialer);
+        self
+    }
+
+    /// Request a BIND for an incoming connection from `addr`.
+    ///
+    /// Returns once the proxy has replied with the address it listens on
+    /// (see [`Socks5Bind::bound_addr`]), which the caller passes to the peer,
+    /// e.g. in an FTP `PORT` command. [`Socks5Bind::accept`] then waits for
+    /// the proxy's second reply and yields the connection.
+    pub fn bind(&self, addr: &Addr) -> Result<Socks5Bind> {
+        let mut stream = self.dial_and_negotiate()?;
+        let (bound_host, bound_port) = self.request(&mut *stream, SOCKS5_CMD_BIND, addr)?;
+        Ok(Socks5Bind {
+            stream,
+            bound_addr: self.bind_addr(bound_host, bound_port),
+        })
+    }
+
+    /// Async: Request a BIND for an incoming connection from `addr`.
+    ///
+    /// See [`Socks5::bind`].
+    #[cfg(feature = "async")]
+    pub async fn async_bind(&self, addr: &Addr) -> Result<AsyncSocks5Bind> {
+        let mut stream = self.async_dial_and_negotiate().await?;
+        let (bound_host, bound_port) = self
+            .async_request(&mut *stream, SOCKS5_CMD_BIND, addr)
+            .await?;
+        Ok(AsyncSocks5Bind {
+            stream,
+            bound_addr: self.bind_addr(bound_host, bound_port),
+        })
+    }
+
+    /// Address a BIND listens on. An unspecified address (0.0.0.0 or ::)
+    /// is replaced with the proxy server's host.
+    fn bind_addr(&self, bound_host: String, bound_port: u16) -> Addr {
+        let is_unspecified = matches!(
+            bound_host.parse::<IpAddr>(),
+            Ok(ip) if ip.is_unspecified()
+        );
+        if is_unspecified {
+            let host = self
+                .proxy_host()
+                .trim_start_matches('[')
+                .trim_end_matches(']');
+            Addr::new(host, bound_port)
+        } else {
+            Addr::new(bound_host, bound_port)
+        }
+    }
+
+    /// Connect to the proxy and perform negotiation.
+    fn dial_and_negotiate(&self) -> Result<Box<dyn TcpConn>> {
+        let stream = self.dialer.dial(&self.addr, self.timeout)?;
+        self.negotiate(stream)
+    }
 
+    /// Perform method negotiation and authentication on a new connection.
+    fn negotiate(&self, mut stream: Box<dyn TcpConn>) -> Result<Box<dyn TcpConn>> {
         stream
             .set_read_timeout(Some(SOCKS5_NEGOTIATION_TIMEOUT))
             .ok();
@@ -384,7 +476,7 @@ impl Socks5 {
     }
 
     /// Send a SOCKS5 request and get the response.
-    fn request(&self, stream: &mut TcpStream, cmd: u8, addr: &Addr) -> Result<(String, u16)> {
+    fn request(&self, stream: &mut dyn TcpConn, cmd: u8, addr: &Addr) -> Result<(String, u16)> {
         stream.set_read_timeout(Some(SOCKS5_REQUEST_TIMEOUT)).ok();
         stream.set_write_timeout(Some(SOCKS5_REQUEST_TIMEOUT)).ok();
 
@@ -396,6 +488,17 @@ impl Socks5 {
                 message: format!("Failed to send request: {}", e),
             })?;
 
+        let bound = Self::read_reply(stream)?;
+
+        // Reset timeout
+        stream.set_read_timeout(None).ok();
+        stream.set_write_timeout(None).ok();
+
+        Ok(bound)
+    }
+
+    /// Read a SOCKS5 reply and return its bound address.
+    fn read_reply(stream: &mut dyn TcpConn) -> Result<(String, u16)> {
         // Read response header
         let mut resp_header = [0u8; 4];
         stream
@@ -437,13 +540,21 @@ impl Socks5 {
 
         let (bound_host, bound_port, _) = parse_bound_addr(resp_header[3], &bound_buf[..needed])?;
 
-        // Reset timeout
-        stream.set_read_timeout(None).ok();
-        stream.set_write_timeout(None).ok();
-
         Ok((bound_host, bound_port))
     }
 
+    /// Host part of the proxy address (formats: "host:port" or "[ipv6]:port").
+    fn proxy_host(&self) -> &str {
+        if let Some(bracket_end) = self.addr.rfind(']') {
+            // IPv6 format: [::1]:port → [::1]
+            &self.addr[..bracket_end + 1]
+        } else if let Some(colon_pos) = self.addr.rfind(':') {
+            &self.addr[..colon_pos]
+        } else {
+            &self.addr
+        }
+    }
+
     /// Resolve the SOCKS5 bound address for UDP.
     /// When the server returns an unspecified address (0.0.0.0 or ::),
     /// replaces it with the proxy server's host address.
@@ -454,16 +565,7 @@ impl Socks5 {
         );
 
         if is_unspecified {
-            // Extract host from self.addr (formats: "host:port" or "[ipv6]:port")
-            let proxy_host = if let Some(bracket_end) = self.addr.rfind(']') {
-                // IPv6 format: [::1]:port → [::1]
-                &self.addr[..bracket_end + 1]
-            } else if let Some(colon_pos) = self.addr.rfind(':') {
-                &self.addr[..colon_pos]
-            } else {
-                &self.addr
-            };
-            format!("{}:{}", proxy_host, bound_port)
+            format!("{}:{}", self.proxy_host(), bound_port)
         } else if bound_host.contains(':') {
             // IPv6 address: wrap in brackets for valid socket address
             format!("[{}]:{}", bound_host, bound_port)
@@ -473,31 +575,9 @@ impl Socks5 {
     }
 
     /// Async: Connect to the proxy and perform negotiation.
-    /// Uses tokio async DNS to avoid blocking the runtime.
     #[cfg(feature = "async")]
-    async fn async_dial_and_negotiate(&self) -> Result<TokioTcpStream> {
-        let addr: SocketAddr = tokio::net::lookup_host(&self.addr)
-            .await
-            .map_err(|e| AclError::OutboundError {
-                kind: OutboundErrorKind::DnsFailed,
-                message: format!("Failed to resolve proxy address: {}", e),
-            })?
-            .next()
-            .ok_or_else(|| AclError::OutboundError {
-                kind: OutboundErrorKind::DnsFailed,
-                message: "No address resolved for proxy".to_string(),
-            })?;
-
-        let mut stream = tokio::time::timeout(self.timeout, TokioTcpStream::connect(addr))
-            .await
-            .map_err(|_| AclError::OutboundError {
-                kind: OutboundErrorKind::Timeout,
-                message: "Connection timeout".to_string(),
-            })?
-            .map_err(|e| AclError::OutboundError {
-                kind: OutboundErrorKind::ConnectionFailed,
-                message: format!("Failed to connect to proxy: {}", e),
-            })?;
+    async fn async_dial_and_negotiate(&self) -> Result<Box<dyn AsyncTcpConn>> {
+        let mut stream = self.dialer.async_dial(&self.addr, self.timeout).await?;
 
         let auth_methods = if self.username.is_some() && self.password.is_some() {
             vec![SOCKS5_AUTH_NONE, SOCKS5_AUTH_PASSWORD]
@@ -613,7 +693,7 @@ impl Socks5 {
     #[cfg(feature = "async")]
     async fn async_request(
         &self,
-        stream: &mut TokioTcpStream,
+        stream: &mut dyn AsyncTcpConn,
         cmd: u8,
         addr: &Addr,
     ) -> Result<(String, u16)> {
@@ -630,13 +710,21 @@ impl Socks5 {
                 message: format!("Failed to send request: {}", e),
             })?;
 
-        let mut resp_header = [0u8; 4];
-        tokio::time::timeout(SOCKS5_REQUEST_TIMEOUT, stream.read_exact(&mut resp_header))
+        tokio::time::timeout(SOCKS5_REQUEST_TIMEOUT, Self::async_read_reply(stream))
             .await
             .map_err(|_| AclError::OutboundError {
                 kind: OutboundErrorKind::Timeout,
                 message: "Request timeout".to_string(),
             })?
+    }
+
+    /// Async: Read a SOCKS5 reply and return its bound address.
+    #[cfg(feature = "async")]
+    async fn async_read_reply(stream: &mut dyn AsyncTcpConn) -> Result<(String, u16)> {
+        let mut resp_header = [0u8; 4];
+        stream
+            .read_exact(&mut resp_header)
+            .await
             .map_err(|e| AclError::OutboundError {
                 kind: OutboundErrorKind::Io,
                 message: format!("Failed to read response: {}", e),
@@ -679,16 +767,40 @@ impl Socks5 {
     }
 }
 
+/// CONNECT target that opens a UoT session on the proxy.
+fn uot_target() -> Addr {
+    Addr::new(uot::UOT_MAGIC_ADDRESS, 0)
+}
+
 impl Outbound for Socks5 {
     fn dial_tcp(&self, addr: &mut Addr) -> Result<Box<dyn TcpConn>> {
         let mut stream = self.dial_and_negotiate()?;
-        self.request(&mut stream, SOCKS5_CMD_CONNECT, addr)?;
-        Ok(Box::new(StdTcpConn::new(stream)))
+        self.request(&mut *stream, SOCKS5_CMD_CONNECT, addr)?;
+        Ok(stream)
     }
 
     fn dial_udp(&self, addr: &mut Addr) -> Result<Box<dyn UdpConn>> {
+        if !self.udp_over_tcp && self.dialer.is_chained() {
+            return Err(udp_not_chainable("SOCKS5"));
+        }
+        if self.udp_over_tcp {
+            let stream = self.dialer.dial(&self.addr, self.timeout)?;
+            let writer = uot::split_writer(&*stream)?;
+            let mut stream = self.negotiate(stream)?;
+            self.request(&mut *stream, SOCKS5_CMD_CONNECT, &uot_target())?;
+            stream.set_write_timeout(Some(SOCKS5_REQUEST_TIMEOUT)).ok();
+            stream
+                .write_all(&uot::encode_request(addr)?)
+                .map_err(|e| AclError::OutboundError {
+                    kind: OutboundErrorKind::Io,
+                    message: format!("Failed to send UDP-over-TCP request: {}", e),
+                })?;
+            stream.set_write_timeout(None).ok();
+            return Ok(Box::new(UotUdpConn::new(stream, writer)));
+        }
         let mut stream = self.dial_and_negotiate()?;
-        let (bound_host, bound_port) = self.request(&mut stream, SOCKS5_CMD_UDP_ASSOCIATE, addr)?;
+        let (bound_host, bound_port) =
+            self.request(&mut *stream, SOCKS5_CMD_UDP_ASSOCIATE, addr)?;
 
         // Connect to the bound address (resolve unspecified 0.0.0.0 to proxy host)
         let udp_addr = self.resolve_bound_addr(&bound_host, bound_port);
@@ -703,6 +815,8 @@ impl Outbound for Socks5 {
             kind: OutboundErrorKind::ConnectionFailed,
             message: format!("Failed to bind UDP: {}", e),
         })?;
+        self.dialer
+            .mark_udp(&udp_socket, bind_addr.starts_with('['))?;
 
         udp_socket
             .connect(&udp_addr)
@@ -720,15 +834,34 @@ impl Outbound for Socks5 {
 impl AsyncOutbound for Socks5 {
     async fn dial_tcp(&self, addr: &mut Addr) -> Result<Box<dyn AsyncTcpConn>> {
         let mut stream = self.async_dial_and_negotiate().await?;
-        self.async_request(&mut stream, SOCKS5_CMD_CONNECT, addr)
+        self.async_request(&mut *stream, SOCKS5_CMD_CONNECT, addr)
             .await?;
-        Ok(Box::new(TokioTcpConn::new(stream)))
+        Ok(stream)
     }
 
     async fn dial_udp(&self, addr: &mut Addr) -> Result<Box<dyn AsyncUdpConn>> {
+        if !self.udp_over_tcp && self.dialer.is_async_chained() {
+            return Err(udp_not_chainable("SOCKS5"));
+        }
         let mut stream = self.async_dial_and_negotiate().await?;
+        if self.udp_over_tcp {
+            self.async_request(&mut *stream, SOCKS5_CMD_CONNECT, &uot_target())
+                .await?;
+            let req = uot::encode_request(addr)?;
+            tokio::time::timeout(SOCKS5_REQUEST_TIMEOUT, stream.write_all(&req))
+                .await
+                .map_err(|_| AclError::OutboundError {
+                    kind: OutboundErrorKind::Timeout,
+                    message: "Request timeout".to_string(),
+                })?
+                .map_err(|e| AclError::OutboundError {
+                    kind: OutboundErrorKind::Io,
+                    message: format!("Failed to send UDP-over-TCP request: {}", e),
+                })?;
+            return Ok(Box::new(AsyncUotUdpConn::new(stream)));
+        }
         let (bound_host, bound_port) = self
-            .async_request(&mut stream, SOCKS5_CMD_UDP_ASSOCIATE, addr)
+            .async_request(&mut *stream, SOCKS5_CMD_UDP_ASSOCIATE, addr)
             .await?;
 
         // Resolve unspecified 0.0.0.0/:: to proxy host
@@ -747,6 +880,8 @@ impl AsyncOutbound for Socks5 {
                     kind: OutboundErrorKind::ConnectionFailed,
                     message: format!("Failed to bind UDP: {}", e),
                 })?;
+        self.dialer
+            .mark_udp(&udp_socket, bind_addr.starts_with('['))?;
 
         udp_socket
             .connect(&udp_addr)
@@ -760,6 +895,55 @@ impl AsyncOutbound for Socks5 {
     }
 }
 
+/// A pending SOCKS5 BIND, returned by [`Socks5::bind`].
+pub struct Socks5Bind {
+    stream: Box<dyn TcpConn>,
+    bound_addr: Addr,
+}
+
+impl Socks5Bind {
+    /// Address the proxy listens on for the incoming connection.
+    pub fn bound_addr(&self) -> &Addr {
+        &self.bound_addr
+    }
+
+    /// Set how long [`accept`](Self::accept) waits for the incoming
+    /// connection. `None` (the default) waits indefinitely.
+    pub fn set_accept_timeout(&self, dur: Option<Duration>) -> Result<()> {
+        self.stream.set_read_timeout(dur)
+    }
+
+    /// Wait for the proxy's second reply and return the connection together
+    /// with the address of the peer that connected.
+    pub fn accept(mut self) -> Result<(Box<dyn TcpConn>, Addr)> {
+        let (peer_host, peer_port) = Socks5::read_reply(&mut *self.stream)?;
+        self.stream.set_read_timeout(None).ok();
+        Ok((self.stream, Addr::new(peer_host, peer_port)))
+    }
+}
+
+/// A pending async SOCKS5 BIND, returned by [`Socks5::async_bind`].
+#[cfg(feature = "async")]
+pub struct AsyncSocks5Bind {
+    stream: Box<dyn AsyncTcpConn>,
+    bound_addr: Addr,
+}
+
+#[cfg(feature = "async")]
+impl AsyncSocks5Bind {
+    /// Address the proxy listens on for the incoming connection.
+    pub fn bound_addr(&self) -> &Addr {
+        &self.bound_addr
+    }
+
+    /// Wait for the proxy's second reply and return the connection together
+    /// with the address of the peer that connected.
+    pub async fn accept(mut self) -> Result<(Box<dyn AsyncTcpConn>, Addr)> {
+        let (peer_host, peer_port) = Socks5::async_read_reply(&mut *self.stream).await?;
+        Ok((self.stream, Addr::new(peer_host, peer_port)))
+    }
+}
+
 /// SOCKS5 UDP connection wrapper.
 /// Encode an address into SOCKS5 UDP datagram header format.
 /// Returns: RSV(2) + FRAG(1) + ATYP(1) + ADDR + PORT(2).
@@ -885,12 +1069,12 @@ fn socks5_udp_decode_addr(data: &[u8]) -> Result<(Addr, usize)> {
 }
 
 struct Socks5UdpConn {
-    _tcp_conn: TcpStream, // Keep TCP connection alive
+    _tcp_conn: Box<dyn TcpConn>, // Keep TCP connection alive
     udp_socket: UdpSocket,
 }
 
 impl Socks5UdpConn {
-    fn new(tcp_conn: TcpStream, udp_socket: UdpSocket) -> Self {
+    fn new(tcp_conn: Box<dyn TcpConn>, udp_socket: UdpSocket) -> Self {
         Self {
             _tcp_conn: tcp_conn,
             udp_socket,
@@ -936,13 +1120,13 @@ impl UdpConn for Socks5UdpConn {
 /// Async SOCKS5 UDP connection wrapper.
 #[cfg(feature = "async")]
 struct AsyncSocks5UdpConn {
-    _tcp_conn: TokioTcpStream,
+    _tcp_conn: Box<dyn AsyncTcpConn>,
     udp_socket: TokioUdpSocket,
 }
 
 #[cfg(feature = "async")]
 impl AsyncSocks5UdpConn {
-    fn new(tcp_conn: TokioTcpStream, udp_socket: TokioUdpSocket) -> Self {
+    fn new(tcp_conn: Box<dyn AsyncTcpConn>, udp_socket: TokioUdpSocket) -> Self {
         Self {
             _tcp_conn: tcp_conn,
             udp_socket,
@@ -992,6 +1176,7 @@ impl AsyncUdpConn for AsyncSocks5UdpConn {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::net::ToSocketAddrs;
 
     #[test]
     fn test_socks5_new() {
@@ -1473,6 +1658,260 @@ mod tests {
         assert!(result.unwrap().is_err());
     }
 
+    /// Minimal no-auth SOCKS5 server that accepts one UoT session and echoes
+    /// every datagram back reversed. Returns the UoT request header it received.
+    pub(super) fn spawn_uot_proxy() -> (u16, std::thread::JoinHandle<Vec<u8>>) {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let handle = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut greeting = [0u8; 3];
+            stream.read_exact(&mut greeting).unwrap();
+            stream
+                .write_all(&[SOCKS5_VERSION, SOCKS5_AUTH_NONE])
+                .unwrap();
+
+            let mut head = [0u8; 5];
+            stream.read_exact(&mut head).unwrap();
+            assert_eq!(
+                &head[..4],
+                &[0x05, SOCKS5_CMD_CONNECT, 0x00, SOCKS5_ATYP_DOMAIN]
+            );
+            let mut host = vec![0u8; head[4] as usize + 2];
+            stream.read_exact(&mut host).unwrap();
+            assert_eq!(&host[..host.len() - 2], uot::UOT_MAGIC_ADDRESS.as_bytes());
+            stream
+                .write_all(&[0x05, 0x00, 0x00, SOCKS5_ATYP_IPV4, 0, 0, 0, 0, 0, 0])
+                .unwrap();
+
+            // isConnect + IPv4 destination + port
+            let mut request = vec![0u8; 8];
+            stream.read_exact(&mut request).unwrap();
+
+            let mut buf = [0u8; 2048];
+            while let Ok((n, addr)) = uot::read_packet(&mut stream, &mut buf) {
+                let mut reply = buf[..n].to_vec();
+                reply.reverse();
+                stream
+                    .write_all(&uot::encode_packet(&addr, &reply).unwrap())
+                    .unwrap();
+            }
+            request
+        });
+        (port, handle)
+    }
+
+    #[test]
+    fn test_socks5_udp_over_tcp() {
+        let (port, server) = spawn_uot_proxy();
+        let socks5 = Socks5::new(format!("127.0.0.1:{}", port)).with_udp_over_tcp(true);
+
+        let mut addr = Addr::new("9.9.9.9", 53);
+        let conn = Outbound::dial_udp(&socks5, &mut addr).unwrap();
+        conn.write_to(b"first", &addr).unwrap();
+        conn.write_to(b"second", &Addr::new("dns.example", 5353))
+            .unwrap();
+
+        let mut buf = [0u8; 64];
+        let (n, from) = conn.read_from(&mut buf).unwrap();
+        assert_eq!(&buf[..n], b"tsrif");
+        assert_eq!((from.host.as_str(), from.port), ("9.9.9.9", 53));
+        let (n, from) = conn.read_from(&mut buf).unwrap();
+        assert_eq!(&buf[..n], b"dnoces");
+        assert_eq!((from.host.as_str(), from.port), ("dns.example", 5353));
+
+        drop(conn);
+        assert_eq!(server.join().unwrap(), vec![0x00, 0x00, 9, 9, 9, 9, 0, 53]);
+    }
+
+    #[test]
+    fn test_socks5_udp_over_tcp_chained_through_dialer() {
+        use crate::outbound::dialer::test_relay::spawn_socks4_relay;
+        use crate::outbound::Socks4;
+
+        let (port, server) = spawn_uot_proxy();
+        let (relay_port, relay) = spawn_socks4_relay();
+        let socks5 = Socks5::new(format!("127.0.0.1:{}", port))
+            .with_udp_over_tcp(true)
+            .with_dialer(Arc::new(Socks4::new(format!("127.0.0.1:{}", relay_port))));
+
+        let mut addr = Addr::new("9.9.9.9", 53);
+        let conn = Outbound::dial_udp(&socks5, &mut addr).unwrap();
+        conn.write_to(b"chained", &addr).unwrap();
+        let mut buf = [0u8; 64];
+        let (n, _) = conn.read_from(&mut buf).unwrap();
+        assert_eq!(&buf[..n], b"deniahc");
+
+        drop(conn);
+        server.join().unwrap();
+        assert_eq!(relay.join().unwrap(), format!("127.0.0.1:{}", port));
+    }
+
+    #[test]
+    fn test_socks5_udp_over_tcp_chained_through_http() {
+        use crate::outbound::dialer::test_relay::spawn_http_relay;
+        use crate::outbound::Http;
+
+        let (port, server) = spawn_uot_proxy();
+        let (relay_port, relay) = spawn_http_relay();
+        let http = Http::new(format!("127.0.0.1:{}", relay_port));
+        let socks5 = Socks5::new(format!("127.0.0.1:{}", port))
+            .with_udp_over_tcp(true)
+            .with_dialer(Arc::new(http));
+
+        let mut addr = Addr::new("9.9.9.9", 53);
+        let conn = Outbound::dial_udp(&socks5, &mut addr).unwrap();
+        std::thread::scope(|s| {
+            // A reader blocked before anything is sent must not hold off writes
+            let reader = s.spawn(|| {
+                let mut buf = [0u8; 64];
+                let (n, _) = conn.read_from(&mut buf).unwrap();
+                buf[..n].to_vec()
+            });
+            std::thread::sleep(std::time::Duration::from_millis(50));
+            conn.write_to(b"first", &addr).unwrap();
+            assert_eq!(reader.join().unwrap(), b"tsrif");
+        });
+
+        drop(conn);
+        server.join().unwrap();
+        assert_eq!(relay.join().unwrap(), format!("127.0.0.1:{}", port));
+    }
+
+    #[test]
+    fn test_socks5_udp_over_tcp_rejects_unsplittable_dialer() {
+        use crate::outbound::http::tls_test;
+        use crate::outbound::{Http, TlsOptions};
+
+        let (port, server) = tls_test::spawn_https_proxy(false);
+        let https = Http::new(format!("127.0.0.1:{}", port))
+            .with_tls(TlsOptions {
+                server_name: Some("localhost".to_string()),
+                ca_file: Some(tls_test::testdata("ca.pem")),
+                ..Default::default()
+            })
+            .unwrap();
+        let socks5 = Socks5::new("127.0.0.1:1080")
+            .with_udp_over_tcp(true)
+            .with_dialer(Arc::new(https));
+
+        let mut addr = Addr::new("9.9.9.9", 53);
+        let err = Outbound::dial_udp(&socks5, &mut addr).err().unwrap();
+        assert!(matches!(err, AclError::ConfigError(_)), "{}", err);
+        // Rejected before any SOCKS5 bytes went through the tunnel
+        assert!(server
+            .join()
+            .unwrap()
+            .starts_with("CONNECT 127.0.0.1:1080 HTTP/1.1\r\n"));
+    }
+
+    #[test]
+    fn test_socks5_udp_associate_not_chainable() {
+        let socks5 =
+            Socks5::new("127.0.0.1:1080").with_dialer(Arc::new(crate::outbound::Direct::new()));
+        let mut addr = Addr::new("9.9.9.9", 53);
+        let err = Outbound::dial_udp(&socks5, &mut addr).err().unwrap();
+        assert!(matches!(
+            err,
+            AclError::OutboundError {
+                kind: OutboundErrorKind::Unsupported,
+                ..
+            }
+        ));
+    }
+
+    /// Minimal no-auth SOCKS5 server for BIND: replies with an unspecified
+    /// bound address on port 4242, then sends the second reply with `rep`
+    /// (peer 203.0.113.5:2121) and echoes data. Returns the request bytes.
+    pub(super) fn spawn_bind_proxy(rep: u8) -> (u16, std::thread::JoinHandle<Vec<u8>>) {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let handle = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut greeting = [0u8; 3];
+            stream.read_exact(&mut greeting).unwrap();
+            stream
+                .write_all(&[SOCKS5_VERSION, SOCKS5_AUTH_NONE])
+                .unwrap();
+
+            // VER CMD RSV ATYP(IPv4) ADDR PORT
+            let mut request = vec![0u8; 10];
+            stream.read_exact(&mut request).unwrap();
+            stream
+                .write_all(&[0x05, 0x00, 0x00, SOCKS5_ATYP_IPV4, 0, 0, 0, 0, 0x10, 0x92])
+                .unwrap();
+            stream
+                .write_all(&[
+                    0x05,
+                    rep,
+                    0x00,
+                    SOCKS5_ATYP_IPV4,
+                    203,
+                    0,
+                    113,
+                    5,
+                    0x08,
+                    0x49,
+                ])
+                .unwrap();
+
+            let mut buf = [0u8; 1024];
+            while let Ok(n) = stream.read(&mut buf) {
+                if n == 0 || stream.write_all(&buf[..n]).is_err() {
+                    break;
+                }
+            }
+            request
+        });
+        (port, handle)
+    }
+
+    #[test]
+    fn test_socks5_bind() {
+        let (port, server) = spawn_bind_proxy(SOCKS5_REP_SUCCESS);
+        let socks5 = Socks5::new(format!("127.0.0.1:{}", port));
+
+        let bind = socks5.bind(&Addr::new("203.0.113.5", 0)).unwrap();
+        // Unspecified bound address is replaced with the proxy host
+        assert_eq!(bind.bound_addr().host, "127.0.0.1");
+        assert_eq!(bind.bound_addr().port, 4242);
+
+        let (mut conn, peer) = bind.accept().unwrap();
+        assert_eq!((peer.host.as_str(), peer.port), ("203.0.113.5", 2121));
+        conn.write_all(b"220 ready").unwrap();
+        let mut buf = [0u8; 9];
+        conn.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"220 ready");
+
+        drop(conn);
+        let request = server.join().unwrap();
+        assert_eq!(
+            &request[..4],
+            &[0x05, SOCKS5_CMD_BIND, 0x00, SOCKS5_ATYP_IPV4]
+        );
+        assert_eq!(&request[4..8], &[203, 0, 113, 5]);
+    }
+
+    #[test]
+    fn test_socks5_bind_second_reply_failure() {
+        let (port, _server) = spawn_bind_proxy(0x05);
+        let socks5 = Socks5::new(format!("127.0.0.1:{}", port));
+
+        let bind = socks5.bind(&Addr::new("203.0.113.5", 0)).unwrap();
+        let err = bind.accept().err().unwrap();
+        assert!(err.to_string().contains("connection refused"), "{}", err);
+    }
+
+    #[test]
+    fn test_socks5_bind_addr_ipv6_proxy() {
+        let socks5 = Socks5::new("[2001:db8::1]:1080");
+        let addr = socks5.bind_addr("::".to_string(), 5000);
+        assert_eq!(addr.host, "2001:db8::1");
+        assert_eq!(addr.port, 5000);
+        let addr = socks5.bind_addr("198.51.100.1".to_string(), 5001);
+        assert_eq!(addr.host, "198.51.100.1");
+    }
+
     // ===== Bug verification tests =====
 
     #[test]
@@ -1586,6 +2025,53 @@ mod async_tests {
         server.abort();
     }
 
+    #[tokio::test]
+    async fn test_async_socks5_udp_over_tcp() {
+        let (port, server) = super::tests::spawn_uot_proxy();
+        let socks5 = Socks5::new(format!("127.0.0.1:{}", port)).with_udp_over_tcp(true);
+
+        let mut addr = Addr::new("9.9.9.9", 53);
+        let conn = AsyncOutbound::dial_udp(&socks5, &mut addr).await.unwrap();
+        conn.write_to(b"ping", &addr).await.unwrap();
+        let mut buf = [0u8; 64];
+        let (n, from) = conn.read_from(&mut buf).await.unwrap();
+        assert_eq!(&buf[..n], b"gnip");
+        assert_eq!(from.port, 53);
+
+        drop(conn);
+        let request = tokio::task::spawn_blocking(move || server.join().unwrap())
+            .await
+            .unwrap();
+        assert_eq!(request[0], 0x00);
+    }
+
+    #[tokio::test]
+    async fn test_async_socks5_bind() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let (port, server) = super::tests::spawn_bind_proxy(SOCKS5_REP_SUCCESS);
+        let socks5 = Socks5::new(format!("127.0.0.1:{}", port));
+
+        let bind = socks5
+            .async_bind(&Addr::new("203.0.113.5", 0))
+            .await
+            .unwrap();
+        assert_eq!(bind.bound_addr().port, 4242);
+
+        let (mut conn, peer) = bind.accept().await.unwrap();
+        assert_eq!(peer.port, 2121);
+        conn.write_all(b"hello").await.unwrap();
+        let mut buf = [0u8; 5];
+        conn.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"hello");
+
+        drop(conn);
+        let request = tokio::task::spawn_blocking(move || server.join().unwrap())
+            .await
+            .unwrap();
+        assert_eq!(request[1], SOCKS5_CMD_BIND);
+    }
+
     #[tokio::test]
     async fn test_async_socks5_dial_tcp_domain_name_proxy() {
         // Bug: Socks5::async_dial_and_negotiate() uses SocketAddr::parse() which rejects domain names.
@@ -0,0 +1,456 @@
+//! TLS client support for proxy outbounds.
+//!
+//! Wraps rustls (ring provider) so proxy outbounds can speak TLS to the proxy
+//! server itself, with optional custom CA bundle, client certificate and an
+//! explicit insecure-skip-verify switch.
+
+use std::fmt;
+use std::io::{Read, Write};
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use parking_lot::Mutex;
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::crypto::{
+    ring as ring_provider, verify_tls12_signature, verify_tls13_signature, CryptoProvider,
+};
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName, UnixTime};
+use rustls::{ClientConfig, ClientConnection, DigitallySignedStruct, RootCertStore, StreamOwned};
+
+use crate::error::{AclError, OutboundErrorKind, Result};
+
+use super::TcpConn;
+
+#[cfg(feature = "async")]
+use super::AsyncTcpConn;
+
+/// TLS options for connecting to a proxy server over TLS.
+#[derive(Debug, Clone, Default)]
+pub struct TlsOptions {
+    /// Server name used for SNI and certificate verification.
+    /// Defaults to the host part of the proxy address.
+    pub server_name: Option<String>,
+    /// PEM file with trusted CA certificates.
+    /// Replaces the built-in webpki roots when set.
+    pub ca_file: Option<PathBuf>,
+    /// PEM file with the client certificate chain (mutual TLS).
+    /// Must be set together with `client_key_file`.
+    pub client_cert_file: Option<PathBuf>,
+    /// PEM file with the client private key (PKCS#8, PKCS#1 or SEC1).
+    pub client_key_file: Option<PathBuf>,
+    /// Skip server certificate verification. Insecure; use for testing only.
+    pub insecure: bool,
+}
+
+/// Prepared TLS client: shared rustls config plus the server name to present.
+#[derive(Clone)]
+pub(crate) struct TlsConnector {
+    config: Arc<ClientConfig>,
+    server_name: ServerName<'static>,
+}
+
+impl fmt::Debug for TlsConnector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TlsConnector")
+            .field("server_name", &self.server_name)
+            .finish_non_exhaustive()
+    }
+}
+
+impl TlsConnector {
+    /// Build a connector from options. `default_host` is used for SNI when
+    /// `opts.server_name` is not set.
+    pub(crate) fn new(opts: &TlsOptions, default_host: &str) -> Result<Self> {
+        let host = opts.server_name.as_deref().unwrap_or(default_host);
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+        let server_name = ServerName::try_from(host.to_string()).map_err(|e| {
+            AclError::ConfigError(format!("Invalid TLS server name '{}': {}", host, e))
+        })?;
+
+        let provider = Arc::new(ring_provider::default_provider());
+        let builder = ClientConfig::builder_with_provider(provider.clone())
+            .with_safe_default_protocol_versions()
+            .map_err(|e| AclError::ConfigError(format!("Invalid TLS configuration: {}", e)))?;
+
+        let builder = if opts.insecure {
+            builder
+                .dangerous()
+                .with_custom_certificate_verifier(Arc::new(NoVerifier(provider)))
+        } else {
+            builder.with_root_certificates(load_roots(opts)?)
+        };
+
+        let config = match (&opts.client_cert_file, &opts.client_key_file) {
+            (Some(cert_file), Some(key_file)) => {
+                let certs = CertificateDer::pem_file_iter(cert_file)
+                    .and_then(|iter| iter.collect::<std::result::Result<Vec<_>, _>>())
+                    .map_err(|e| {
+                        AclError::ConfigError(format!(
+                            "Failed to load client certificate '{}': {}",
+                            cert_file.display(),
+                            e
+                        ))
+                    })?;
+                let key = PrivateKeyDer::from_pem_file(key_file).map_err(|e| {
+                    AclError::ConfigError(format!(
+                        "Failed to load client key '{}': {}",
+                        key_file.display(),
+                        e
+                    ))
+                })?;
+                builder.with_client_auth_cert(certs, key).map_err(|e| {
+                    AclError::ConfigError(format!("Invalid client certificate: {}", e))
+                })?
+            }
+            (None, None) => builder.with_no_client_auth(),
+            _ => {
+                return Err(AclError::ConfigError(
+                    "client_cert_file and client_key_file must be set together".to_string(),
+                ))
+            }
+        };
+
+        Ok(Self {
+            config: Arc::new(config),
+            server_name,
+        })
+    }
+
+    /// Perform a blocking TLS handshake over `stream`.
+    ///
+    /// The caller is responsible for setting read/write timeouts on `stream`.
+    pub(crate) fn connect(&self, stream: Box<dyn TcpConn>) -> Result<TlsTcpConn> {
+        let conn = ClientConnection::new(self.config.clone(), self.server_name.clone())
+            .map_err(|e| tls_error(e.to_string()))?;
+        let mut tls = StreamOwned::new(conn, stream);
+        while tls.conn.is_handshaking() {
+            tls.conn
+                .complete_io(&mut tls.sock)
+                .map_err(|e| tls_error(e.to_string()))?;
+        }
+        Ok(TlsTcpConn {
+            stream: Mutex::new(tls),
+        })
+    }
+
+    /// Perform a TLS handshake over a tokio stream.
+    #[cfg(feature = "async")]
+    pub(crate) async fn async_connect(
+        &self,
+        stream: Box<dyn AsyncTcpConn>,
+    ) -> Result<AsyncTlsTcpConn> {
+        let connector = tokio_rustls::TlsConnector::from(self.config.clone());
+        let stream = connector
+            .connect(self.server_name.clone(), stream)
+            .await
+            .map_err(|e| tls_error(e.to_string()))?;
+        Ok(AsyncTlsTcpConn { stream })
+    }
+}
+
+fn tls_error(message: String) -> AclError {
+    AclError::OutboundError {
+        kind: OutboundErrorKind::Protocol,
+        message: format!("TLS handshake failed: {}", message),
+    }
+}
+
+/// Load trust anchors from `opts.ca_file`, or the built-in webpki roots.
+fn load_roots(opts: &TlsOptions) -> Result<RootCertStore> {
+    let Some(ref ca_file) = opts.ca_file else {
+        return Ok(RootCertStore {
+            roots: webpki_roots::TLS_SERVER_ROOTS.to_vec(),
+        });
+    };
+
+    let load_err = |e: &dyn fmt::Display| {
+        AclError::ConfigError(format!(
+            "Failed to load CA file '{}': {}",
+            ca_file.display(),
+            e
+        ))
+    };
+    let mut roots = RootCertStore::empty();
+    for cert in CertificateDer::pem_file_iter(ca_file).map_err(|e| load_err(&e))? {
+        roots
+            .add(cert.map_err(|e| load_err(&e))?)
+            .map_err(|e| load_err(&e))?;
+    }
+    if roots.is_empty() {
+        return Err(load_err(&"no certificates found"));
+    }
+    Ok(roots)
+}
+
+/// Certificate verifier that accepts any server certificate.
+/// Handshake signatures are still checked so the peer must hold the key.
+#[derive(Debug)]
+struct NoVerifier(Arc<CryptoProvider>);
+
+impl ServerCertVerifier for NoVerifier {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> std::result::Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> std::result::Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls12_signature(
+            message,
+            cert,
+            dss,
+            &self.0.signature_verification_algorithms,
+        )
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> std::result::Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls13_signature(
+            message,
+            cert,
+            dss,
+            &self.0.signature_verification_algorithms,
+        )
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<rustls::SignatureScheme> {
+        self.0.signature_verification_algorithms.supported_schemes()
+    }
+}
+
+/// Blocking TLS client connection over a TCP connection.
+///
+/// The stream sits behind a mutex only so `shutdown(&self)` can send
+/// `close_notify`; reads and writes go through `get_mut` without locking.
+pub(crate) struct TlsTcpConn {
+    stream: Mutex<StreamOwned<ClientConnection, Box<dyn TcpConn>>>,
+}
+
+impl Read for TlsTcpConn {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.stream.get_mut().read(buf)
+    }
+}
+
+impl Write for TlsTcpConn {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.stream.get_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.stream.get_mut().flush()
+    }
+}
+
+impl TcpConn for TlsTcpConn {
+    fn local_addr(&self) -> Result<SocketAddr> {
+        self.stream.lock().sock.local_addr()
+    }
+
+    fn peer_addr(&self) -> Result<SocketAddr> {
+        self.stream.lock().sock.peer_addr()
+    }
+
+    fn set_read_timeout(&self, dur: Option<Duration>) -> Result<()> {
+        self.stream.lock().sock.set_read_timeout(dur)
+    }
+
+    fn set_write_timeout(&self, dur: Option<Duration>) -> Result<()> {
+        self.stream.lock().sock.set_write_timeout(dur)
+    }
+
+    fn shutdown(&self, how: std::net::Shutdown) -> Result<()> {
+        let mut stream = self.stream.lock();
+        if how != std::net::Shutdown::Read {
+            stream.conn.send_close_notify();
+            // Best effort: the peer may already be gone, and the TCP
+            // shutdown below should happen regardless
+            stream.flush().ok();
+        }
+        stream.sock.shutdown(how)
+    }
+}
+
+/// Async TLS client connection over a TCP connection.
+#[cfg(feature = "async")]
+pub(crate) struct AsyncTlsTcpConn {
+    stream: tokio_rustls::client::TlsStream<Box<dyn AsyncTcpConn>>,
+}
+
+#[cfg(feature = "async")]
+impl tokio::io::AsyncRead for AsyncTlsTcpConn {
+    fn poll_read(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+        buf: &mut tokio::io::ReadBuf<'_>,
+    ) -> std::task::Poll<std::io::Result<()>> {
+        std::pin::Pin::new(&mut self.stream).poll_read(cx, buf)
+    }
+}
+
+#[cfg(feature = "async")]
+impl tokio::io::AsyncWrite for AsyncTlsTcpConn {
+    fn poll_write(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+        buf: &[u8],
+    ) -> std::task::Poll<std::io::Result<usize>> {
+        std::pin::Pin::new(&mut self.stream).poll_write(cx, buf)
+    }
+
+    fn poll_flush(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<std::io::Result<()>> {
+        std::pin::Pin::new(&mut self.stream).poll_flush(cx)
+    }
+
+    fn poll_shutdown(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<std::io::Result<()>> {
+        std::pin::Pin::new(&mut self.stream).poll_shutdown(cx)
+    }
+}
+
+#[cfg(feature = "async")]
+impl AsyncTcpConn for AsyncTlsTcpConn {
+    fn local_addr(&self) -> Result<SocketAddr> {
+        self.stream.get_ref().0.local_addr()
+    }
+
+    fn peer_addr(&self) -> Result<SocketAddr> {
+        self.stream.get_ref().0.peer_addr()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn testdata(name: &str) -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("testdata/tls")
+            .join(name)
+    }
+
+    #[test]
+    fn test_tls_shutdown_sends_close_notify() {
+        use crate::outbound::http::tls_test::server_config;
+        use crate::outbound::StdTcpConn;
+        use rustls::ServerConnection;
+        use std::net::{Shutdown, TcpListener, TcpStream};
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = std::thread::spawn(move || {
+            let (sock, _) = listener.accept().unwrap();
+            sock.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+            let conn = ServerConnection::new(server_config(false)).unwrap();
+            let mut tls = StreamOwned::new(conn, sock);
+            let mut received = Vec::new();
+            // A clean EOF needs close_notify; a bare FIN is UnexpectedEof
+            tls.read_to_end(&mut received).map(|_| received)
+        });
+
+        let connector = TlsConnector::new(
+            &TlsOptions {
+                server_name: Some("localhost".to_string()),
+                ca_file: Some(testdata("ca.pem")),
+                ..Default::default()
+            },
+            "localhost",
+        )
+        .unwrap();
+        let stream = TcpStream::connect(addr).unwrap();
+        let mut conn = connector
+            .connect(Box::new(StdTcpConn::new(stream)))
+            .unwrap();
+        conn.write_all(b"bye").unwrap();
+        conn.flush().unwrap();
+        conn.shutdown(Shutdown::Write).unwrap();
+
+        assert_eq!(server.join().unwrap().unwrap(), b"bye");
+    }
+
+    #[test]
+    fn test_tls_connector_default_roots() {
+        let connector = TlsConnector::new(&TlsOptions::default(), "proxy.example.com").unwrap();
+        assert_eq!(
+            connector.server_name,
+            ServerName::try_from("proxy.example.com").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_tls_connector_ip_and_bracketed_server_name() {
+        let connector = TlsConnector::new(&TlsOptions::default(), "[::1]").unwrap();
+        assert!(matches!(connector.server_name, ServerName::IpAddress(_)));
+
+        let opts = TlsOptions {
+            server_name: Some("sni.example.com".to_string()),
+            ..Default::default()
+        };
+        let connector = TlsConnector::new(&opts, "127.0.0.1").unwrap();
+        assert_eq!(
+            connector.server_name,
+            ServerName::try_from("sni.example.com").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_tls_connector_config_errors() {
+        let opts = TlsOptions {
+            ca_file: Some(testdata("missing.pem")),
+            ..Default::default()
+        };
+        assert!(matches!(
+            TlsConnector::new(&opts, "localhost"),
+            Err(AclError::ConfigError(_))
+        ));
+
+        // A key file contains no certificates
+        let opts = TlsOptions {
+            ca_file: Some(testdata("client.key")),
+            ..Default::default()
+        };
+        assert!(matches!(
+            TlsConnector::new(&opts, "localhost"),
+            Err(AclError::ConfigError(_))
+        ));
+
+        let opts = TlsOptions {
+            client_cert_file: Some(testdata("client.pem")),
+            ..Default::default()
+        };
+        assert!(matches!(
+            TlsConnector::new(&opts, "localhost"),
+            Err(AclError::ConfigError(_))
+        ));
+
+        let opts = TlsOptions {
+            ca_file: Some(testdata("ca.pem")),
+            client_cert_file: Some(testdata("client.pem")),
+            client_key_file: Some(testdata("client.key")),
+            ..Default::default()
+        };
+        assert!(TlsConnector::new(&opts, "localhost").is_ok());
+    }
+}
@@ -0,0 +1,343 @@
+//! UDP-over-TCP (UoT) framing.
+//!
+//! Implements version 2 of the `udp-over-tcp` convention used by sing-box and
+//! compatible servers: the client opens a stream to the magic host
+//! [`UOT_MAGIC_ADDRESS`], sends a request header, then exchanges datagrams
+//! framed as `ADDR + PORT + LENGTH(u16) + PAYLOAD` in both directions.
+//!
+//! Addresses use the UoT family bytes (0x00 IPv4, 0x01 IPv6, 0x02 domain),
+//! not the SOCKS5 ATYP values.
+
+use parking_lot::Mutex;
+use std::io::{self, Read, Write};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use crate::error::{AclError, OutboundErrorKind, Result};
+
+use super::{Addr, TcpConn, UdpConn};
+
+#[cfg(feature = "async")]
+use super::{AsyncTcpConn, AsyncUdpConn};
+#[cfg(feature = "async")]
+use async_trait::async_trait;
+#[cfg(feature = "async")]
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+#[cfg(feature = "async")]
+use tokio::io::{ReadHalf, WriteHalf};
+
+/// Magic target host that asks the server for a UoT v2 session.
+pub(super) const UOT_MAGIC_ADDRESS: &str = "sp.v2.udp-over-tcp.arpa";
+
+const UOT_FAMILY_IPV4: u8 = 0x00;
+const UOT_FAMILY_IPV6: u8 = 0x01;
+const UOT_FAMILY_DOMAIN: u8 = 0x02;
+
+/// Encode an address in UoT format: FAMILY + ADDR + PORT.
+fn encode_addr(addr: &Addr, out: &mut Vec<u8>) -> Result<()> {
+    match addr.host.parse::<IpAddr>() {
+        Ok(IpAddr::V4(v4)) => {
+            out.push(UOT_FAMILY_IPV4);
+            out.extend_from_slice(&v4.octets());
+        }
+        Ok(IpAddr::V6(v6)) => {
+            out.push(UOT_FAMILY_IPV6);
+            out.extend_from_slice(&v6.octets());
+        }
+        Err(_) => {
+            let domain = addr.host.as_bytes();
+            if domain.len() > 255 {
+                return Err(AclError::OutboundError {
+                    kind: OutboundErrorKind::InvalidInput,
+                    message: format!(
+                        "Domain name too long for UDP-over-TCP: {} bytes (max 255)",
+                        domain.len()
+                    ),
+                });
+            }
+            out.push(UOT_FAMILY_DOMAIN);
+            out.push(domain.len() as u8);
+            out.extend_from_slice(domain);
+        }
+    }
+    out.extend_from_slice(&addr.port.to_be_bytes());
+    Ok(())
+}
+
+/// Build the v2 request header sent once after the stream is established.
+///
+/// The header carries `isConnect = 0` so every datagram names its own
+/// destination, matching the `UdpConn` interface.
+pub(super) fn encode_request(destination: &Addr) -> Result<Vec<u8>> {
+    let mut out = vec![0x00];
+    encode_addr(destination, &mut out)?;
+    Ok(out)
+}
+
+/// Frame one datagram: ADDR + PORT + LENGTH + PAYLOAD.
+pub(super) fn encode_packet(addr: &Addr, payload: &[u8]) -> Result<Vec<u8>> {
+    if payload.len() > u16::MAX as usize {
+        return Err(AclError::OutboundError {
+            kind: OutboundErrorKind::InvalidInput,
+            message: format!(
+                "Datagram too large for UDP-over-TCP: {} bytes (max 65535)",
+                payload.len()
+            ),
+        });
+    }
+    let mut out = Vec::with_capacity(payload.len() + 24);
+    encode_addr(addr, &mut out)?;
+    out.extend_from_slice(&(payload.len() as u16).to_be_bytes());
+    out.extend_from_slice(payload);
+    Ok(out)
+}
+
+/// Number of address bytes following a family byte, or `None` for domains
+/// (whose length byte must be read first).
+fn fixed_addr_len(family: u8) -> io::Result<Option<usize>> {
+    match family {
+        UOT_FAMILY_IPV4 => Ok(Some(4)),
+        UOT_FAMILY_IPV6 => Ok(Some(16)),
+        UOT_FAMILY_DOMAIN => Ok(None),
+        other => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Unknown UDP-over-TCP address family: {}", other),
+        )),
+    }
+}
+
+/// Parse the address bytes (without family/length prefix) and trailing port.
+fn decode_addr(family: u8, data: &[u8]) -> Addr {
+    let (host, port) = data.split_at(data.len() - 2);
+    let host = match family {
+        UOT_FAMILY_IPV4 => Ipv4Addr::new(host[0], host[1], host[2], host[3]).to_string(),
+        UOT_FAMILY_IPV6 => {
+            let mut octets = [0u8; 16];
+            octets.copy_from_slice(host);
+            Ipv6Addr::from(octets).to_string()
+        }
+        _ => String::from_utf8_lossy(host).to_string(),
+    };
+    Addr::new(host, u16::from_be_bytes([port[0], port[1]]))
+}
+
+/// Read one framed datagram, copying as much payload as fits into `buf`.
+pub(super) fn read_packet<R: Read>(reader: &mut R, buf: &mut [u8]) -> io::Result<(usize, Addr)> {
+    let mut family = [0u8; 1];
+    reader.read_exact(&mut family)?;
+    let addr_len = match fixed_addr_len(family[0])? {
+        Some(len) => len,
+        None => {
+            let mut len = [0u8; 1];
+            reader.read_exact(&mut len)?;
+            len[0] as usize
+        }
+    };
+
+    // ADDR + PORT + LENGTH
+    let mut header = vec![0u8; addr_len + 4];
+    reader.read_exact(&mut header)?;
+    let len = u16::from_be_bytes([header[addr_len + 2], header[addr_len + 3]]) as usize;
+    let addr = decode_addr(family[0], &header[..addr_len + 2]);
+
+    let mut payload = vec![0u8; len];
+    reader.read_exact(&mut payload)?;
+    let copy_len = len.min(buf.len());
+    buf[..copy_len].copy_from_slice(&payload[..copy_len]);
+    Ok((copy_len, addr))
+}
+
+/// Async: read one framed datagram, copying as much payload as fits into `buf`.
+#[cfg(feature = "async")]
+async fn async_read_packet<R: tokio::io::AsyncRead + Unpin>(
+    reader: &mut R,
+    buf: &mut [u8],
+) -> io::Result<(usize, Addr)> {
+    let family = reader.read_u8().await?;
+    let addr_len = match fixed_addr_len(family)? {
+        Some(len) => len,
+        None => reader.read_u8().await? as usize,
+    };
+
+    let mut header = vec![0u8; addr_len + 4];
+    reader.read_exact(&mut header).await?;
+    let len = u16::from_be_bytes([header[addr_len + 2], header[addr_len + 3]]) as usize;
+    let addr = decode_addr(family, &header[..addr_len + 2]);
+
+    let mut payload = vec![0u8; len];
+    reader.read_exact(&mut payload).await?;
+    let copy_len = len.min(buf.len());
+    buf[..copy_len].copy_from_slice(&payload[..copy_len]);
+    Ok((copy_len, addr))
+}
+
+fn recv_error(e: io::Error) -> AclError {
+    AclError::OutboundError {
+        kind: OutboundErrorKind::Io,
+        message: format!("UDP-over-TCP recv error: {}", e),
+    }
+}
+
+fn send_error(e: io::Error) -> AclError {
+    AclError::OutboundError {
+        kind: OutboundErrorKind::Io,
+        message: format!("UDP-over-TCP send error: {}", e),
+    }
+}
+
+/// Clone `stream` into the write handle of a UoT session.
+///
+/// Reads and writes must not share one blocking handle, or a thread waiting
+/// in `read_from` would hold off every `write_to`. Streams that cannot be
+/// cloned (TLS, Shadowsocks, HTTP CONNECT with buffered data) are rejected
+/// with `ConfigError`.
+pub(super) fn split_writer(stream: &dyn TcpConn) -> Result<Box<dyn TcpConn>> {
+    stream.try_clone().map_err(|e| {
+        AclError::ConfigError(format!(
+            "UDP-over-TCP needs a proxy connection that can be split: {}",
+            e
+        ))
+    })
+}
+
+/// UDP connection carried over an established UoT stream.
+pub(super) struct UotUdpConn {
+    reader: Mutex<Box<dyn TcpConn>>,
+    writer: Mutex<Box<dyn TcpConn>>,
+}
+
+impl UotUdpConn {
+    /// Wrap a stream whose UoT request header has already been sent, and a
+    /// write handle from [`split_writer`].
+    pub(super) fn new(reader: Box<dyn TcpConn>, writer: Box<dyn TcpConn>) -> Self {
+        Self {
+            reader: Mutex::new(reader),
+            writer: Mutex::new(writer),
+        }
+    }
+}
+
+impl UdpConn for UotUdpConn {
+    fn read_from(&self, buf: &mut [u8]) -> Result<(usize, Addr)> {
+        let mut reader = self.reader.lock();
+        read_packet(&mut *reader, buf).map_err(recv_error)
+    }
+
+    fn write_to(&self, buf: &[u8], addr: &Addr) -> Result<usize> {
+        let packet = encode_packet(addr, buf)?;
+        let mut writer = self.writer.lock();
+        writer.write_all(&packet).map_err(send_error)?;
+        Ok(buf.len())
+    }
+}
+
+/// Async UDP connection carried over an established UoT stream.
+#[cfg(feature = "async")]
+pub(super) struct AsyncUotUdpConn {
+    reader: tokio::sync::Mutex<ReadHalf<Box<dyn AsyncTcpConn>>>,
+    writer: tokio::sync::Mutex<WriteHalf<Box<dyn AsyncTcpConn>>>,
+}
+
+#[cfg(feature = "async")]
+impl AsyncUotUdpConn {
+    /// Wrap a stream whose UoT request header has already been sent.
+    pub(super) fn new(stream: Box<dyn AsyncTcpConn>) -> Self {
+        let (reader, writer) = tokio::io::split(stream);
+        Self {
+            reader: tokio::sync::Mutex::new(reader),
+            writer: tokio::sync::Mutex::new(writer),
+        }
+    }
+}
+
+#[cfg(feature = "async")]
+#[async_trait]
+impl AsyncUdpConn for AsyncUotUdpConn {
+    async fn read_from(&self, buf: &mut [u8]) -> Result<(usize, Addr)> {
+        let mut reader = self.reader.lock().await;
+        async_read_packet(&mut *reader, buf)
+            .await
+            .map_err(recv_error)
+    }
+
+    async fn write_to(&self, buf: &[u8], addr: &Addr) -> Result<usize> {
+        let packet = encode_packet(addr, buf)?;
+        let mut writer = self.writer.lock().await;
+        writer.write_all(&packet).await.map_err(send_error)?;
+        Ok(buf.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_uot_encode_request() {
+        let req = encode_request(&Addr::new("1.2.3.4", 53)).unwrap();
+        assert_eq!(req, vec![0x00, UOT_FAMILY_IPV4, 1, 2, 3, 4, 0x00, 0x35]);
+    }
+
+    #[test]
+    fn test_uot_packet_round_trip() {
+        for host in ["192.0.2.1", "2001:db8::1", "dns.example"] {
+            let packet = encode_packet(&Addr::new(host, 5353), b"hello").unwrap();
+            let mut buf = [0u8; 16];
+            let (n, addr) = read_packet(&mut &packet[..], &mut buf).unwrap();
+            assert_eq!(&buf[..n], b"hello");
+            assert_eq!(addr.host, host);
+            assert_eq!(addr.port, 5353);
+        }
+    }
+
+    #[test]
+    fn test_uot_packet_domain_layout() {
+        let packet = encode_packet(&Addr::new("ab", 80), b"x").unwrap();
+        assert_eq!(
+            packet,
+            vec![
+                UOT_FAMILY_DOMAIN,
+                2,
+                b'a',
+                b'b',
+                0x00,
+                0x50,
+                0x00,
+                0x01,
+                b'x'
+            ]
+        );
+    }
+
+    #[test]
+    fn test_uot_read_truncates_to_buffer() {
+        let mut stream = encode_packet(&Addr::new("10.0.0.1", 1), b"abcdef").unwrap();
+        stream.extend(encode_packet(&Addr::new("10.0.0.2", 2), b"gh").unwrap());
+        let mut reader = &stream[..];
+        let mut buf = [0u8; 3];
+        let (n, _) = read_packet(&mut reader, &mut buf).unwrap();
+        assert_eq!(&buf[..n], b"abc");
+        // The rest of the oversized payload is discarded, framing stays aligned
+        let (n, addr) = read_packet(&mut reader, &mut buf).unwrap();
+        assert_eq!(&buf[..n], b"gh");
+        assert_eq!(addr.host, "10.0.0.2");
+    }
+
+    #[test]
+    fn test_uot_read_unknown_family() {
+        let data = [0x07u8, 0, 0];
+        let err = read_packet(&mut &data[..], &mut [0u8; 4]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_uot_oversized_payload_rejected() {
+        let payload = vec![0u8; 65536];
+        assert!(matches!(
+            encode_packet(&Addr::new("1.1.1.1", 53), &payload),
+            Err(AclError::OutboundError {
+                kind: OutboundErrorKind::InvalidInput,
+                ..
+            })
+        ));
+    }
+}
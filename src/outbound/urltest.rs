@@ -0,0 +1,429 @@
+//! Latency-based (url-test style) outbound group.
+//!
+//! [`UrlTestGroup`] periodically measures the connect latency through every
+//! member to a [`HealthCheck`] target and sends new dials to the fastest
+//! one. A tolerance band keeps the current member unless another one is
+//! faster by more than the tolerance, which avoids flapping between members
+//! with near-identical latency.
+
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use parking_lot::Mutex;
+
+use crate::error::Result;
+use crate::router::OutboundEntry;
+
+use super::group::validate_members;
+use super::health::{self, HealthCheck};
+use super::{Addr, Outbound, TcpConn, UdpConn};
+
+#[cfg(feature = "async")]
+use super::{AsyncOutbound, AsyncTcpConn, AsyncUdpConn};
+#[cfg(feature = "async")]
+use async_trait::async_trait;
+
+/// Default tolerance before switching to a faster member
+pub const DEFAULT_LATENCY_TOLERANCE: Duration = Duration::from_millis(50);
+
+/// Outbound that dials through the member with the lowest measured latency.
+///
+/// Generic over the outbound trait like [`OutboundEntry`]; use
+/// `UrlTestGroup` for sync members and `AsyncUrlTestGroup` for async ones.
+/// Until the first measurement the first member is used.
+pub struct UrlTestGroup<T: ?Sized = dyn Outbound> {
+    members: Vec<OutboundEntry<T>>,
+    /// Last measured latency per member; `None` if untested or failed
+    latencies: Vec<Mutex<Option<Duration>>>,
+    /// Index of the member new dials go to
+    selected: AtomicUsize,
+    health_check: HealthCheck,
+    tolerance: Duration,
+}
+
+/// Async latency-based group (type alias for `UrlTestGroup<dyn AsyncOutbound>`).
+#[cfg(feature = "async")]
+pub type AsyncUrlTestGroup = UrlTestGroup<dyn AsyncOutbound>;
+
+impl<T: ?Sized> fmt::Debug for UrlTestGroup<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("UrlTestGroup")
+            .field("latencies", &self.latencies())
+            .field("selected", &self.selected())
+            .field("health_check", &self.health_check)
+            .field("tolerance", &self.tolerance)
+            .finish()
+    }
+}
+
+impl<T: ?Sized> UrlTestGroup<T> {
+    /// Create a new latency-based group probing `health_check`'s target.
+    ///
+    /// Returns `ConfigError` if `members` is empty or two members share a
+    /// name (compared case-insensitively, as in ACL rules).
+    pub fn new(members: Vec<OutboundEntry<T>>, health_check: HealthCheck) -> Result<Self> {
+        validate_members(&members, "URL test group")?;
+
+        let latencies = members.iter().map(|_| Mutex::new(None)).collect();
+        Ok(Self {
+            members,
+            latencies,
+            selected: AtomicUsize::new(0),
+            health_check,
+            tolerance: DEFAULT_LATENCY_TOLERANCE,
+        })
+    }
+
+    /// Set how much faster another member must be before it replaces the
+    /// current one.
+    pub fn with_tolerance(mut self, tolerance: Duration) -> Self {
+        self.tolerance = tolerance;
+        self
+    }
+
+    /// Get the group members.
+    pub fn members(&self) -> &[OutboundEntry<T>] {
+        &self.members
+    }
+
+    /// Get the health check settings.
+    pub fn health_check(&self) -> &HealthCheck {
+        &self.health_check
+    }
+
+    /// Name of the member new dials go to.
+    pub fn selected(&self) -> &str {
+        &self.members[self.selected.load(Ordering::Relaxed)].name
+    }
+
+    /// Last measured latency per member, in member order.
+    ///
+    /// `None` means the member has not been measured yet or its last probe
+    /// failed.
+    pub fn latencies(&self) -> Vec<(&str, Option<Duration>)> {
+        self.members
+            .iter()
+            .zip(&self.latencies)
+            .map(|(m, l)| (m.name.as_str(), *l.lock()))
+            .collect()
+    }
+
+    /// Store a measurement round and re-select the member.
+    fn update(&self, results: Vec<Option<Duration>>) {
+        for (slot, latency) in self.latencies.iter().zip(&results) {
+            *slot.lock() = *latency;
+        }
+
+        let Some((fastest, best)) = results
+            .iter()
+            .enumerate()
+            .filter_map(|(i, l)| l.map(|l| (i, l)))
+            .min_by_key(|&(_, l)| l)
+        else {
+            // Nothing answered; keep the current member
+            return;
+        };
+        let current = self.selected.load(Ordering::Relaxed);
+        let keep = results[current].is_some_and(|l| l <= best + self.tolerance);
+        if !keep {
+            self.selected.store(fastest, Ordering::Relaxed);
+        }
+    }
+
+    fn current(&self) -> &Arc<T> {
+        &self.members[self.selected.load(Ordering::Relaxed)].outbound
+    }
+}
+
+impl UrlTestGroup<dyn Outbound> {
+    /// Measure every member once and re-select the fastest.
+    pub fn test_latency(&self) {
+        let results = self
+            .members
+            .iter()
+            .map(|m| self.health_check.probe(m.outbound.as_ref()).ok())
+            .collect();
+        self.update(results);
+    }
+
+    /// Start measuring latency from a background thread, now and then every
+    /// health check interval.
+    ///
+    /// The thread holds only a weak reference and exits once the group is
+    /// dropped.
+    pub fn spawn_latency_test(self: &Arc<Self>) -> std::thread::JoinHandle<()> {
+        health::spawn_thread(self, self.health_check.interval(), true, Self::test_latency)
+    }
+}
+
+impl Outbound for UrlTestGroup<dyn Outbound> {
+    fn dial_tcp(&self, addr: &mut Addr) -> Result<Box<dyn TcpConn>> {
+        self.current().dial_tcp(addr)
+    }
+
+    fn dial_udp(&self, addr: &mut Addr) -> Result<Box<dyn UdpConn>> {
+        self.current().dial_udp(addr)
+    }
+}
+
+#[cfg(feature = "async")]
+impl UrlTestGroup<dyn AsyncOutbound> {
+    /// Async: Measure every member once, concurrently, and re-select the
+    /// fastest.
+    pub async fn test_latency(&self) {
+        let mut probes = tokio::task::JoinSet::new();
+        for (index, member) in self.members.iter().enumerate() {
+            let outbound = Arc::clone(&member.outbound);
+            let health_check = self.health_check.clone();
+            probes.spawn(async move {
+                let latency = health_check.async_probe(outbound.as_ref()).await;
+                (index, latency.ok())
+            });
+        }
+
+        let mut results = vec![None; self.members.len()];
+        while let Some(probe) = probes.join_next().await {
+            if let Ok((index, latency)) = probe {
+                results[index] = latency;
+            }
+        }
+        self.update(results);
+    }
+
+    /// Start measuring latency from a tokio task, now and then every health
+    /// check interval. Must be called within a tokio runtime.
+    ///
+    /// The task holds only a weak reference and exits once the group is
+    /// dropped.
+    pub fn spawn_latency_test(self: &Arc<Self>) -> tokio::task::JoinHandle<()> {
+        health::spawn_task(
+            self,
+            self.health_check.interval(),
+            true,
+            |group| async move { group.test_latency().await },
+        )
+    }
+}
+
+#[cfg(feature = "async")]
+#[async_trait]
+impl AsyncOutbound for UrlTestGroup<dyn AsyncOutbound> {
+    async fn dial_tcp(&self, addr: &mut Addr) -> Result<Box<dyn AsyncTcpConn>> {
+        self.current().dial_tcp(addr).await
+    }
+
+    async fn dial_udp(&self, addr: &mut Addr) -> Result<Box<dyn AsyncUdpConn>> {
+        self.current().dial_udp(addr).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::AclError;
+    use crate::outbound::{Reject, StdTcpConn};
+    use std::net::{TcpListener, TcpStream};
+
+    /// Member that connects to a local listener after `delay`.
+    struct Delayed {
+        listener: TcpListener,
+        delay: Mutex<Option<Duration>>,
+    }
+
+    impl Outbound for Delayed {
+        fn dial_tcp(&self, _addr: &mut Addr) -> Result<Box<dyn TcpConn>> {
+            let delay = *self.delay.lock();
+            let delay = delay.ok_or_else(|| AclError::OutboundError {
+                kind: crate::error::OutboundErrorKind::ConnectionFailed,
+                message: "down".to_string(),
+            })?;
+            std::thread::sleep(delay);
+            let stream = TcpStream::connect(self.listener.local_addr().unwrap())?;
+            Ok(Box::new(StdTcpConn::new(stream)))
+        }
+
+        fn dial_udp(&self, addr: &mut Addr) -> Result<Box<dyn UdpConn>> {
+            Outbound::dial_udp(&Reject::new(), addr)
+        }
+    }
+
+    fn delayed(ms: Option<u64>) -> Arc<Delayed> {
+        Arc::new(Delayed {
+            listener: TcpListener::bind("127.0.0.1:0").unwrap(),
+            delay: Mutex::new(ms.map(Duration::from_millis)),
+        })
+    }
+
+    fn group(members: &[Arc<Delayed>], tolerance_ms: u64) -> UrlTestGroup {
+        let entries = members
+            .iter()
+            .enumerate()
+            .map(|(i, m)| OutboundEntry::new(format!("exit-{}", i), m.clone() as Arc<dyn Outbound>))
+            .collect();
+        let check = HealthCheck::new(Addr::new("probe.example", 80))
+            .with_interval(Duration::from_millis(20));
+        UrlTestGroup::new(entries, check)
+            .unwrap()
+            .with_tolerance(Duration::from_millis(tolerance_ms))
+    }
+
+    #[test]
+    fn test_urltest_rejects_duplicate_members() {
+        let reject: Arc<dyn Outbound> = Arc::new(Reject::new());
+        let entries = vec![
+            OutboundEntry::new("a", reject.clone()),
+            OutboundEntry::new("A", reject),
+        ];
+        let check = HealthCheck::new(Addr::new("probe.example", 80));
+        assert!(matches!(
+            UrlTestGroup::new(entries, check),
+            Err(AclError::ConfigError(_))
+        ));
+    }
+
+    #[test]
+    fn test_urltest_selects_fastest() {
+        let members = [delayed(Some(120)), delayed(Some(0)), delayed(None)];
+        let group = group(&members, 10);
+        assert_eq!(group.selected(), "exit-0");
+
+        group.test_latency();
+        assert_eq!(group.selected(), "exit-1");
+        let latencies = group.latencies();
+        assert!(latencies[0].1.unwrap() >= Duration::from_millis(120));
+        assert!(latencies[1].1.unwrap() < latencies[0].1.unwrap());
+        assert_eq!(latencies[2], ("exit-2", None));
+    }
+
+    #[test]
+    fn test_urltest_tolerance_avoids_flapping() {
+        let group = group(&[delayed(None), delayed(None), delayed(None)], 50);
+        let ms = |v: u64| Some(Duration::from_millis(v));
+
+        group.update(vec![ms(30), ms(20), None]);
+        // exit-0 is within the band of the fastest, so it stays
+        assert_eq!(group.selected(), "exit-0");
+        group.update(vec![ms(100), ms(20), None]);
+        assert_eq!(group.selected(), "exit-1");
+        group.update(vec![ms(10), ms(40), None]);
+        assert_eq!(group.selected(), "exit-1");
+        // The current member failing moves traffic even inside the band
+        group.update(vec![ms(10), None, ms(12)]);
+        assert_eq!(group.selected(), "exit-0");
+        // Nothing answering keeps the current member
+        group.update(vec![None, None, None]);
+        assert_eq!(group.selected(), "exit-0");
+    }
+
+    #[test]
+    fn test_urltest_background_thread() {
+        let members = [delayed(Some(100)), delayed(Some(0))];
+        let group = Arc::new(group(&members, 10));
+        let handle = group.spawn_latency_test();
+
+        let deadline = std::time::Instant::now() + Duration::from_secs(5);
+        while group.selected() != "exit-1" {
+            assert!(std::time::Instant::now() < deadline, "fastest not selected");
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        let conn = Outbound::dial_tcp(group.as_ref(), &mut Addr::new("example.com", 80));
+        assert!(conn.is_ok());
+
+        // Slow down exit-1 beyond the tolerance; the next round switches back
+        *members[1].delay.lock() = Some(Duration::from_millis(250));
+        *members[0].delay.lock() = Some(Duration::from_millis(0));
+        while group.selected() != "exit-0" {
+            assert!(std::time::Instant::now() < deadline, "member not switched");
+            std::thread::sleep(Duration::from_millis(10));
+        }
+
+        drop(group);
+        handle.join().unwrap();
+    }
+}
+
+#[cfg(all(test, feature = "async"))]
+mod async_tests {
+    use super::*;
+    use crate::outbound::TokioTcpConn;
+    use std::net::SocketAddr;
+    use tokio::net::{TcpListener, TcpStream};
+
+    struct Delayed {
+        addr: SocketAddr,
+        _listener: TcpListener,
+        delay: Duration,
+    }
+
+    #[async_trait]
+    impl AsyncOutbound for Delayed {
+        async fn dial_tcp(&self, _addr: &mut Addr) -> Result<Box<dyn AsyncTcpConn>> {
+            tokio::time::sleep(self.delay).await;
+            let stream = TcpStream::connect(self.addr).await?;
+            Ok(Box::new(TokioTcpConn::new(stream)))
+        }
+
+        async fn dial_udp(&self, addr: &mut Addr) -> Result<Box<dyn AsyncUdpConn>> {
+            AsyncOutbound::dial_udp(&crate::outbound::Reject::new(), addr).await
+        }
+    }
+
+    async fn delayed(ms: u64) -> Arc<dyn AsyncOutbound> {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        Arc::new(Delayed {
+            addr: listener.local_addr().unwrap(),
+            _listener: listener,
+            delay: Duration::from_millis(ms),
+        })
+    }
+
+    #[tokio::test]
+    async fn test_async_urltest_task() {
+        let entries = vec![
+            OutboundEntry::new("slow", delayed(150).await),
+            OutboundEntry::new("hung", delayed(10_000).await),
+            OutboundEntry::new("fast", delayed(0).await),
+        ];
+        let check = HealthCheck::new(Addr::new("probe.example", 80))
+            .with_interval(Duration::from_millis(20))
+            .with_timeout(Duration::from_millis(500));
+        let group = Arc::new(AsyncUrlTestGroup::new(entries, check).unwrap());
+        let handle = group.spawn_latency_test();
+
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while group.selected() != "fast" {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("fastest not selected");
+        // The hung member is cut off by the probe timeout
+        assert_eq!(group.latencies()[1], ("hung", None));
+
+        let mut addr = Addr::new("example.com", 443);
+        assert!(AsyncOutbound::dial_tcp(group.as_ref(), &mut addr)
+            .await
+            .is_ok());
+
+        drop(group);
+        handle.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_async_urltest_probes_concurrently() {
+        let entries = vec![
+            OutboundEntry::new("a", delayed(300).await),
+            OutboundEntry::new("b", delayed(300).await),
+            OutboundEntry::new("c", delayed(300).await),
+        ];
+        let check = HealthCheck::new(Addr::new("probe.example", 80));
+        let group = AsyncUrlTestGroup::new(entries, check).unwrap();
+
+        let start = std::time::Instant::now();
+        group.test_latency().await;
+        assert!(start.elapsed() < Duration::from_millis(600));
+        assert!(group.latencies().iter().all(|(_, l)| l.is_some()));
+    }
+}
@@ -1,10 +1,11 @@
 use once_cell::sync::Lazy;
 use regex::Regex;
 use std::fs;
+use std::net::{IpAddr, Ipv6Addr};
 use std::path::Path;
 
 use crate::error::{AclError, Result};
-use crate::types::{Protocol, TextRule};
+use crate::types::{HijackHost, HijackTarget, Protocol, TextRule};
 
 /// Regex pattern for parsing ACL rules
 /// Format: outbound(address[, protoPort][, hijackAddress])
@@ -160,6 +161,78 @@ pub fn parse_proto_port(spec: &str) -> Result<(Protocol, u16, u16)> {
     Ok((protocol, start_port, end_port))
 }
 
+/// Parse a hijack address specification.
+/// Examples: "127.0.0.1", "127.0.0.1:5353", "[::1]:5353", "::1", ":5353",
+/// "mirror.local", "mirror.local:8080"
+pub fn parse_hijack_address(spec: &str) -> Result<HijackTarget> {
+    let spec = spec.trim();
+    let invalid = || AclError::InvalidAddress(format!("Invalid hijack address: {}", spec));
+
+    // Bare IP (including unbracketed IPv6 such as "::1")
+    if let Ok(ip) = spec.parse::<IpAddr>() {
+        return Ok(HijackTarget {
+            host: Some(HijackHost::Ip(ip)),
+            port: None,
+        });
+    }
+
+    // Port only
+    if let Some(port_str) = spec.strip_prefix(':') {
+        return Ok(HijackTarget {
+            host: None,
+            port: Some(parse_hijack_port(port_str).ok_or_else(invalid)?),
+        });
+    }
+
+    // Bracketed IPv6 with optional port
+    if let Some(rest) = spec.strip_prefix('[') {
+        let (ip_str, rest) = rest.split_once(']').ok_or_else(invalid)?;
+        let ip: Ipv6Addr = ip_str.parse().map_err(|_| invalid())?;
+        let port = if rest.is_empty() {
+            None
+        } else {
+            let port_str = rest.strip_prefix(':').ok_or_else(invalid)?;
+            Some(parse_hijack_port(port_str).ok_or_else(invalid)?)
+        };
+        return Ok(HijackTarget {
+            host: Some(HijackHost::Ip(IpAddr::V6(ip))),
+            port,
+        });
+    }
+
+    let (host, port) = match spec.split_once(':') {
+        Some((host, port_str)) => (host, Some(parse_hijack_port(port_str).ok_or_else(invalid)?)),
+        None => (spec, None),
+    };
+
+    let host = if let Ok(ip) = host.parse::<IpAddr>() {
+        HijackHost::Ip(ip)
+    } else if is_valid_hostname(host) {
+        HijackHost::Name(host.to_lowercase())
+    } else {
+        return Err(invalid());
+    };
+
+    Ok(HijackTarget {
+        host: Some(host),
+        port,
+    })
+}
+
+/// Parse a non-zero port number.
+fn parse_hijack_port(s: &str) -> Option<u16> {
+    s.parse::<u16>().ok().filter(|&p| p != 0)
+}
+
+/// Check that a hostname only contains letters, digits, '-', '_' and '.'.
+fn is_valid_hostname(host: &str) -> bool {
+    !host.is_empty()
+        && host.len() <= 253
+        && host
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'-' | b'_' | b'.'))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -202,6 +275,7 @@ mod tests {
         assert_eq!(rules[0].address, "all");
         assert_eq!(rules[0].proto_port, Some("udp/53".to_string()));
         assert_eq!(rules[0].hijack_address, Some("127.0.0.1".to_string()));
+        assert_eq!(rules[0].to_string(), text);
     }
 
     #[test]
@@ -452,4 +526,64 @@ proxy(all)
         assert_eq!(rules[0].outbound, "us.west");
         assert_eq!(rules[0].address, "10.0.0.0/8");
     }
+
+    #[test]
+    fn test_parse_hijack_address_forms() {
+        let t = parse_hijack_address("127.0.0.1").unwrap();
+        assert_eq!(t.host, Some(HijackHost::Ip("127.0.0.1".parse().unwrap())));
+        assert_eq!(t.port, None);
+
+        let t = parse_hijack_address("127.0.0.1:5353").unwrap();
+        assert_eq!(t.ip(), Some("127.0.0.1".parse().unwrap()));
+        assert_eq!(t.port, Some(5353));
+
+        let t = parse_hijack_address("::1").unwrap();
+        assert_eq!(t.ip(), Some("::1".parse().unwrap()));
+        assert_eq!(t.port, None);
+
+        let t = parse_hijack_address("[2001:db8::1]:53").unwrap();
+        assert_eq!(t.ip(), Some("2001:db8::1".parse().unwrap()));
+        assert_eq!(t.port, Some(53));
+
+        let t = parse_hijack_address("[::1]").unwrap();
+        assert_eq!(t.ip(), Some("::1".parse().unwrap()));
+        assert_eq!(t.port, None);
+
+        let t = parse_hijack_address(":5353").unwrap();
+        assert_eq!(t.host, None);
+        assert_eq!(t.port, Some(5353));
+
+        let t = parse_hijack_address("Mirror.Local").unwrap();
+        assert_eq!(t.host, Some(HijackHost::Name("mirror.local".to_string())));
+        assert_eq!(t.port, None);
+        assert_eq!(t.ip(), None);
+
+        let t = parse_hijack_address("mirror.local:8080").unwrap();
+        assert_eq!(t.host, Some(HijackHost::Name("mirror.local".to_string())));
+        assert_eq!(t.port, Some(8080));
+    }
+
+    #[test]
+    fn test_parse_hijack_address_invalid() {
+        for spec in [
+            "",
+            ":",
+            ":0",
+            ":70000",
+            "1.2.3.4:",
+            "1.2.3.4:abc",
+            "[::1",
+            "[1.2.3.4]:53",
+            "[::1]53",
+            "bad host",
+            "host:1:2",
+            "ho/st",
+        ] {
+            assert!(
+                parse_hijack_address(spec).is_err(),
+                "{:?} should be rejected",
+                spec
+            );
+        }
+    }
 }
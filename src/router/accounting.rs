@@ -0,0 +1,431 @@
+//! Traffic accounting for routed connections.
+//!
+//! When a [`RouterOptions`](super::RouterOptions) carries an [`Accounting`],
+//! the router wraps every connection it returns and tallies bytes,
+//! connection counts, durations and errors by outbound name and by the ACL
+//! rule that selected the outbound.
+
+use std::collections::HashMap;
+use std::io::{self, Read, Write};
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use parking_lot::Mutex;
+
+use crate::error::Result;
+use crate::outbound::{Addr, TcpConn, UdpConn};
+
+#[cfg(feature = "async")]
+use crate::outbound::{AsyncTcpConn, AsyncUdpConn};
+#[cfg(feature = "async")]
+use async_trait::async_trait;
+#[cfg(feature = "async")]
+use std::pin::Pin;
+#[cfg(feature = "async")]
+use std::task::{Context, Poll};
+#[cfg(feature = "async")]
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+/// Traffic counters for one outbound or rule.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TrafficStats {
+    /// Connections successfully dialed
+    pub connections: u64,
+    /// Connections currently open (not cleared by [`Accounting::reset`])
+    pub active: u64,
+    /// Failed dials plus I/O errors on open connections (timeouts excluded)
+    pub errors: u64,
+    /// Bytes written by the client
+    pub bytes_sent: u64,
+    /// Bytes read by the client
+    pub bytes_received: u64,
+    /// Total lifetime of closed connections
+    pub duration: Duration,
+}
+
+/// Point-in-time copy of all counters in an [`Accounting`].
+#[derive(Debug, Clone, Default)]
+pub struct AccountingSnapshot {
+    /// Counters keyed by lowercase outbound name
+    pub outbounds: HashMap<String, TrafficStats>,
+    /// Counters keyed by rule text, e.g. `proxy(*.google.com, tcp/443)`.
+    ///
+    /// Connections that matched no rule only count toward their outbound.
+    pub rules: HashMap<String, TrafficStats>,
+}
+
+/// Shared traffic accounting for one or more routers.
+///
+/// Counters are keyed by name rather than tied to a router, so totals carry
+/// over when a router is rebuilt with reloaded rules and the same
+/// `Accounting`.
+#[derive(Debug, Default)]
+pub struct Accounting {
+    outbounds: Mutex<HashMap<String, Arc<Counters>>>,
+    rules: Mutex<HashMap<String, Arc<Counters>>>,
+}
+
+impl Accounting {
+    /// Create an empty accounting.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Copy the current counters.
+    pub fn snapshot(&self) -> AccountingSnapshot {
+        let collect = |map: &Mutex<HashMap<String, Arc<Counters>>>| {
+            map.lock()
+                .iter()
+                .map(|(key, counters)| (key.clone(), counters.load()))
+                .collect()
+        };
+        AccountingSnapshot {
+            outbounds: collect(&self.outbounds),
+            rules: collect(&self.rules),
+        }
+    }
+
+    /// Zero all counters except the number of open connections.
+    pub fn reset(&self) {
+        for counters in self.outbounds.lock().values() {
+            counters.reset();
+        }
+        for counters in self.rules.lock().values() {
+            counters.reset();
+        }
+    }
+
+    /// Counters for connections routed to outbound `name` by `rule`.
+    pub(crate) fn route(&self, name: &str, rule: Option<String>) -> Route {
+        let outbound = self
+            .outbounds
+            .lock()
+            .entry(name.to_lowercase())
+            .or_default()
+            .clone();
+        let rule = rule.map(|rule| self.rules.lock().entry(rule).or_default().clone());
+        Route { outbound, rule }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Counters {
+    connections: AtomicU64,
+    active: AtomicU64,
+    errors: AtomicU64,
+    bytes_sent: AtomicU64,
+    bytes_received: AtomicU64,
+    duration_nanos: AtomicU64,
+}
+
+impl Counters {
+    fn load(&self) -> TrafficStats {
+        TrafficStats {
+            connections: self.connections.load(Ordering::Relaxed),
+            active: self.active.load(Ordering::Relaxed),
+            errors: self.errors.load(Ordering::Relaxed),
+            bytes_sent: self.bytes_sent.load(Ordering::Relaxed),
+            bytes_received: self.bytes_received.load(Ordering::Relaxed),
+            duration: Duration::from_nanos(self.duration_nanos.load(Ordering::Relaxed)),
+        }
+    }
+
+    fn reset(&self) {
+        self.connections.store(0, Ordering::Relaxed);
+        self.errors.store(0, Ordering::Relaxed);
+        self.bytes_sent.store(0, Ordering::Relaxed);
+        self.bytes_received.store(0, Ordering::Relaxed);
+        self.duration_nanos.store(0, Ordering::Relaxed);
+    }
+}
+
+/// Counters a routed connection reports to: its outbound and, if a rule
+/// matched, that rule.
+#[derive(Debug, Clone)]
+pub(crate) struct Route {
+    outbound: Arc<Counters>,
+    rule: Option<Arc<Counters>>,
+}
+
+impl Route {
+    fn each(&self, f: impl Fn(&Counters)) {
+        f(&self.outbound);
+        if let Some(ref rule) = self.rule {
+            f(rule);
+        }
+    }
+
+    /// Record the outcome of a dial and wrap the connection on success.
+    pub(crate) fn track_tcp(&self, dialed: Result<Box<dyn TcpConn>>) -> Result<Box<dyn TcpConn>> {
+        let inner = self.dialed(dialed)?;
+        Ok(Box::new(TrackedTcpConn {
+            inner,
+            meter: self.open(),
+        }))
+    }
+
+    pub(crate) fn track_udp(&self, dialed: Result<Box<dyn UdpConn>>) -> Result<Box<dyn UdpConn>> {
+        let inner = self.dialed(dialed)?;
+        Ok(Box::new(TrackedUdpConn {
+            inner,
+            meter: self.open(),
+        }))
+    }
+
+    #[cfg(feature = "async")]
+    pub(crate) fn track_async_tcp(
+        &self,
+        dialed: Result<Box<dyn AsyncTcpConn>>,
+    ) -> Result<Box<dyn AsyncTcpConn>> {
+        let inner = self.dialed(dialed)?;
+        Ok(Box::new(TrackedAsyncTcpConn {
+            inner,
+            meter: self.open(),
+        }))
+    }
+
+    #[cfg(feature = "async")]
+    pub(crate) fn track_async_udp(
+        &self,
+        dialed: Result<Box<dyn AsyncUdpConn>>,
+    ) -> Result<Box<dyn AsyncUdpConn>> {
+        let inner = self.dialed(dialed)?;
+        Ok(Box::new(TrackedAsyncUdpConn {
+            inner,
+            meter: self.open(),
+        }))
+    }
+
+    fn dialed<C>(&self, dialed: Result<C>) -> Result<C> {
+        if dialed.is_err() {
+            self.each(|c| {
+                c.errors.fetch_add(1, Ordering::Relaxed);
+            });
+        }
+        dialed
+    }
+
+    fn open(&self) -> Meter {
+        self.each(|c| {
+            c.connections.fetch_add(1, Ordering::Relaxed);
+            c.active.fetch_add(1, Ordering::Relaxed);
+        });
+        Meter(Arc::new(MeterInner {
+            route: self.clone(),
+            opened: Instant::now(),
+        }))
+    }
+}
+
+/// Per-connection handle; shared by `try_clone` copies of a connection so
+/// the connection closes once the last copy is dropped.
+#[derive(Clone)]
+struct Meter(Arc<MeterInner>);
+
+struct MeterInner {
+    route: Route,
+    opened: Instant,
+}
+
+impl Drop for MeterInner {
+    fn drop(&mut self) {
+        let nanos = self.opened.elapsed().as_nanos() as u64;
+        self.route.each(|c| {
+            c.active.fetch_sub(1, Ordering::Relaxed);
+            c.duration_nanos.fetch_add(nanos, Ordering::Relaxed);
+        });
+    }
+}
+
+impl Meter {
+    fn sent(&self, n: usize) {
+        self.0.route.each(|c| {
+            c.bytes_sent.fetch_add(n as u64, Ordering::Relaxed);
+        });
+    }
+
+    fn received(&self, n: usize) {
+        self.0.route.each(|c| {
+            c.bytes_received.fetch_add(n as u64, Ordering::Relaxed);
+        });
+    }
+
+    fn error(&self) {
+        self.0.route.each(|c| {
+            c.errors.fetch_add(1, Ordering::Relaxed);
+        });
+    }
+
+    /// Count a stream I/O result; read/write timeouts are not errors.
+    fn io<T>(&self, result: io::Result<T>) -> io::Result<T> {
+        if let Err(ref e) = result {
+            if !matches!(
+                e.kind(),
+                io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut | io::ErrorKind::Interrupted
+            ) {
+                self.error();
+            }
+        }
+        result
+    }
+
+    fn udp<T>(&self, result: Result<T>) -> Result<T> {
+        if result.is_err() {
+            self.error();
+        }
+        result
+    }
+}
+
+struct TrackedTcpConn {
+    inner: Box<dyn TcpConn>,
+    meter: Meter,
+}
+
+impl Read for TrackedTcpConn {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.meter.io(self.inner.read(buf))?;
+        self.meter.received(n);
+        Ok(n)
+    }
+}
+
+impl Write for TrackedTcpConn {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.meter.io(self.inner.write(buf))?;
+        self.meter.sent(n);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.meter.io(self.inner.flush())
+    }
+}
+
+impl TcpConn for TrackedTcpConn {
+    fn local_addr(&self) -> Result<SocketAddr> {
+        self.inner.local_addr()
+    }
+
+    fn peer_addr(&self) -> Result<SocketAddr> {
+        self.inner.peer_addr()
+    }
+
+    fn set_read_timeout(&self, dur: Option<Duration>) -> Result<()> {
+        self.inner.set_read_timeout(dur)
+    }
+
+    fn set_write_timeout(&self, dur: Option<Duration>) -> Result<()> {
+        self.inner.set_write_timeout(dur)
+    }
+
+    fn shutdown(&self, how: std::net::Shutdown) -> Result<()> {
+        self.inner.shutdown(how)
+    }
+
+    fn try_clone(&self) -> Result<Box<dyn TcpConn>> {
+        Ok(Box::new(TrackedTcpConn {
+            inner: self.inner.try_clone()?,
+            meter: self.meter.clone(),
+        }))
+    }
+}
+
+struct TrackedUdpConn {
+    inner: Box<dyn UdpConn>,
+    meter: Meter,
+}
+
+impl UdpConn for TrackedUdpConn {
+    fn read_from(&self, buf: &mut [u8]) -> Result<(usize, Addr)> {
+        let (n, addr) = self.meter.udp(self.inner.read_from(buf))?;
+        self.meter.received(n);
+        Ok((n, addr))
+    }
+
+    fn write_to(&self, buf: &[u8], addr: &Addr) -> Result<usize> {
+        let n = self.meter.udp(self.inner.write_to(buf, addr))?;
+        self.meter.sent(n);
+        Ok(n)
+    }
+}
+
+#[cfg(feature = "async")]
+struct TrackedAsyncTcpConn {
+    inner: Box<dyn AsyncTcpConn>,
+    meter: Meter,
+}
+
+#[cfg(feature = "async")]
+impl AsyncRead for TrackedAsyncTcpConn {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let before = buf.filled().len();
+        let result = std::task::ready!(Pin::new(&mut this.inner).poll_read(cx, buf));
+        this.meter.io(result)?;
+        this.meter.received(buf.filled().len() - before);
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(feature = "async")]
+impl AsyncWrite for TrackedAsyncTcpConn {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        let result = std::task::ready!(Pin::new(&mut this.inner).poll_write(cx, buf));
+        let n = this.meter.io(result)?;
+        this.meter.sent(n);
+        Poll::Ready(Ok(n))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_shutdown(cx)
+    }
+}
+
+#[cfg(feature = "async")]
+impl AsyncTcpConn for TrackedAsyncTcpConn {
+    fn local_addr(&self) -> Result<SocketAddr> {
+        self.inner.local_addr()
+    }
+
+    fn peer_addr(&self) -> Result<SocketAddr> {
+        self.inner.peer_addr()
+    }
+}
+
+#[cfg(feature = "async")]
+struct TrackedAsyncUdpConn {
+    inner: Box<dyn AsyncUdpConn>,
+    meter: Meter,
+}
+
+#[cfg(feature = "async")]
+#[async_trait]
+impl AsyncUdpConn for TrackedAsyncUdpConn {
+    async fn read_from(&self, buf: &mut [u8]) -> Result<(usize, Addr)> {
+        let (n, addr) = self.meter.udp(self.inner.read_from(buf).await)?;
+        self.meter.received(n);
+        Ok((n, addr))
+    }
+
+    async fn write_to(&self, buf: &[u8], addr: &Addr) -> Result<usize> {
+        let n = self.meter.udp(self.inner.write_to(buf, addr).await)?;
+        self.meter.sent(n);
+        Ok(n)
+    }
+}
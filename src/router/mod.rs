@@ -2,6 +2,10 @@
 //!
 //! Routes connections to different outbounds based on ACL rules.
 
+mod accounting;
+
+pub use accounting::{Accounting, AccountingSnapshot, TrafficStats};
+
 use std::collections::HashMap;
 use std::fmt;
 use std::fs;
@@ -14,11 +18,13 @@ use crate::compile::{compile, CompiledRuleSet};
 use crate::error::{AclError, Result};
 use crate::geo::GeoLoader;
 use crate::outbound::{
-    build_resolve_info, try_resolve_from_ip, Addr, Direct, DirectMode, Outbound, Reject,
-    ResolveInfo, TcpConn, UdpConn,
+    build_resolve_info, try_resolve_from_ip, Addr, Direct, DirectMode, Hosts, Outbound, Reject,
+    RejectMode, ResolveInfo, TcpConn, UdpConn, DEFAULT_BLOCK_PAGE, DEFAULT_REJECT_DROP_TIMEOUT,
 };
 use crate::parser::parse_rules;
-use crate::types::Protocol;
+use crate::types::{HijackHost, HijackTarget, Protocol};
+
+use accounting::Route;
 
 #[cfg(feature = "async")]
 use crate::outbound::{AsyncOutbound, AsyncTcpConn, AsyncUdpConn};
@@ -37,6 +43,15 @@ pub const DEFAULT_CACHE_SIZE: NonZeroUsize = NonZeroUsize::new(1024).unwrap();
 pub struct RouterInner<T: ?Sized> {
     rule_set: CompiledRuleSet<Arc<T>>,
     default_outbound: Arc<T>,
+    hosts: Option<Arc<Hosts>>,
+    accounting: Option<RouteAccounting>,
+}
+
+/// Accounting handle plus the counters each rule reports to.
+struct RouteAccounting {
+    shared: Arc<Accounting>,
+    rules: Vec<Route>,
+    default: Route,
 }
 
 impl<T: ?Sized> fmt::Debug for RouterInner<T> {
@@ -44,6 +59,8 @@ impl<T: ?Sized> fmt::Debug for RouterInner<T> {
         f.debug_struct("Router")
             .field("rule_count", &self.rule_set.rule_count())
             .field("needs_ip_matching", &self.rule_set.needs_ip_matching())
+            .field("hosts", &self.hosts.as_ref().map(|h| h.len()))
+            .field("accounting", &self.accounting.is_some())
             .finish()
     }
 }
@@ -98,12 +115,24 @@ impl<T: ?Sized> OutboundEntry<T> {
 pub struct RouterOptions {
     /// LRU cache size for rule matching results
     pub cache_size: NonZeroUsize,
+    /// Static hosts table consulted before system DNS.
+    ///
+    /// Overrides are applied before rule matching, so IP rules and the
+    /// selected outbound both see the configured addresses.
+    pub hosts: Option<Arc<Hosts>>,
+    /// Traffic accounting for routed connections.
+    ///
+    /// When set, connections are tallied by outbound name and by the rule
+    /// that matched them.
+    pub accounting: Option<Arc<Accounting>>,
 }
 
 impl Default for RouterOptions {
     fn default() -> Self {
         Self {
             cache_size: DEFAULT_CACHE_SIZE,
+            hosts: None,
+            accounting: None,
         }
     }
 }
@@ -119,6 +148,18 @@ impl RouterOptions {
         self.cache_size = size;
         self
     }
+
+    /// Set the static hosts table.
+    pub fn with_hosts(mut self, hosts: Arc<Hosts>) -> Self {
+        self.hosts = Some(hosts);
+        self
+    }
+
+    /// Set the traffic accounting.
+    pub fn with_accounting(mut self, accounting: Arc<Accounting>) -> Self {
+        self.accounting = Some(accounting);
+        self
+    }
 }
 
 /// Shared implementation for both sync and async routers.
@@ -132,14 +173,32 @@ impl<T: ?Sized + DefaultOutbounds> RouterInner<T> {
         options: RouterOptions,
     ) -> Result<Self> {
         let text_rules = parse_rules(rules)?;
+        // Name the default outbound is accounted under (see `entries_to_map`)
+        let default_name = outbounds
+            .iter()
+            .find(|e| e.name.eq_ignore_ascii_case("default"))
+            .or(outbounds.first())
+            .map_or("direct", |e| e.name.as_str())
+            .to_string();
         let ob_map = entries_to_map(outbounds);
         let rule_set = compile(&text_rules, &ob_map, options.cache_size, geo_loader)?;
 
         let default_outbound = ob_map.get("default").cloned().unwrap_or_else(T::direct);
 
+        let accounting = options.accounting.map(|shared| RouteAccounting {
+            rules: text_rules
+                .iter()
+                .map(|rule| shared.route(&rule.outbound, Some(rule.to_string())))
+                .collect(),
+            default: shared.route(&default_name, None),
+            shared,
+        });
+
         Ok(Self {
             rule_set,
             default_outbound,
+            hosts: options.hosts,
+            accounting,
         })
     }
 
@@ -157,8 +216,50 @@ impl<T: ?Sized + DefaultOutbounds> RouterInner<T> {
         Self::new(&rules, outbounds, geo_loader, options)
     }
 
-    /// Match the address against ACL rules and return the outbound.
-    fn match_outbound(&self, addr: &mut Addr, proto: Protocol) -> Arc<T> {
+    /// Get the traffic accounting, if configured.
+    pub fn accounting(&self) -> Option<&Arc<Accounting>> {
+        self.accounting.as_ref().map(|a| &a.shared)
+    }
+
+    /// Counters for a connection matched by `rule` (`None`: no rule matched).
+    fn route(&self, rule: Option<usize>) -> Option<&Route> {
+        let accounting = self.accounting.as_ref()?;
+        Some(rule.map_or(&accounting.default, |i| &accounting.rules[i]))
+    }
+
+    /// Answer the address from the static hosts table, if configured.
+    fn resolve_from_hosts(&self, addr: &mut Addr) -> bool {
+        self.hosts.as_ref().is_some_and(|hosts| hosts.resolve(addr))
+    }
+
+    /// Rewrite the address to the hijack target.
+    ///
+    /// IP targets replace the resolve info directly. Name targets clear it so
+    /// the outbound resolves the new name (after the hosts table, if any).
+    fn apply_hijack(&self, addr: &mut Addr, hijack: HijackTarget) {
+        match hijack.host {
+            Some(HijackHost::Ip(ip)) => {
+                addr.host = ip.to_string();
+                addr.resolve_info = Some(match ip {
+                    IpAddr::V4(v4) => ResolveInfo::from_ipv4(v4),
+                    IpAddr::V6(v6) => ResolveInfo::from_ipv6(v6),
+                });
+            }
+            Some(HijackHost::Name(name)) => {
+                addr.host = name;
+                addr.resolve_info = None;
+                self.resolve_from_hosts(addr);
+            }
+            None => {}
+        }
+        if let Some(port) = hijack.port {
+            addr.port = port;
+        }
+    }
+
+    /// Match the address against ACL rules and return the outbound along
+    /// with the index of the matched rule.
+    fn match_outbound(&self, addr: &mut Addr, proto: Protocol) -> (Arc<T>, Option<usize>) {
         let host_info = crate::types::HostInfo {
             name: addr.host.to_lowercase(),
             ipv4: addr.resolve_info.as_ref().and_then(|i| i.ipv4),
@@ -166,20 +267,12 @@ impl<T: ?Sized + DefaultOutbounds> RouterInner<T> {
         };
 
         if let Some(result) = self.rule_set.match_host(&host_info, proto, addr.port) {
-            if let Some(hijack_ip) = result.hijack_ip {
-                addr.host = hijack_ip.to_string();
-                match hijack_ip {
-                    IpAddr::V4(v4) => {
-                        addr.resolve_info = Some(ResolveInfo::from_ipv4(v4));
-                    }
-                    IpAddr::V6(v6) => {
-                        addr.resolve_info = Some(ResolveInfo::from_ipv6(v6));
-                    }
-                }
+            if let Some(hijack) = result.hijack {
+                self.apply_hijack(addr, hijack);
             }
-            result.outbound
+            (result.outbound, Some(result.rule_index))
         } else {
-            self.default_outbound.clone()
+            (self.default_outbound.clone(), None)
         }
     }
 }
@@ -204,19 +297,27 @@ impl Router {
 
 impl Outbound for Router {
     fn dial_tcp(&self, addr: &mut Addr) -> Result<Box<dyn TcpConn>> {
-        if self.rule_set.needs_ip_matching() {
+        if !self.resolve_from_hosts(addr) && self.rule_set.needs_ip_matching() {
             self.resolve(addr);
         }
-        let outbound = self.match_outbound(addr, Protocol::TCP);
-        outbound.dial_tcp(addr)
+        let (outbound, rule) = self.match_outbound(addr, Protocol::TCP);
+        let conn = outbound.dial_tcp(addr);
+        match self.route(rule) {
+            Some(route) => route.track_tcp(conn),
+            None => conn,
+        }
     }
 
     fn dial_udp(&self, addr: &mut Addr) -> Result<Box<dyn UdpConn>> {
-        if self.rule_set.needs_ip_matching() {
+        if !self.resolve_from_hosts(addr) && self.rule_set.needs_ip_matching() {
             self.resolve(addr);
         }
-        let outbound = self.match_outbound(addr, Protocol::UDP);
-        outbound.dial_udp(addr)
+        let (outbound, rule) = self.match_outbound(addr, Protocol::UDP);
+        let conn = outbound.dial_udp(addr);
+        match self.route(rule) {
+            Some(route) => route.track_udp(conn),
+            None => conn,
+        }
     }
 }
 
@@ -247,34 +348,42 @@ impl AsyncRouter {
 #[async_trait]
 impl AsyncOutbound for AsyncRouter {
     async fn dial_tcp(&self, addr: &mut Addr) -> Result<Box<dyn AsyncTcpConn>> {
-        if self.rule_set.needs_ip_matching() {
+        if !self.resolve_from_hosts(addr) && self.rule_set.needs_ip_matching() {
             self.resolve(addr).await;
         }
-        let outbound = self.match_outbound(addr, Protocol::TCP);
-        outbound.dial_tcp(addr).await
+        let (outbound, rule) = self.match_outbound(addr, Protocol::TCP);
+        let conn = outbound.dial_tcp(addr).await;
+        match self.route(rule) {
+            Some(route) => route.track_async_tcp(conn),
+            None => conn,
+        }
     }
 
     async fn dial_udp(&self, addr: &mut Addr) -> Result<Box<dyn AsyncUdpConn>> {
-        if self.rule_set.needs_ip_matching() {
+        if !self.resolve_from_hosts(addr) && self.rule_set.needs_ip_matching() {
             self.resolve(addr).await;
         }
-        let outbound = self.match_outbound(addr, Protocol::UDP);
-        outbound.dial_udp(addr).await
+        let (outbound, rule) = self.match_outbound(addr, Protocol::UDP);
+        let conn = outbound.dial_udp(addr).await;
+        match self.route(rule) {
+            Some(route) => route.track_async_udp(conn),
+            None => conn,
+        }
     }
 }
 
 /// Trait for creating default outbound entries (Direct and Reject).
 trait DefaultOutbounds {
     fn direct() -> Arc<Self>;
-    fn reject() -> Arc<Self>;
+    fn reject(mode: RejectMode) -> Arc<Self>;
 }
 
 impl DefaultOutbounds for dyn Outbound {
     fn direct() -> Arc<Self> {
         Arc::new(Direct::with_mode(DirectMode::Auto))
     }
-    fn reject() -> Arc<Self> {
-        Arc::new(Reject::new())
+    fn reject(mode: RejectMode) -> Arc<Self> {
+        Arc::new(Reject::with_mode(mode))
     }
 }
 
@@ -283,16 +392,29 @@ impl DefaultOutbounds for dyn AsyncOutbound {
     fn direct() -> Arc<Self> {
         Arc::new(Direct::with_mode(DirectMode::Auto))
     }
-    fn reject() -> Arc<Self> {
-        Arc::new(Reject::new())
+    fn reject(mode: RejectMode) -> Arc<Self> {
+        Arc::new(Reject::with_mode(mode))
     }
 }
 
+/// Built-in reject outbounds by rule name.
+fn reject_modes() -> [(&'static str, RejectMode); 4] {
+    [
+        ("reject", RejectMode::Error),
+        ("reject-drop", RejectMode::Drop(DEFAULT_REJECT_DROP_TIMEOUT)),
+        ("reject-reset", RejectMode::Reset),
+        (
+            "reject-page",
+            RejectMode::BlockPage(DEFAULT_BLOCK_PAGE.to_string()),
+        ),
+    ]
+}
+
 /// Convert outbound entries to a map with built-in defaults.
 fn entries_to_map<T: ?Sized + DefaultOutbounds>(
     outbounds: Vec<OutboundEntry<T>>,
 ) -> HashMap<String, Arc<T>> {
-    let mut map: HashMap<String, Arc<T>> = HashMap::with_capacity(outbounds.len() + 3);
+    let mut map: HashMap<String, Arc<T>> = HashMap::with_capacity(outbounds.len() + 6);
 
     let first_outbound = outbounds.first().map(|e| e.outbound.clone());
 
@@ -303,8 +425,10 @@ fn entries_to_map<T: ?Sized + DefaultOutbounds>(
     if !map.contains_key("direct") {
         map.insert("direct".to_string(), T::direct());
     }
-    if !map.contains_key("reject") {
-        map.insert("reject".to_string(), T::reject());
+    for (name, mode) in reject_modes() {
+        if !map.contains_key(name) {
+            map.insert(name.to_string(), T::reject(mode));
+        }
     }
 
     if !map.contains_key("default") {
@@ -368,6 +492,26 @@ mod async_tests {
         }
     }
 
+    #[tokio::test]
+    async fn test_async_router_hosts_override_feeds_ip_rules() {
+        let rules = r#"
+            reject(10.0.0.0/8)
+            direct(all)
+        "#;
+
+        let hosts = Hosts::new().with_entry("internal.hosts.invalid", "10.1.2.3".parse().unwrap());
+        let outbounds: Vec<AsyncOutboundEntry> = vec![];
+        let options = RouterOptions::new().with_hosts(Arc::new(hosts));
+        let router = AsyncRouter::new(rules, outbounds, &NilGeoLoader, options).unwrap();
+
+        let mut addr = Addr::new("internal.hosts.invalid", 443);
+        let result = AsyncOutbound::dial_tcp(&router, &mut addr).await;
+        match result {
+            Err(e) => assert!(e.to_string().contains("rejected"), "got: {}", e),
+            Ok(_) => panic!("hosts override should route to reject"),
+        }
+    }
+
     #[tokio::test]
     async fn test_async_router_routes_udp() {
         let rules = r#"
@@ -412,6 +556,42 @@ mod async_tests {
             AsyncOutboundEntry::new("proxy", Arc::new(Reject::new()) as Arc<dyn AsyncOutbound>);
         assert_eq!(entry.name, "proxy");
     }
+
+    #[tokio::test]
+    async fn test_async_router_accounting() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut buf = [0u8; 4];
+            stream.read_exact(&mut buf).await.unwrap();
+            stream.write_all(&buf).await.unwrap();
+        });
+
+        let accounting = Arc::new(Accounting::new());
+        let router = AsyncRouter::new(
+            "direct(all)",
+            vec![],
+            &NilGeoLoader,
+            RouterOptions::new().with_accounting(accounting.clone()),
+        )
+        .unwrap();
+
+        let mut addr = crate::outbound::Addr::new("127.0.0.1", port);
+        let mut conn = AsyncOutbound::dial_tcp(&router, &mut addr).await.unwrap();
+        conn.write_all(b"ping").await.unwrap();
+        let mut buf = [0u8; 4];
+        conn.read_exact(&mut buf).await.unwrap();
+        server.await.unwrap();
+        drop(conn);
+
+        let stats = &accounting.snapshot().rules["direct(all)"];
+        assert_eq!(stats.connections, 1);
+        assert_eq!(stats.active, 0);
+        assert_eq!((stats.bytes_sent, stats.bytes_received), (4, 4));
+    }
 }
 
 #[cfg(test)]
@@ -432,6 +612,45 @@ mod tests {
         assert!(map.contains_key("default"));
     }
 
+    #[test]
+    fn test_router_reject_variants() {
+        use std::io::Read;
+
+        let rules = "reject-page(ads.example, tcp/80)\nreject-reset(tracker.example)\nreject(all)";
+        let router = Router::new(rules, vec![], &NilGeoLoader, RouterOptions::new()).unwrap();
+
+        let mut conn = router.dial_tcp(&mut Addr::new("ads.example", 80)).unwrap();
+        let mut response = String::new();
+        conn.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 403 Forbidden"));
+        assert!(response.ends_with(DEFAULT_BLOCK_PAGE));
+
+        let mut conn = router
+            .dial_tcp(&mut Addr::new("tracker.example", 443))
+            .unwrap();
+        assert!(conn.read(&mut [0u8; 4]).is_err());
+        assert!(router
+            .dial_tcp(&mut Addr::new("other.example", 443))
+            .is_err());
+
+        // A user-defined entry replaces the built-in block page
+        let custom = Reject::with_mode(RejectMode::BlockPage("custom".to_string()));
+        let router = Router::new(
+            rules,
+            vec![OutboundEntry::new(
+                "reject-page",
+                Arc::new(custom) as Arc<dyn Outbound>,
+            )],
+            &NilGeoLoader,
+            RouterOptions::new(),
+        )
+        .unwrap();
+        let mut conn = router.dial_tcp(&mut Addr::new("ads.example", 80)).unwrap();
+        let mut response = String::new();
+        conn.read_to_string(&mut response).unwrap();
+        assert!(response.ends_with("\r\n\r\ncustom"));
+    }
+
     #[test]
     fn test_entries_to_map_empty_outbounds_no_panic() {
         // Empty outbounds should safely produce defaults without panicking.
@@ -485,6 +704,121 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_router_hosts_override_feeds_rules_and_dial() {
+        // The hosts table must be applied before matching so IP rules see it,
+        // and the resolved address must reach the outbound that dials.
+        use std::net::TcpListener;
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let handle = std::thread::spawn(move || {
+            let _ = listener.accept();
+        });
+
+        let rules = r#"
+            direct(127.0.0.0/8)
+            reject(all)
+        "#;
+        let hosts = Hosts::parse("127.0.0.1 suffix:hosts.invalid").unwrap();
+        let options = RouterOptions::new().with_hosts(Arc::new(hosts));
+        let router = Router::new(rules, vec![], &NilGeoLoader, options).unwrap();
+
+        let mut addr = Addr::new("svc.hosts.invalid", port);
+        let conn = router.dial_tcp(&mut addr).expect("should route to direct");
+        assert_eq!(conn.peer_addr().unwrap().port(), port);
+        drop(conn);
+        let _ = handle.join();
+
+        // Names outside the table still fall through to the rules
+        let mut addr = Addr::new("other.invalid", port);
+        assert!(router.dial_tcp(&mut addr).is_err());
+    }
+
+    #[test]
+    fn test_router_hosts_override_without_ip_rules() {
+        // Even with domain-only rules, dialing should see the override.
+        let hosts = Hosts::new().with_entry("a.hosts.invalid", "192.0.2.7".parse().unwrap());
+        let options = RouterOptions::new().with_hosts(Arc::new(hosts));
+        let router = Router::new("reject(all)", vec![], &NilGeoLoader, options).unwrap();
+        assert!(!router.rule_set.needs_ip_matching());
+
+        let mut addr = Addr::new("a.hosts.invalid", 80);
+        assert!(router.dial_tcp(&mut addr).is_err());
+        assert_eq!(
+            addr.resolve_info().unwrap().ipv4,
+            Some("192.0.2.7".parse().unwrap())
+        );
+    }
+
+    #[test]
+    fn test_router_hijack_rewrites_port_and_host() {
+        use std::net::TcpListener;
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let handle = std::thread::spawn(move || {
+            for _ in 0..3 {
+                let _ = listener.accept();
+            }
+        });
+
+        let rules = format!(
+            "direct(ip.hijack.invalid, tcp/1, 127.0.0.1:{port})\n\
+             direct(127.0.0.1, tcp/2, :{port})\n\
+             direct(name.hijack.invalid, tcp/3, Mirror.hijack.invalid:{port})\n\
+             reject(all)"
+        );
+        let hosts = Hosts::new().with_entry("mirror.hijack.invalid", "127.0.0.1".parse().unwrap());
+        let options = RouterOptions::new().with_hosts(Arc::new(hosts));
+        let router = Router::new(&rules, vec![], &NilGeoLoader, options).unwrap();
+
+        // ip:port
+        let mut addr = Addr::new("ip.hijack.invalid", 1);
+        let conn = router.dial_tcp(&mut addr).unwrap();
+        assert_eq!(conn.peer_addr().unwrap().port(), port);
+        assert_eq!(addr.host(), "127.0.0.1");
+        assert_eq!(addr.port(), port);
+
+        // :port keeps the original host
+        let mut addr = Addr::new("127.0.0.1", 2);
+        let conn2 = router.dial_tcp(&mut addr).unwrap();
+        assert_eq!(addr.host(), "127.0.0.1");
+        assert_eq!(addr.port(), port);
+
+        // hostname:port resolves the new name (here via the hosts table)
+        let mut addr = Addr::new("name.hijack.invalid", 3)
+            .with_resolve_info(ResolveInfo::from_ipv4("192.0.2.1".parse().unwrap()));
+        let conn3 = router.dial_tcp(&mut addr).unwrap();
+        assert_eq!(addr.host(), "mirror.hijack.invalid");
+        assert_eq!(addr.port(), port);
+        assert_eq!(
+            addr.resolve_info().unwrap().ipv4,
+            Some("127.0.0.1".parse().unwrap())
+        );
+
+        drop((conn, conn2, conn3));
+        let _ = handle.join();
+    }
+
+    #[test]
+    fn test_router_hijack_to_name_clears_resolve_info() {
+        let router = Router::new(
+            "reject(all, tcp/80, mirror.hijack.invalid)",
+            vec![],
+            &NilGeoLoader,
+            RouterOptions::new(),
+        )
+        .unwrap();
+
+        let mut addr = Addr::new("origin.invalid", 80)
+            .with_resolve_info(ResolveInfo::from_ipv4("192.0.2.1".parse().unwrap()));
+        assert!(router.dial_tcp(&mut addr).is_err());
+        assert_eq!(addr.host(), "mirror.hijack.invalid");
+        assert_eq!(addr.port(), 80);
+        assert!(addr.resolve_info().is_none());
+    }
+
     #[test]
     fn test_router_needs_dns_for_ip_rules() {
         // When rules include IP/CIDR matchers, needs_ip_matching() should be true
@@ -578,4 +912,83 @@ mod tests {
         let cloned = options.clone();
         assert_eq!(cloned.cache_size, DEFAULT_CACHE_SIZE);
     }
+
+    #[test]
+    fn test_router_accounting() {
+        use std::io::{Read, Write};
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut buf = [0u8; 4];
+            stream.read_exact(&mut buf).unwrap();
+            stream.write_all(b"pong!").unwrap();
+        });
+
+        let accounting = Arc::new(Accounting::new());
+        let router = Router::new(
+            "reject(blocked.example)\nproxy(127.0.0.1)",
+            vec![OutboundEntry::new(
+                "Proxy",
+                Arc::new(Direct::new()) as Arc<dyn Outbound>,
+            )],
+            &NilGeoLoader,
+            RouterOptions::new().with_accounting(accounting.clone()),
+        )
+        .unwrap();
+        assert!(Arc::ptr_eq(router.accounting().unwrap(), &accounting));
+
+        let mut conn = router.dial_tcp(&mut Addr::new("127.0.0.1", port)).unwrap();
+        conn.write_all(b"ping").unwrap();
+        let mut buf = [0u8; 5];
+        conn.read_exact(&mut buf).unwrap();
+        let clone = conn.try_clone().unwrap();
+        drop(conn);
+        assert_eq!(accounting.snapshot().outbounds["proxy"].active, 1);
+        drop(clone);
+        server.join().unwrap();
+
+        assert!(router
+            .dial_tcp(&mut Addr::new("blocked.example", 80))
+            .is_err());
+
+        let snapshot = accounting.snapshot();
+        let proxy = &snapshot.outbounds["proxy"];
+        assert_eq!(proxy.connections, 1);
+        assert_eq!(proxy.active, 0);
+        assert_eq!(proxy.bytes_sent, 4);
+        assert_eq!(proxy.bytes_received, 5);
+        assert!(proxy.duration > std::time::Duration::ZERO);
+        assert_eq!(snapshot.rules["proxy(127.0.0.1)"], *proxy);
+        assert_eq!(snapshot.outbounds["reject"].errors, 1);
+        assert_eq!(snapshot.rules["reject(blocked.example)"].errors, 1);
+
+        accounting.reset();
+        assert_eq!(
+            accounting.snapshot().outbounds["proxy"],
+            TrafficStats::default()
+        );
+    }
+
+    #[test]
+    fn test_router_accounting_unmatched() {
+        let accounting = Arc::new(Accounting::new());
+        let router = Router::new(
+            "direct(*.example.com)",
+            vec![OutboundEntry::new(
+                "blocked",
+                Arc::new(Reject::new()) as Arc<dyn Outbound>,
+            )],
+            &NilGeoLoader,
+            RouterOptions::new().with_accounting(accounting.clone()),
+        )
+        .unwrap();
+
+        // No rule matches: counted toward the default outbound only
+        assert!(router.dial_udp(&mut Addr::new("other.org", 53)).is_err());
+        let snapshot = accounting.snapshot();
+        assert_eq!(snapshot.outbounds["blocked"].errors, 1);
+        assert_eq!(snapshot.rules["direct(*.example.com)"].errors, 0);
+    }
 }
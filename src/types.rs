@@ -1,3 +1,4 @@
+use std::fmt;
 use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
 
 /// Network protocol type
@@ -84,19 +85,83 @@ pub struct TextRule {
     pub address: String,
     /// Protocol/port specification (e.g., "tcp/443", "udp/53", "*/80-90")
     pub proto_port: Option<String>,
-    /// Hijack address
+    /// Hijack address (e.g., "127.0.0.1", "127.0.0.1:5353", ":5353", "mirror.local:8080")
     pub hijack_address: Option<String>,
     /// Line number in the original text (for error reporting)
     pub line_num: usize,
 }
 
+impl fmt::Display for TextRule {
+    /// Formats the rule in its text form, e.g. `direct(all, udp/53, 127.0.0.1)`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}({}", self.outbound, self.address)?;
+        for part in [&self.proto_port, &self.hijack_address]
+            .into_iter()
+            .flatten()
+        {
+            write!(f, ", {}", part)?;
+        }
+        f.write_str(")")
+    }
+}
+
+/// Host part of a hijack target
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HijackHost {
+    /// Rewrite to a literal IP address
+    Ip(IpAddr),
+    /// Rewrite to a hostname (lowercased); resolved again before dialing
+    Name(String),
+}
+
+/// Parsed hijack target: `ip`, `ip:port`, `[v6]:port`, `:port`, `hostname[:port]`.
+///
+/// A missing host keeps the original destination host; a missing port keeps
+/// the original destination port.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HijackTarget {
+    /// Replacement host, if any
+    pub host: Option<HijackHost>,
+    /// Replacement port, if any
+    pub port: Option<u16>,
+}
+
+impl HijackTarget {
+    /// The replacement IP address, if the target host is an IP literal.
+    pub fn ip(&self) -> Option<IpAddr> {
+        match self.host {
+            Some(HijackHost::Ip(ip)) => Some(ip),
+            _ => None,
+        }
+    }
+}
+
 /// Match result from the ACL engine
 #[derive(Debug, Clone)]
 pub struct MatchResult<O> {
     /// The matched outbound
     pub outbound: O,
-    /// Hijack IP address (if any)
-    pub hijack_ip: Option<IpAddr>,
+    /// Hijack target (if any)
+    pub(crate) hijack: Option<HijackTarget>,
+    /// Index of the matched rule in the rule set
+    pub(crate) rule_index: usize,
+}
+
+impl<O> MatchResult<O> {
+    /// Hijack target (host and/or port rewrite), if any.
+    pub fn hijack(&self) -> Option<&HijackTarget> {
+        self.hijack.as_ref()
+    }
+
+    /// Hijack IP address, set when the hijack target host is an IP literal.
+    pub fn hijack_ip(&self) -> Option<IpAddr> {
+        self.hijack.as_ref().and_then(HijackTarget::ip)
+    }
+
+    /// Index of the matched rule in the rule set.
+    pub fn rule_index(&self) -> usize {
+        self.rule_index
+    }
 }
 
 /// Cache key for LRU cache.